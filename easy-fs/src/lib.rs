@@ -0,0 +1,25 @@
+//! A simple inode based filesystem, all the files live in the root directory.
+//!
+//! The disk is laid out as: super block | inode bitmap | inode area | data bitmap | data area.
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+pub const BLOCK_SZ: usize = 512;
+
+use bitmap::Bitmap;
+pub use block_cache::block_cache_invalidate;
+use block_cache::{block_cache_sync_all, get_block_cache};
+pub use block_dev::BlockDevice;
+pub use efs::EasyFileSystem;
+use layout::*;
+pub use layout::{MAX_FILE_SIZE, NAME_LIMIT};
+pub use vfs::Inode;
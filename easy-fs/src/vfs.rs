@@ -0,0 +1,317 @@
+use super::{
+    block_cache_sync_all, get_block_cache, BlockDevice, DirEntry, DiskInode, DiskInodeType,
+    EasyFileSystem, BLOCK_SZ, DIRENT_SZ, MAX_FILE_SIZE, NAME_LIMIT,
+};
+use alloc::string::String;
+use core::ops::Range;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+/// Virtual filesystem layer over easy-fs
+pub struct Inode {
+    inode_id: u32,
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    /// Create a vfs inode, it must have been recorded by `EasyFileSystem::open_inode`
+    pub(crate) fn new(
+        inode_id: u32,
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            inode_id,
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    /// Find the slot of `name` in the directory, return (slot, inode id)
+    fn find_dirent(&self, name: &str, disk_inode: &DiskInode) -> Option<(usize, u32)> {
+        // assert it is a directory
+        assert!(disk_inode.is_dir());
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            assert_eq!(
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device),
+                DIRENT_SZ,
+            );
+            // the slots of the unlinked files have an empty name
+            if !dirent.name().is_empty() && dirent.name() == name {
+                return Some((i, dirent.inode_number()));
+            }
+        }
+        None
+    }
+
+    fn find_free_slot(&self, disk_inode: &DiskInode) -> Option<usize> {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        (0..file_count).find(|&i| {
+            disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device);
+            dirent.name().is_empty()
+        })
+    }
+
+    fn inode_from_id(&self, fs: &mut EasyFileSystem, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        fs.open_inode(inode_id);
+        Arc::new(Self::new(
+            inode_id,
+            block_id,
+            block_offset,
+            Arc::clone(&self.fs),
+            Arc::clone(&self.block_device),
+        ))
+    }
+
+    /// Find inode under current inode by name
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let mut fs = self.fs.lock();
+        let (_, inode_id) = self.read_disk_inode(|disk_inode| self.find_dirent(name, disk_inode))?;
+        Some(self.inode_from_id(&mut fs, inode_id))
+    }
+
+    /// Increase the size of a disk inode, `new_size` must not exceed `MAX_FILE_SIZE`.
+    /// Nothing is changed and None is returned if the disk is full.
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> Option<()> {
+        if new_size < disk_inode.size {
+            return Some(());
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut v: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => v.push(block_id),
+                None => {
+                    for block_id in v {
+                        fs.dealloc_data(block_id);
+                    }
+                    return None;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, v, &self.block_device);
+        Some(())
+    }
+
+    /// Create inode under current inode by name,
+    /// None if the name exists or is too long, or there is no space left
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        if name.is_empty() || name.len() > NAME_LIMIT {
+            return None;
+        }
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|root_inode| self.find_dirent(name, root_inode))
+            .is_some()
+        {
+            return None;
+        }
+        // create a new file
+        // alloc a inode with an indirect block
+        let new_inode_id = fs.alloc_inode()?;
+        // initialize inode
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(DiskInodeType::File);
+            });
+        let linked = self.modify_disk_inode(|root_inode| {
+            // reuse the slot of an unlinked file, or append a new one
+            let slot = match self.find_free_slot(root_inode) {
+                Some(slot) => slot,
+                None => {
+                    let file_count = (root_inode.size as usize) / DIRENT_SZ;
+                    let new_size = (file_count + 1) * DIRENT_SZ;
+                    if new_size > MAX_FILE_SIZE {
+                        return None;
+                    }
+                    self.increase_size(new_size as u32, root_inode, &mut fs)?;
+                    file_count
+                }
+            };
+            // write dirent
+            let dirent = DirEntry::new(name, new_inode_id);
+            root_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            Some(())
+        });
+        if linked.is_none() {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
+
+        block_cache_sync_all();
+        Some(self.inode_from_id(&mut fs, new_inode_id))
+    }
+
+    /// Remove `name` from the directory and free its inode and data blocks.
+    /// A file that is still open is freed once its last `Inode` is dropped.
+    pub fn unlink(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let (slot, inode_id) = match self.read_disk_inode(|root_inode| self.find_dirent(name, root_inode)) {
+            Some(dirent) => dirent,
+            None => return false,
+        };
+        self.modify_disk_inode(|root_inode| {
+            root_inode.write_at(slot * DIRENT_SZ, DirEntry::empty().as_bytes(), &self.block_device);
+        });
+        if !fs.orphan_inode(inode_id) {
+            free_inode(&mut fs, inode_id);
+        }
+        block_cache_sync_all();
+        true
+    }
+
+    /// List inodes under current inode
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ,
+                );
+                if !dirent.name().is_empty() {
+                    v.push(String::from(dirent.name()));
+                }
+            }
+            v
+        })
+    }
+
+    /// Read data from current inode
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// Write data to current inode, the file grows if needed.
+    /// The data past `MAX_FILE_SIZE` is not written, None if there is no space left.
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> Option<usize> {
+        let end = offset.saturating_add(buf.len()).min(MAX_FILE_SIZE);
+        if offset >= end {
+            return Some(0);
+        }
+        let buf = &buf[..end - offset];
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            self.increase_size(end as u32, disk_inode, &mut fs)?;
+            Some(disk_inode.write_at(offset, buf, &self.block_device))
+        });
+        block_cache_sync_all();
+        size
+    }
+
+    /// Grow the file to `new_size` bytes, nothing happens if it is already larger.
+    /// None if `new_size` exceeds `MAX_FILE_SIZE` or there is no space left.
+    pub fn grow(&self, new_size: usize) -> Option<()> {
+        if new_size > MAX_FILE_SIZE {
+            return None;
+        }
+        let mut fs = self.fs.lock();
+        let res = self.modify_disk_inode(|disk_inode| {
+            self.increase_size(new_size as u32, disk_inode, &mut fs)
+        });
+        block_cache_sync_all();
+        res
+    }
+
+    /// The data blocks holding `[offset, offset + len)` of the file, clipped to the file size.
+    /// Each item is a block id and the range inside that block, in file order.
+    pub fn block_ranges(&self, offset: usize, len: usize) -> Vec<(usize, Range<usize>)> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let end = offset.saturating_add(len).min(disk_inode.size as usize);
+            let mut ranges = Vec::new();
+            let mut start = offset;
+            while start < end {
+                let inner_id = start / BLOCK_SZ;
+                let block_end = ((inner_id + 1) * BLOCK_SZ).min(end);
+                let block_id = disk_inode.get_block_id(inner_id as u32, &self.block_device);
+                ranges.push((block_id as usize, start % BLOCK_SZ..(block_end - 1) % BLOCK_SZ + 1));
+                start = block_end;
+            }
+            ranges
+        })
+    }
+
+    /// Size of the file in bytes
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// Clear the data in current inode
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            for data_block in data_blocks_dealloc.into_iter() {
+                fs.dealloc_data(data_block);
+            }
+        });
+        block_cache_sync_all();
+    }
+}
+
+impl Drop for Inode {
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        if fs.close_inode(self.inode_id) {
+            free_inode(&mut fs, self.inode_id);
+            block_cache_sync_all();
+        }
+    }
+}
+
+/// Free the data blocks and the inode of an unlinked file.
+fn free_inode(fs: &mut EasyFileSystem, inode_id: u32) {
+    let block_device = Arc::clone(&fs.block_device);
+    let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+    let data_blocks_dealloc = get_block_cache(block_id as usize, Arc::clone(&block_device))
+        .lock()
+        .modify(block_offset, |disk_inode: &mut DiskInode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&block_device);
+            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            data_blocks_dealloc
+        });
+    for data_block in data_blocks_dealloc.into_iter() {
+        fs.dealloc_data(data_block);
+    }
+    fs.dealloc_inode(inode_id);
+}
@@ -0,0 +1,92 @@
+use easy_fs::{BlockDevice, EasyFileSystem, Inode, BLOCK_SZ, MAX_FILE_SIZE};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+// one inode bitmap block needs 1024 blocks of inodes, 73 data blocks are left
+const TOTAL_BLOCKS: u32 = 1100;
+const DATA_BLOCKS: usize = 73;
+
+struct MemDisk(Mutex<Vec<[u8; BLOCK_SZ]>>);
+
+impl BlockDevice for MemDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.0.lock().unwrap()[block_id]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.0.lock().unwrap()[block_id].copy_from_slice(buf);
+    }
+}
+
+/// The block cache is global and keyed by the block id only, so the tests share one disk
+/// and run one at a time, each of them formats it again.
+fn fresh_fs() -> (MutexGuard<'static, ()>, Inode) {
+    static LOCK: Mutex<()> = Mutex::new(());
+    static DISK: OnceLock<Arc<MemDisk>> = OnceLock::new();
+    let guard = LOCK.lock().unwrap_or_else(|err| err.into_inner());
+    let disk = DISK.get_or_init(|| {
+        Arc::new(MemDisk(Mutex::new(vec![[0; BLOCK_SZ]; TOTAL_BLOCKS as usize])))
+    });
+    let efs = EasyFileSystem::create(disk.clone(), TOTAL_BLOCKS, 1);
+    (guard, EasyFileSystem::root_inode(&efs))
+}
+
+#[test]
+fn write_read_unlink() {
+    let (_guard, root) = fresh_fs();
+    let file = root.create("hello").unwrap();
+    assert!(root.create("hello").is_none());
+    let data: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+    assert_eq!(file.write_at(100, &data), Some(data.len()));
+    assert_eq!(file.size(), 3100);
+    let mut buf = vec![0; data.len()];
+    assert_eq!(root.find("hello").unwrap().read_at(100, &mut buf), data.len());
+    assert_eq!(buf, data);
+    assert_eq!(root.ls(), vec!["hello"]);
+    assert!(root.unlink("hello"));
+    assert!(!root.unlink("hello"));
+    assert!(root.find("hello").is_none());
+    assert!(root.ls().is_empty());
+}
+
+#[test]
+fn disk_full() {
+    let (_guard, root) = fresh_fs();
+    let file = root.create("big").unwrap();
+    let too_big = vec![1u8; (DATA_BLOCKS + 1) * BLOCK_SZ];
+    assert_eq!(file.write_at(0, &too_big), None);
+    // the blocks taken by the failed write are given back
+    assert_eq!(file.size(), 0);
+    let fits = vec![2u8; 20 * BLOCK_SZ];
+    assert_eq!(file.write_at(0, &fits), Some(fits.len()));
+    assert_eq!(file.grow(DATA_BLOCKS * BLOCK_SZ), None);
+    assert_eq!(file.size(), fits.len());
+}
+
+#[test]
+fn size_limit() {
+    let (_guard, root) = fresh_fs();
+    let file = root.create("limit").unwrap();
+    let buf = [0u8; 16];
+    assert_eq!(file.write_at(MAX_FILE_SIZE, &buf), Some(0));
+    assert_eq!(file.write_at(usize::MAX - 4, &buf), Some(0));
+    assert_eq!(file.grow(MAX_FILE_SIZE + 1), None);
+    assert_eq!(file.size(), 0);
+    assert!(file.block_ranges(usize::MAX - 4, 16).is_empty());
+}
+
+#[test]
+fn unlink_while_open() {
+    let (_guard, root) = fresh_fs();
+    let file = root.create("open").unwrap();
+    let data = vec![3u8; 40 * BLOCK_SZ];
+    assert_eq!(file.write_at(0, &data), Some(data.len()));
+    assert!(root.unlink("open"));
+    // the data stays readable and keeps its blocks until the file is closed
+    let mut buf = vec![0; data.len()];
+    assert_eq!(file.read_at(0, &mut buf), data.len());
+    assert_eq!(buf, data);
+    let other = root.create("other").unwrap();
+    assert_eq!(other.write_at(0, &data), None);
+    drop(file);
+    assert_eq!(other.write_at(0, &data), Some(data.len()));
+}
@@ -8,11 +8,9 @@
 extern crate lib_so;
 extern crate alloc;
 
-use lib_so::config::{ENTRY, MAX_THREAD_NUM, MAX_PROC_NUM, HEAP_BUFFER};
+use lib_so::config::{ENTRY, MAX_THREAD_NUM, HEAP_BUFFER};
 use spin::Mutex;
-use core::sync::atomic::Ordering;
-use core::sync::atomic::AtomicUsize;
-use lib_so::{Executor, CoroutineId, CoroutineKind};
+use lib_so::{Executor, CoroutineId, CoroutineKind, PrioBitmap};
 use alloc::boxed::Box;
 use core::pin::Pin;
 use core::future::Future;
@@ -23,7 +21,7 @@ type LockedHeap = Mutex<Heap>;
 
 
 // 自定义的模块接口，模块添加进地址空间之后，需要执行 _start() 函数填充这个接口表
-static mut INTERFACE: [usize; 10] = [0; 10];
+static mut INTERFACE: [usize; 11] = [0; 11];
 
 #[no_mangle]
 fn main() -> usize{
@@ -38,18 +36,19 @@ fn main() -> usize{
         INTERFACE[7] = add_virtual_core as usize;
         INTERFACE[8] = update_prio as usize;
         INTERFACE[9] = get_pending_status as usize;
-        &INTERFACE as *const [usize; 10] as usize
+        INTERFACE[10] = cancel as usize;
+        &INTERFACE as *const [usize; 11] as usize
     }
 }
 
 /// sret 进入用户态的入口，在这个函数再执行 main 函数
+/// argc、argv、envp 由内核在 exec 和 spawn 时放在 a0、a1、a2 中，按 C ABI 原样转交给用户程序的 _start
 #[no_mangle]
 #[inline(never)]
-fn user_entry() {
+extern "C" fn user_entry(argc: usize, argv: usize, envp: usize) {
     unsafe {
-        let secondary_init: fn(usize) = core::mem::transmute(ENTRY);
-        // main_addr 表示用户进程 main 函数的地址
-        secondary_init(&INTERFACE as *const [usize; 10] as usize);
+        let secondary_init: extern "C" fn(usize, usize, usize) = core::mem::transmute(ENTRY);
+        secondary_init(argc, argv, envp);
     }
     let start = get_time();
 
@@ -63,38 +62,22 @@ fn user_entry() {
 }
 
 
-/// 各个进程的最高优先级协程，通过共享内存的形式进行通信
-pub static mut PRIO_ARRAY: [AtomicUsize; MAX_PROC_NUM + 1] = [const { AtomicUsize::new(usize::MAX) }; MAX_PROC_NUM + 1];
+/// 各个进程的最高优先级协程，通过共享内存的形式进行通信，按照优先级分层记录在位图中
+pub static PRIO_BITMAP: PrioBitmap = PrioBitmap::new();
 
 /// 进程的 Executor 调用这个函数，通过原子操作更新自己的最高优先级
 #[no_mangle]
 #[inline(never)]
 pub fn update_prio(idx: usize, prio: usize) {
-    unsafe {
-        PRIO_ARRAY[idx].store(prio, Ordering::Relaxed);
-    }
+    PRIO_BITMAP.update(idx, prio);
 }
 
 /// 内核重新调度进程时，调用这个函数，选出优先级最高的进程，再选出对应的线程
-/// 所有进程的优先级相同时，则内核会优先执行协程，这里用 0 来表示内核的优先级
+/// 所有进程的优先级相同时，选择编号最小的进程；内核的 0 号不参与选择
 #[no_mangle]
 #[inline(never)]
 pub fn max_prio_pid() -> usize {
-    let mut ret;
-    let mut pid = 1;
-    unsafe {
-        ret = PRIO_ARRAY[1].load(Ordering::Relaxed);
-    }
-    for i in 1..MAX_PROC_NUM {
-        unsafe {
-            let prio = PRIO_ARRAY[i].load(Ordering::Relaxed);
-            if prio < ret {
-                ret = prio;
-                pid = i;
-            }
-        }
-    }
-    pid
+    PRIO_BITMAP.first().unwrap_or(1)
 }
 
 
@@ -105,7 +88,7 @@ pub fn spawn(future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>, pr
     unsafe {
         let heapptr = *(HEAP_BUFFER as *const usize);
         let exe = (heapptr + core::mem::size_of::<LockedHeap>()) as *mut usize as *mut Executor;
-        let cid = (*exe).spawn(future, prio, kind);
+        let cid = (*exe).spawn(future, prio, pid, kind, re_back);
         // 更新优先级标记
         let prio = (*exe).priority;
         update_prio(pid, prio);
@@ -140,7 +123,11 @@ pub fn poll_user_future() {
                     // println!("user task kind {:?}", task.kind);
                     match task.execute() {
                         Poll::Pending => {
-                            (*exe).pending(cid.0);
+                            // 执行过程中被取消的协程可能又向内核注册了异步操作，丢弃之前一并取消
+                            if let Some(cancelled) = (*exe).pending(cid.0) {
+                                cancel_async(cid.0);
+                                drop(cancelled);
+                            }
                         }
                         Poll::Ready(()) => {
                             (*exe).del_coroutine(cid);
@@ -229,22 +216,24 @@ pub fn current_cid(is_kernel: bool) -> usize {
     }
 }
 
-/// 协程重新入队，手动执行唤醒的过程，内核和用户都会调用这个函数
+/// 协程重新入队，手动执行唤醒的过程，内核和用户都会调用这个函数，也是协程 waker 的唤醒函数
+/// 只能唤醒当前地址空间中的协程，在内核中唤醒用户协程时交给内核设置的 user_wake_hook 处理
 #[no_mangle]
 #[inline(never)]
 pub fn re_back(cid: usize, pid: usize) {
     // println!("[Exec]re back func enter");
-    let mut start = 0;
-    
     unsafe {
         let heapptr = *(HEAP_BUFFER as *const usize);
         let exe = (heapptr + core::mem::size_of::<LockedHeap>()) as *mut usize as *mut Executor;
+        if pid != 0 {
+            if let Some(hook) = (*exe).user_wake_hook {
+                hook(cid, pid);
+                return;
+            }
+        }
         let prio = (*exe).re_back(CoroutineId(cid));
         // 重新入队之后，需要检查优先级
-        let process_prio = PRIO_ARRAY[pid].load(Ordering::Relaxed);
-        if prio < process_prio {
-            PRIO_ARRAY[pid].store(prio, Ordering::Relaxed);
-        }
+        PRIO_BITMAP.raise(pid, prio);
     }
 }
 
@@ -258,6 +247,22 @@ pub fn get_pending_status(cid: usize) -> bool {
     }
 }
 
+/// 取消协程，协程的 future 被丢弃，返回协程是否存在，内核和用户都会调用这个函数
+#[no_mangle]
+#[inline(never)]
+pub fn cancel(cid: usize, pid: usize) -> bool {
+    unsafe {
+        let heapptr = *(HEAP_BUFFER as *const usize);
+        let exe = (heapptr + core::mem::size_of::<LockedHeap>()) as *mut usize as *mut Executor;
+        let (found, task) = (*exe).cancel(CoroutineId(cid));
+        // 在 Executor 的锁之外丢弃 future
+        drop(task);
+        let _lock = (*exe).wr_lock.lock();
+        update_prio(pid, (*exe).priority);
+        found
+    }
+}
+
 /// 更新协程优先级
 #[no_mangle]
 #[inline(never)]
@@ -287,6 +292,7 @@ pub fn wait_other_cores() {
         let heapptr = *(HEAP_BUFFER as *const usize);
         let exe = (heapptr + core::mem::size_of::<LockedHeap>()) as *mut usize as *mut Executor;
         for tid in (*exe).waits.iter() {
+            // 阻塞直到线程退出
             waittid(*tid);
         }
     }
@@ -50,16 +50,20 @@ pub fn init_spawn(ptr:usize){
     VDSO_SPAWN = ptr;
   }
 }
+/// 添加协程，返回的 JoinHandle 可以用来等待协程的返回值
 #[inline(never)]
-pub fn spawn<F, T>(f: F, prio: usize, pid: usize, kind: CoroutineKind) -> usize 
+pub fn spawn<F, T>(f: F, prio: usize, pid: usize, kind: CoroutineKind) -> JoinHandle<T::Output>
 where 
     F: FnOnce() -> T,
-    T: Future<Output = ()> + 'static + Send + Sync
+    T: Future + 'static + Send + Sync,
+    T::Output: Send + 'static,
 {
+  let (task, mut handle) = JoinHandle::wrap(f());
   unsafe {
     let func:fn(f:Pin<Box<dyn Future<Output = ()> +'static+Send+Sync> > ,prio:usize,pid:usize,kind:CoroutineKind) -> usize = core::mem::transmute(VDSO_SPAWN);
-    func(Box::pin(f()),prio,pid,kind)
+    handle.set_cid(func(Box::pin(task),prio,pid,kind));
   }
+  handle
 }
 
 get_libfn!(
@@ -94,4 +98,8 @@ get_libfn!(
   pub fn get_pending_status(cid: usize) -> bool {}
 );
 
+get_libfn!(
+    pub fn cancel(cid: usize, pid: usize) -> bool {}
+);
+
 
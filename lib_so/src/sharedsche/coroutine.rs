@@ -32,19 +32,30 @@ impl CoroutineId {
     } 
 }
 
-/// 协程 waker，在这里只提供一个上下文
-struct CoroutineWaker(CoroutineId);
+/// 唤醒函数，参数为协程 Id 和协程所属进程在优先级数组中的下标，由共享调度器的 `re_back` 提供
+pub type WakeFn = fn(cid: usize, pid: usize);
+
+/// 协程 waker，记录协程 Id 和所属进程，唤醒时调用共享调度器的 `re_back` 将协程重新入队
+struct CoroutineWaker {
+    cid: CoroutineId,
+    pid: usize,
+    wake: WakeFn,
+}
 
 impl CoroutineWaker {
     /// 新建协程 waker
-    pub fn new(cid: CoroutineId) -> Waker {
-        Waker::from(Arc::new(Self(cid)))
+    pub fn new(cid: CoroutineId, pid: usize, wake: WakeFn) -> Waker {
+        Waker::from(Arc::new(Self { cid, pid, wake }))
     }
 }
 
 impl Wake for CoroutineWaker {
-    fn wake(self: Arc<Self>) { }
-    fn wake_by_ref(self: &Arc<Self>) { }
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.wake)(self.cid.0, self.pid);
+    }
 }
 
 
@@ -77,8 +88,8 @@ pub struct CoroutineInner {
 }
 
 impl Coroutine {
-    /// 生成协程
-    pub fn new(future: Pin<Box<dyn Future<Output=()> + Send + Sync>>, prio: usize, kind: CoroutineKind) -> Arc<Self> {
+    /// 生成协程，pid 为协程所属进程在优先级数组中的下标，内核为 0
+    pub fn new(future: Pin<Box<dyn Future<Output=()> + Send + Sync>>, prio: usize, pid: usize, kind: CoroutineKind, wake: WakeFn) -> Arc<Self> {
         let cid = CoroutineId::generate();
         Arc::new(
             Coroutine {
@@ -87,7 +98,7 @@ impl Coroutine {
                 inner: Mutex::new(CoroutineInner {
                     future,
                     prio,
-                    waker: Arc::new(CoroutineWaker::new(cid)),
+                    waker: Arc::new(CoroutineWaker::new(cid, pid, wake)),
                 })
                 
             }
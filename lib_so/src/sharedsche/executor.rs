@@ -4,7 +4,7 @@ use alloc::vec::Vec;
 use spin::Mutex;
 use syscall::yield_;
 use super::{
-    coroutine::{Coroutine, CoroutineId, CoroutineKind},
+    coroutine::{Coroutine, CoroutineId, CoroutineKind, WakeFn},
     BitMap,
 };
 use alloc::boxed::Box;
@@ -48,6 +48,10 @@ pub struct Executor {
     pub ready_queue: Vec<VecDeque<CoroutineId>>,
     /// 阻塞协程集合
     pub pending_set: BTreeSet<usize>,
+    /// 执行过程中被唤醒的协程集合，返回 Pending 时直接重新入队
+    pub woken_set: BTreeSet<usize>,
+    /// 执行过程中被取消的协程集合，返回 Pending 时被删除
+    pub cancelled_set: BTreeSet<usize>,
     /// 协程优先级位图
     pub bitmap: BitMap,
     /// 进程最高优先级协程代表的优先级，内核可以直接访问物理地址来读取
@@ -56,6 +60,8 @@ pub struct Executor {
     pub wr_lock: ExMutex,
     /// 执行器线程id
     pub waits: Vec<usize>,
+    /// 只在内核的 Executor 中设置，在内核中唤醒用户协程时调用，由内核通知对应的进程
+    pub user_wake_hook: Option<WakeFn>,
 }
 
 impl Executor {
@@ -66,10 +72,13 @@ impl Executor {
             tasks: BTreeMap::new(),
             ready_queue: Vec::new(),
             pending_set: BTreeSet::new(),
+            woken_set: BTreeSet::new(),
+            cancelled_set: BTreeSet::new(),
             bitmap: BitMap(0),
             priority: PRIO_NUM,
             wr_lock: ExMutex::new(busy_wait),
             waits: Vec::new(),
+            user_wake_hook: None,
         }
     }
 }
@@ -93,9 +102,9 @@ impl Executor {
         self.bitmap.update(prio, true);
         self.priority = self.bitmap.get_priority();
     }
-    /// 添加协程
-    pub fn spawn(&mut self, future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>, prio: usize, kind: CoroutineKind) -> usize {
-        let task = Coroutine::new(future, prio, kind);
+    /// 添加协程，pid 和 wake 用于构造协程的 waker
+    pub fn spawn(&mut self, future: Pin<Box<dyn Future<Output=()> + 'static + Send + Sync>>, prio: usize, pid: usize, kind: CoroutineKind, wake: WakeFn) -> usize {
+        let task = Coroutine::new(future, prio, pid, kind, wake);
         let cid = task.cid;
         let lock = self.wr_lock.lock();
         self.ready_queue[prio].push_back(cid);
@@ -127,8 +136,9 @@ impl Executor {
                 self.bitmap.update(prio, false);
                 self.priority = self.bitmap.get_priority();
             }
-            drop(_lock);
+            // 在释放锁之前记录，唤醒时据此判断协程是否正在执行
             self.currents[tid] = Some(cid);
+            drop(_lock);
             Some(task)
         }
     }
@@ -163,10 +173,26 @@ impl Executor {
     //     }
     // }
 
-    // 加入阻塞集合
-    pub fn pending(&mut self, cid: usize) {
+    // 加入阻塞集合，执行过程中已经被唤醒的协程直接重新入队，
+    // 执行过程中已经被取消的协程从执行器中删除，返回的协程需要在释放锁之后丢弃
+    pub fn pending(&mut self, cid: usize) -> Option<Arc<Coroutine>> {
         let _lock = self.wr_lock.lock();
-        self.pending_set.insert(cid);
+        self.clear_current(CoroutineId(cid));
+        if self.cancelled_set.remove(&cid) {
+            self.woken_set.remove(&cid);
+            return self.tasks.remove(&CoroutineId(cid));
+        }
+        if self.woken_set.remove(&cid) {
+            let prio = self.tasks.get(&CoroutineId(cid)).unwrap().inner.lock().prio;
+            self.ready_queue[prio].push_back(CoroutineId(cid));
+            self.bitmap.update(prio, true);
+            if prio < self.priority {
+                self.priority = prio;
+            }
+        } else {
+            self.pending_set.insert(cid);
+        }
+        None
     }
 
     // // 加入阻塞集合
@@ -211,16 +237,21 @@ impl Executor {
         self.waits.push(tid);
     }
 
-    /// 阻塞协程重新入队
+    /// 阻塞协程重新入队，正在执行的协程在返回 Pending 时重新入队，
+    /// 已经就绪或者已经结束的协程不做处理，因此同一个协程可以被多次唤醒
     pub fn re_back(&mut self, cid: CoroutineId) -> usize {
         let lock = self.wr_lock.lock();
-        let prio = self.tasks.get(&cid).unwrap().inner.lock().prio;
-        self.ready_queue[prio].push_back(cid);
-        self.bitmap.update(prio, true);
-        if prio < self.priority {
-            self.priority = prio;
+        if self.pending_set.remove(&cid.0) {
+            // 阻塞的协程没有在执行，不会与 execute 争用 inner 锁
+            let prio = self.tasks.get(&cid).unwrap().inner.lock().prio;
+            self.ready_queue[prio].push_back(cid);
+            self.bitmap.update(prio, true);
+            if prio < self.priority {
+                self.priority = prio;
+            }
+        } else if self.tasks.contains_key(&cid) && self.currents.contains(&Some(cid)) {
+            self.woken_set.insert(cid.0);
         }
-        self.pending_set.remove(&cid.0);
         drop(lock);
         self.priority
     }
@@ -247,10 +278,47 @@ impl Executor {
     //     self.priority
     // }
 
+    /// 取消协程，阻塞或者就绪的协程直接从执行器中删除，正在执行的协程在返回 Pending 时删除，
+    /// 返回协程是否存在以及被删除的协程，被删除的协程需要在释放锁之后丢弃，
+    /// 丢弃 future 时可能会唤醒其他协程
+    pub fn cancel(&mut self, cid: CoroutineId) -> (bool, Option<Arc<Coroutine>>) {
+        let _lock = self.wr_lock.lock();
+        if !self.tasks.contains_key(&cid) {
+            return (false, None);
+        }
+        if self.pending_set.remove(&cid.0) {
+            return (true, self.tasks.remove(&cid));
+        }
+        // 正在执行的协程持有 inner 锁，不能通过 inner 读取优先级，因此查找所有的就绪队列
+        for (prio, queue) in self.ready_queue.iter_mut().enumerate() {
+            if let Some(idx) = queue.iter().position(|ready| *ready == cid) {
+                queue.remove(idx);
+                if queue.is_empty() {
+                    self.bitmap.update(prio, false);
+                    self.priority = self.bitmap.get_priority();
+                }
+                return (true, self.tasks.remove(&cid));
+            }
+        }
+        // 既不阻塞也不就绪，说明正在执行
+        self.cancelled_set.insert(cid.0);
+        (true, None)
+    }
+
     /// 删除协程，协程已经被执行完了，在 fetch 取出 id 是就已经更新位图了，因此，这时不需要更新位图
     pub fn del_coroutine(&mut self, cid: CoroutineId) {
         let lock = self.wr_lock.lock();
+        self.clear_current(cid);
         self.tasks.remove(&cid);
+        self.woken_set.remove(&cid.0);
+        self.cancelled_set.remove(&cid.0);
         drop(lock);
     }
+
+    /// 协程的这次执行已经返回，不再是执行线程的当前协程，之后的唤醒不会被当成执行过程中的唤醒
+    fn clear_current(&mut self, cid: CoroutineId) {
+        for current in self.currents.iter_mut().filter(|current| **current == Some(cid)) {
+            *current = None;
+        }
+    }
 }
\ No newline at end of file
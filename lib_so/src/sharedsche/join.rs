@@ -0,0 +1,113 @@
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+use syscall::yield_;
+
+/// 等待协程时的错误
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JoinError {
+    /// 协程在执行完之前被取消
+    Cancelled,
+}
+
+/// 协程与 JoinHandle 共享的状态
+struct JoinState<T> {
+    /// 协程的结果，被 JoinHandle 取走之后为 None
+    result: Option<Result<T, JoinError>>,
+    /// 协程是否已经结束，包括被取消
+    finished: bool,
+    /// 等待协程结束的 waker
+    waker: Option<Waker>,
+}
+
+/// 由协程持有，协程结束时写入结果；在协程执行完之前被丢弃，说明协程被取消
+struct Completion<T>(Arc<Mutex<JoinState<T>>>);
+
+impl<T> Completion<T> {
+    fn finish(&self, result: Result<T, JoinError>) {
+        let mut state = self.0.lock();
+        if state.finished {
+            return;
+        }
+        state.result = Some(result);
+        state.finished = true;
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        self.finish(Err(JoinError::Cancelled));
+    }
+}
+
+/// 协程的句柄，可以在另一个协程中 await，也可以在线程中调用 `join` 阻塞等待，
+/// 得到协程的返回值；丢弃句柄不影响协程的执行
+pub struct JoinHandle<T> {
+    cid: usize,
+    state: Arc<Mutex<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// 包装 future，返回交给执行器的协程以及句柄，句柄的协程 Id 在添加协程之后设置
+    pub(crate) fn wrap<F>(future: F) -> (impl Future<Output = ()> + 'static + Send + Sync, Self)
+    where
+        F: Future<Output = T> + 'static + Send + Sync,
+        T: Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(JoinState { result: None, finished: false, waker: None }));
+        let completion = Completion(state.clone());
+        let task = async move {
+            let completion = completion;
+            let output = future.await;
+            completion.finish(Ok(output));
+        };
+        (task, Self { cid: usize::MAX, state })
+    }
+
+    pub(crate) fn set_cid(&mut self, cid: usize) {
+        self.cid = cid;
+    }
+
+    /// 协程 Id
+    pub fn cid(&self) -> usize {
+        self.cid
+    }
+
+    /// 协程是否已经结束，包括被取消
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().finished
+    }
+
+    /// 在线程中阻塞等待协程结束，等待期间让出 CPU，只能在用户态使用
+    pub fn join(self) -> Result<T, JoinError> {
+        loop {
+            let mut state = self.state.lock();
+            if state.finished {
+                return state.result.take().expect("JoinHandle polled after completion");
+            }
+            drop(state);
+            yield_();
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock();
+        if state.finished {
+            Poll::Ready(state.result.take().expect("JoinHandle polled after completion"))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
@@ -11,9 +11,14 @@
 mod bitmap;
 mod coroutine;
 mod executor;
+mod join;
+mod prio_bitmap;
+pub mod sync;
 
 // extern crate alloc;
 
 pub use executor::Executor;
-pub use coroutine::{CoroutineId, Coroutine, CoroutineKind};
+pub use coroutine::{CoroutineId, Coroutine, CoroutineKind, WakeFn};
+pub use join::{JoinError, JoinHandle};
+pub use prio_bitmap::PrioBitmap;
 use bitmap::BitMap;
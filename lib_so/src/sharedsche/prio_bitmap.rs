@@ -0,0 +1,135 @@
+use crate::config::{MAX_PROC_NUM, PRIO_NUM};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const BITS: usize = usize::BITS as usize;
+/// 优先级为 PRIO_NUM 表示进程没有就绪的协程，也单独作为一层，保证没有就绪协程的进程仍然可以被选出
+const LEVELS: usize = PRIO_NUM + 1;
+/// 每一层的字数，覆盖 0..MAX_PROC_NUM，其中内核的 0 号不参与选择
+const WORDS: usize = MAX_PROC_NUM / BITS;
+
+const _: () = assert!(WORDS <= BITS && LEVELS <= BITS);
+
+/// 各个进程的最高优先级以及按照优先级分层的进程位图
+///
+/// 每一层是两级位图：`summary` 的第 w 位表示 `words` 的第 w 个字不为空，
+/// `levels` 的第 p 位表示第 p 层不为空，三次 trailing_zeros 就能选出优先级最高、编号最小的进程。
+/// 所有的修改都是原子操作，修改同一个字时先改下层再改上层，清除之后重新检查下层，
+/// 上层的位可能短暂地多出来，但不会丢失
+pub struct PrioBitmap {
+    prios: [AtomicUsize; MAX_PROC_NUM + 1],
+    levels: AtomicUsize,
+    summary: [AtomicUsize; LEVELS],
+    words: [[AtomicUsize; WORDS]; LEVELS],
+}
+
+impl PrioBitmap {
+    pub const fn new() -> Self {
+        Self {
+            prios: [const { AtomicUsize::new(usize::MAX) }; MAX_PROC_NUM + 1],
+            levels: AtomicUsize::new(0),
+            summary: [const { AtomicUsize::new(0) }; LEVELS],
+            words: [const { [const { AtomicUsize::new(0) }; WORDS] }; LEVELS],
+        }
+    }
+
+    /// 进程 idx 的最高优先级，没有设置过时为 usize::MAX
+    pub fn get(&self, idx: usize) -> usize {
+        self.prios[idx].load(Ordering::Acquire)
+    }
+
+    /// 更新进程 idx 的最高优先级
+    pub fn update(&self, idx: usize, prio: usize) {
+        let old = self.prios[idx].swap(prio, Ordering::AcqRel);
+        self.moved(idx, old, prio);
+    }
+
+    /// 协程被唤醒时调用，只在 prio 更高时更新进程 idx 的最高优先级，比较和更新是一次原子操作
+    pub fn raise(&self, idx: usize, prio: usize) {
+        let old = self.prios[idx].fetch_min(prio, Ordering::AcqRel);
+        if prio < old {
+            self.moved(idx, old, prio);
+        }
+    }
+
+    /// 优先级最高的进程，优先级相同时选择编号最小的
+    pub fn first(&self) -> Option<usize> {
+        let mut levels = self.levels.load(Ordering::Acquire);
+        while levels != 0 {
+            let level = levels.trailing_zeros() as usize;
+            let mut summary = self.summary[level].load(Ordering::Acquire);
+            while summary != 0 {
+                let w = summary.trailing_zeros() as usize;
+                let word = self.words[level][w].load(Ordering::Acquire);
+                if word != 0 {
+                    return Some(w * BITS + word.trailing_zeros() as usize);
+                }
+                // 上层多出来的位，跳过
+                summary &= summary - 1;
+            }
+            levels &= levels - 1;
+        }
+        None
+    }
+
+    /// 进程 idx 的优先级从 old 变为 prio，先加入新的一层再离开原来的一层
+    fn moved(&self, idx: usize, old: usize, prio: usize) {
+        if idx == 0 || idx >= MAX_PROC_NUM {
+            return;
+        }
+        if prio < LEVELS {
+            self.sync(prio, idx);
+        }
+        if old != prio && old < LEVELS {
+            self.sync(old, idx);
+        }
+    }
+
+    /// 让进程 idx 在第 level 层中的位与它当前的优先级一致，
+    /// 其他核同时更新这个进程时，重新检查直到一致
+    fn sync(&self, level: usize, idx: usize) {
+        loop {
+            let present = self.get(idx) == level;
+            if present {
+                self.set(level, idx);
+            } else {
+                self.clear(level, idx);
+            }
+            if (self.get(idx) == level) == present {
+                break;
+            }
+        }
+    }
+
+    fn set(&self, level: usize, idx: usize) {
+        let (w, bit) = (idx / BITS, idx % BITS);
+        self.words[level][w].fetch_or(1 << bit, Ordering::AcqRel);
+        self.summary[level].fetch_or(1 << w, Ordering::AcqRel);
+        self.levels.fetch_or(1 << level, Ordering::AcqRel);
+    }
+
+    fn clear(&self, level: usize, idx: usize) {
+        let (w, bit) = (idx / BITS, idx % BITS);
+        if self.words[level][w].fetch_and(!(1 << bit), Ordering::AcqRel) != 1 << bit {
+            return;
+        }
+        // 这个字空了，清除上层的位之后重新检查，期间其他核可能又设置了这个字
+        self.summary[level].fetch_and(!(1 << w), Ordering::AcqRel);
+        if self.words[level][w].load(Ordering::Acquire) != 0 {
+            self.summary[level].fetch_or(1 << w, Ordering::AcqRel);
+            return;
+        }
+        if self.summary[level].load(Ordering::Acquire) != 0 {
+            return;
+        }
+        self.levels.fetch_and(!(1 << level), Ordering::AcqRel);
+        if self.summary[level].load(Ordering::Acquire) != 0 {
+            self.levels.fetch_or(1 << level, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Default for PrioBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
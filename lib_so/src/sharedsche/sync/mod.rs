@@ -0,0 +1,16 @@
+//! 协程同步原语，拿不到资源的协程返回 Pending 进入阻塞集合，
+//! 资源释放时通过 waker 调用 `Executor::re_back` 回到就绪队列，不会阻塞执行器所在的线程
+
+mod mutex;
+mod notify;
+mod rwlock;
+mod semaphore;
+mod wait_queue;
+
+pub mod mpsc;
+pub mod oneshot;
+
+pub use mutex::{Mutex, MutexGuard};
+pub use notify::{Notified, Notify};
+pub use rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use semaphore::{Acquire, Semaphore, SemaphorePermit};
@@ -0,0 +1,232 @@
+//! 有界的多生产者单消费者通道，通道满时发送的协程阻塞，通道空时接收的协程阻塞
+
+use super::wait_queue::WaitQueue;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+/// 接收端已经关闭，发送失败，返回没有发出的值
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// `try_send` 的错误
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// 通道已满
+    Full(T),
+    /// 接收端已经关闭
+    Closed(T),
+}
+
+struct Chan<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    /// 发送端的数量，为 0 时接收端在通道空了之后返回 None
+    senders: usize,
+    receiver_closed: bool,
+    recv_waker: Option<Waker>,
+    /// 等待通道空出位置的发送协程
+    send_waiters: WaitQueue,
+}
+
+impl<T> Chan<T> {
+    /// 通道还有位置时唤醒排在队首的发送协程
+    fn wake_sender(&self) -> Option<Waker> {
+        if self.queue.len() < self.capacity { self.send_waiters.first() } else { None }
+    }
+}
+
+/// 新建容量为 capacity 的通道
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "mpsc channel capacity must be positive");
+    let chan = Arc::new(Mutex::new(Chan {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        receiver_closed: false,
+        recv_waker: None,
+        send_waiters: WaitQueue::new(),
+    }));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// 发送端，可以复制给多个协程
+pub struct Sender<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// 发送 value，通道满时等待
+    pub fn send(&self, value: T) -> SendFuture<'_, T> {
+        SendFuture { sender: self, value: Some(value), id: None }
+    }
+
+    /// 不等待，通道满或者接收端已经关闭时返回错误
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut chan = self.chan.lock();
+        if chan.receiver_closed {
+            return Err(TrySendError::Closed(value));
+        }
+        // 有协程在等待时不插队
+        if !chan.send_waiters.is_empty() || chan.queue.len() >= chan.capacity {
+            return Err(TrySendError::Full(value));
+        }
+        chan.queue.push_back(value);
+        let waker = chan.recv_waker.take();
+        drop(chan);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(())
+    }
+
+    /// 接收端是否已经关闭
+    pub fn is_closed(&self) -> bool {
+        self.chan.lock().receiver_closed
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.lock().senders += 1;
+        Self { chan: self.chan.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut chan = self.chan.lock();
+        chan.senders -= 1;
+        let waker = if chan.senders == 0 { chan.recv_waker.take() } else { None };
+        drop(chan);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// 发送的 future，由 `Sender::send` 创建
+pub struct SendFuture<'a, T> {
+    sender: &'a Sender<T>,
+    value: Option<T>,
+    id: Option<usize>,
+}
+
+// value 只会被移动出来，不会被 pin 住
+impl<T> Unpin for SendFuture<'_, T> {}
+
+impl<T> Future for SendFuture<'_, T> {
+    type Output = Result<(), SendError<T>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let sender = self.sender;
+        let mut chan = sender.chan.lock();
+        if chan.receiver_closed {
+            if let Some(id) = self.id.take() {
+                chan.send_waiters.remove(id);
+            }
+            let value = self.value.take().expect("SendFuture polled after completion");
+            return Poll::Ready(Err(SendError(value)));
+        }
+        if chan.send_waiters.is_first(self.id) && chan.queue.len() < chan.capacity {
+            let value = self.value.take().expect("SendFuture polled after completion");
+            chan.queue.push_back(value);
+            if let Some(id) = self.id.take() {
+                chan.send_waiters.remove(id);
+            }
+            let recv_waker = chan.recv_waker.take();
+            // 还有位置时让下一个发送协程继续
+            let send_waker = chan.wake_sender();
+            drop(chan);
+            if let Some(waker) = recv_waker {
+                waker.wake();
+            }
+            if let Some(waker) = send_waker {
+                waker.wake();
+            }
+            return Poll::Ready(Ok(()));
+        }
+        chan.send_waiters.register(&mut self.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for SendFuture<'_, T> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut chan = self.sender.chan.lock();
+            let first = chan.send_waiters.is_first(Some(id));
+            chan.send_waiters.remove(id);
+            let waker = if first { chan.wake_sender() } else { None };
+            drop(chan);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// 接收端
+pub struct Receiver<T> {
+    chan: Arc<Mutex<Chan<T>>>,
+}
+
+impl<T> Receiver<T> {
+    /// 接收一个值，通道空时等待；所有发送端都已经丢弃并且通道空了之后返回 None
+    pub fn recv(&mut self) -> RecvFuture<'_, T> {
+        RecvFuture { receiver: self }
+    }
+
+    /// 不等待，通道空时返回 None
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut chan = self.chan.lock();
+        let value = chan.queue.pop_front();
+        let waker = if value.is_some() { chan.wake_sender() } else { None };
+        drop(chan);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        value
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut chan = self.chan.lock();
+        chan.receiver_closed = true;
+        let wakers = chan.send_waiters.take_all();
+        drop(chan);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// 接收的 future，由 `Receiver::recv` 创建
+pub struct RecvFuture<'a, T> {
+    receiver: &'a mut Receiver<T>,
+}
+
+impl<T> Future for RecvFuture<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut chan = self.receiver.chan.lock();
+        if let Some(value) = chan.queue.pop_front() {
+            let waker = chan.wake_sender();
+            drop(chan);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+            return Poll::Ready(Some(value));
+        }
+        if chan.senders == 0 {
+            return Poll::Ready(None);
+        }
+        chan.recv_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
@@ -0,0 +1,106 @@
+use super::wait_queue::WaitQueue;
+use alloc::collections::BTreeSet;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spin::Mutex;
+
+struct NotifyInner {
+    /// 没有协程等待时 notify_one 留下的许可，最多一个
+    permit: bool,
+    waiters: WaitQueue,
+    /// 已经被 notify_one 通知、还没有返回的协程
+    notified: BTreeSet<usize>,
+}
+
+/// 协程通知，等待通知的协程阻塞，由 `notify_one` 按照等待的先后顺序唤醒一个，
+/// 或者由 `notify_waiters` 唤醒当前等待的全部协程
+pub struct Notify {
+    inner: Mutex<NotifyInner>,
+}
+
+impl Notify {
+    pub const fn new() -> Self {
+        Self { inner: Mutex::new(NotifyInner { permit: false, waiters: WaitQueue::new(), notified: BTreeSet::new() }) }
+    }
+
+    /// 等待通知
+    pub fn notified(&self) -> Notified<'_> {
+        Notified { notify: self, id: None, done: false }
+    }
+
+    /// 唤醒一个等待的协程，没有协程等待时留下一个许可，下一次 `notified` 直接返回
+    pub fn notify_one(&self) {
+        let mut inner = self.inner.lock();
+        match inner.waiters.pop_front() {
+            Some((id, waker)) => {
+                inner.notified.insert(id);
+                drop(inner);
+                waker.wake();
+            }
+            None => inner.permit = true,
+        }
+    }
+
+    /// 唤醒当前等待的全部协程，不留下许可
+    pub fn notify_waiters(&self) {
+        let wakers = self.inner.lock().waiters.take_all();
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 等待通知的 future，由 `Notify::notified` 创建
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    id: Option<usize>,
+    done: bool,
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let notify = self.notify;
+        let mut inner = notify.inner.lock();
+        let id = self.id;
+        match id {
+            None if inner.permit => inner.permit = false,
+            // 被 notify_one 或者 notify_waiters 移出了队列
+            Some(id) if !inner.waiters.contains(id) => {
+                inner.notified.remove(&id);
+                self.id = None;
+            }
+            _ => {
+                inner.waiters.register(&mut self.id, cx.waker());
+                return Poll::Pending;
+            }
+        }
+        self.done = true;
+        Poll::Ready(())
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = self.id.take() {
+            let mut inner = self.notify.inner.lock();
+            inner.waiters.remove(id);
+            // 收到了 notify_one 的通知却被取消，把通知交给下一个协程
+            if inner.notified.remove(&id) {
+                drop(inner);
+                self.notify.notify_one();
+            }
+        }
+    }
+}
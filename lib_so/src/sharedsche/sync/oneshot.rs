@@ -0,0 +1,87 @@
+//! 只能发送一次的通道，接收端是一个 future
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+/// 发送端在发送之前被丢弃
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+struct Inner<T> {
+    value: Option<T>,
+    /// 发送端已经丢弃，包括发送之后
+    complete: bool,
+    receiver_closed: bool,
+    waker: Option<Waker>,
+}
+
+/// 新建通道
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Arc::new(Mutex::new(Inner { value: None, complete: false, receiver_closed: false, waker: None }));
+    (Sender { inner: inner.clone() }, Receiver { inner })
+}
+
+/// 发送端
+pub struct Sender<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+impl<T> Sender<T> {
+    /// 发送 value，接收端已经丢弃时返回 value
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut inner = self.inner.lock();
+        if inner.receiver_closed {
+            return Err(value);
+        }
+        inner.value = Some(value);
+        // 丢弃发送端时唤醒接收端
+        Ok(())
+    }
+
+    /// 接收端是否已经丢弃
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().receiver_closed
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock();
+        inner.complete = true;
+        let waker = inner.waker.take();
+        drop(inner);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// 接收端，await 得到发送的值
+pub struct Receiver<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.inner.lock();
+        if let Some(value) = inner.value.take() {
+            Poll::Ready(Ok(value))
+        } else if inner.complete {
+            Poll::Ready(Err(RecvError))
+        } else {
+            inner.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.lock().receiver_closed = true;
+    }
+}
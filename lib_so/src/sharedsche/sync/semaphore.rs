@@ -0,0 +1,129 @@
+use super::wait_queue::WaitQueue;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use spin::Mutex;
+
+struct SemaphoreInner {
+    permits: usize,
+    waiters: WaitQueue,
+}
+
+/// 协程信号量，获取不到许可的协程阻塞，不占用执行器线程；
+/// 按照等待的先后顺序分配许可，需要许可多的协程不会被饿死
+pub struct Semaphore {
+    inner: Mutex<SemaphoreInner>,
+}
+
+impl Semaphore {
+    /// 新建信号量，初始有 permits 个许可
+    pub const fn new(permits: usize) -> Self {
+        Self { inner: Mutex::new(SemaphoreInner { permits, waiters: WaitQueue::new() }) }
+    }
+
+    /// 获取一个许可
+    pub fn acquire(&self) -> Acquire<'_> {
+        self.acquire_many(1)
+    }
+
+    /// 获取 count 个许可
+    pub fn acquire_many(&self, count: usize) -> Acquire<'_> {
+        Acquire { semaphore: self, count, id: None }
+    }
+
+    /// 不等待，获取不到时返回 None
+    pub fn try_acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let mut inner = self.inner.lock();
+        if inner.waiters.is_empty() && inner.permits >= 1 {
+            inner.permits -= 1;
+            Some(SemaphorePermit { semaphore: self, count: 1 })
+        } else {
+            None
+        }
+    }
+
+    /// 增加 count 个许可
+    pub fn add_permits(&self, count: usize) {
+        let mut inner = self.inner.lock();
+        inner.permits += count;
+        self.wake_first(inner);
+    }
+
+    /// 当前可用的许可数
+    pub fn available_permits(&self) -> usize {
+        self.inner.lock().permits
+    }
+
+    /// 唤醒队首的协程，由它检查许可是否足够
+    fn wake_first(&self, inner: spin::MutexGuard<'_, SemaphoreInner>) {
+        let waker = if inner.permits > 0 { inner.waiters.first() } else { None };
+        drop(inner);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// 信号量许可，丢弃时归还
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+}
+
+impl SemaphorePermit<'_> {
+    /// 不归还许可
+    pub fn forget(mut self) {
+        self.count = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.count > 0 {
+            self.semaphore.add_permits(self.count);
+        }
+    }
+}
+
+/// 获取许可的 future，由 `Semaphore::acquire` 创建
+pub struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    count: usize,
+    id: Option<usize>,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = SemaphorePermit<'a>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let semaphore = self.semaphore;
+        let mut inner = semaphore.inner.lock();
+        if inner.waiters.is_first(self.id) && inner.permits >= self.count {
+            inner.permits -= self.count;
+            if let Some(id) = self.id.take() {
+                inner.waiters.remove(id);
+            }
+            // 剩下的许可可能够下一个协程使用
+            semaphore.wake_first(inner);
+            let count = self.count;
+            return Poll::Ready(SemaphorePermit { semaphore, count });
+        }
+        inner.waiters.register(&mut self.id, cx.waker());
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        // 被取消的协程如果排在队首，需要让下一个协程检查许可
+        if let Some(id) = self.id.take() {
+            let mut inner = self.semaphore.inner.lock();
+            if inner.waiters.is_first(Some(id)) {
+                inner.waiters.remove(id);
+                self.semaphore.wake_first(inner);
+            } else {
+                inner.waiters.remove(id);
+            }
+        }
+    }
+}
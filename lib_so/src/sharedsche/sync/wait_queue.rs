@@ -0,0 +1,75 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::task::Waker;
+
+/// 阻塞协程的等待队列，保存协程的 waker，按照加入的先后顺序唤醒；
+/// 被唤醒的协程通过 waker 调用 `Executor::re_back`，从阻塞集合回到就绪队列
+pub(crate) struct WaitQueue {
+    waiters: VecDeque<(usize, Waker)>,
+    next_id: usize,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self { waiters: VecDeque::new(), next_id: 0 }
+    }
+
+    /// 加入队列，已经在队列中时只更新 waker，id 记录在队列中的编号
+    pub fn register(&mut self, id: &mut Option<usize>, waker: &Waker) {
+        if let Some(id) = *id {
+            if let Some((_, old)) = self.waiters.iter_mut().find(|(waiter, _)| *waiter == id) {
+                if !old.will_wake(waker) {
+                    *old = waker.clone();
+                }
+                return;
+            }
+        }
+        let new_id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.waiters.push_back((new_id, waker.clone()));
+        *id = Some(new_id);
+    }
+
+    /// 移出队列，返回是否还在队列中
+    pub fn remove(&mut self, id: usize) -> bool {
+        match self.waiters.iter().position(|(waiter, _)| *waiter == id) {
+            Some(idx) => {
+                self.waiters.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.waiters.iter().any(|(waiter, _)| *waiter == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.is_empty()
+    }
+
+    /// 是否排在队首，不在队列中并且队列为空时也算
+    pub fn is_first(&self, id: Option<usize>) -> bool {
+        match (id, self.waiters.front()) {
+            (_, None) => true,
+            (Some(id), Some((first, _))) => id == *first,
+            (None, Some(_)) => false,
+        }
+    }
+
+    /// 队首协程的 waker，不移出队列，由协程被唤醒之后自己移出
+    pub fn first(&self) -> Option<Waker> {
+        self.waiters.front().map(|(_, waker)| waker.clone())
+    }
+
+    /// 移出队首协程，返回它的编号和 waker
+    pub fn pop_front(&mut self) -> Option<(usize, Waker)> {
+        self.waiters.pop_front()
+    }
+
+    /// 移出所有协程
+    pub fn take_all(&mut self) -> Vec<Waker> {
+        self.waiters.drain(..).map(|(_, waker)| waker).collect()
+    }
+}
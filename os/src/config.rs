@@ -2,6 +2,7 @@ pub const USER_STACK_SIZE: usize = 0x4000;
 pub const KERNEL_STACK_SIZE: usize = 0x4000;
 pub const KERNEL_HEAP_SIZE: usize = 0x80_0000;
 
+// used when there is no device tree, see `memory_end`
 #[cfg(feature = "board_qemu")]
 pub const MEMORY_END: usize = 0x84000000;
 
@@ -9,6 +10,11 @@ pub const MEMORY_END: usize = 0x84000000;
 // pub const MEMORY_END: usize = 0x100A00000;
 pub const MEMORY_END: usize = 0x101000000;
 
+/// End of the memory managed by the kernel, the trace buffer of `TRACE_SIZE` follows it.
+pub fn memory_end() -> usize {
+    crate::device::board_info().memory_end
+}
+
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
@@ -18,6 +24,10 @@ pub const USER_TRAP_BUFFER: usize = TRAMPOLINE - PAGE_SIZE;
 pub const HEAP_BUFFER: usize = USER_TRAP_BUFFER - PAGE_SIZE;
 pub const TRAP_CONTEXT: usize = HEAP_BUFFER - PAGE_SIZE;
 
+// where mmap places the mappings without a usable hint, far above the trace buffer
+pub const MMAP_BASE: usize = 0x10_0000_0000;
+pub const MMAP_END: usize = 0x40_0000_0000;
+
 #[cfg(feature = "board_qemu")]
 pub const CLOCK_FREQ: usize = 12500000;
 
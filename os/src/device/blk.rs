@@ -0,0 +1,303 @@
+use core::ptr::NonNull;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use easy_fs::{BlockDevice, BLOCK_SZ};
+use lazy_static::*;
+use spin::Mutex;
+use virtio_drivers::{
+    device::blk::{BlkReq, BlkResp, VirtIOBlk, SECTOR_SIZE},
+    transport::{
+        mmio::{MmioTransport, VirtIOHeader},
+        DeviceType,
+    },
+};
+use crate::device::bus::virtio::VirtioHal;
+use crate::fs::ReadHelper;
+
+/// An in-flight request, boxed so that the driver can hold on to `req`, `resp` and `buf`
+/// until the device hands the token back.
+struct BlockRequest {
+    req: BlkReq,
+    resp: BlkResp,
+    buf: Box<[u8]>,
+    block_id: usize,
+    write: bool,
+    result: Option<virtio_drivers::Result>,
+    // kernel coroutines waiting for the request
+    waiters: Vec<usize>,
+    // nobody waits for the result anymore, removed once the device hands the token back
+    abandoned: bool,
+}
+
+/// Held by `submit_async` while its request is in flight, the future may be dropped
+/// before the request completes.
+struct RequestGuard<'a> {
+    device: &'a VirtIOBlock,
+    id: u64,
+}
+
+impl Drop for RequestGuard<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.device.inner.lock();
+        if inner.requests.get(&self.id).map_or(false, |request| request.result.is_some()) {
+            inner.requests.remove(&self.id);
+        } else if let Some(request) = inner.requests.get_mut(&self.id) {
+            // the device still owns the buffers of the request
+            request.abandoned = true;
+        }
+    }
+}
+
+struct BlockRequests {
+    // keyed by an id of our own, the tokens are reused by the driver as soon as they complete
+    requests: BTreeMap<u64, Box<BlockRequest>>,
+    // id of the request holding each token
+    tokens: BTreeMap<u16, u64>,
+    // kernel coroutines waiting for a free descriptor
+    queue_waiters: Vec<usize>,
+}
+
+/// Every request is submitted without blocking and completed by whoever sees the token
+/// in the used ring first: the interrupt handler or a synchronous caller spinning on it.
+pub struct VirtIOBlock {
+    virtio: Mutex<VirtIOBlk<VirtioHal, MmioTransport>>,
+    inner: Mutex<BlockRequests>,
+    next_id: AtomicU64,
+    // set by a failed request of the synchronous `BlockDevice` interface, see `take_error`
+    io_error: AtomicBool,
+}
+
+lazy_static! {
+    // None if the board has no virtio-blk device
+    pub static ref BLOCK_DEVICE: Option<Arc<VirtIOBlock>> = VirtIOBlock::new().map(Arc::new);
+}
+
+/// The irq of the block device, if there is one.
+pub fn blk_irq() -> Option<u16> {
+    super::board_info().virtio_device(DeviceType::Block).map(|blk| blk.irq)
+}
+
+pub fn blk_interrupt_handler() {
+    if let Some(block_device) = BLOCK_DEVICE.as_ref() {
+        block_device.handle_irq();
+    }
+}
+
+impl VirtIOBlock {
+    fn new() -> Option<Self> {
+        let blk_device_addr = super::board_info().virtio_device(DeviceType::Block)?.base;
+        debug!("blk device addr: {:#x}", blk_device_addr);
+        let header = NonNull::new(blk_device_addr as *mut VirtIOHeader).unwrap();
+        let transport = unsafe { MmioTransport::new(header) }.unwrap();
+        let virtio = VirtIOBlk::<VirtioHal, MmioTransport>::new(transport)
+            .expect("can't create blk device by virtio");
+        Some(Self {
+            virtio: Mutex::new(virtio),
+            inner: Mutex::new(BlockRequests {
+                requests: BTreeMap::new(),
+                tokens: BTreeMap::new(),
+                queue_waiters: Vec::new(),
+            }),
+            next_id: AtomicU64::new(0),
+            io_error: AtomicBool::new(false),
+        })
+    }
+
+    /// Number of blocks of the disk.
+    pub fn num_blocks(&self) -> usize {
+        self.virtio.lock().capacity() as usize * SECTOR_SIZE / BLOCK_SZ
+    }
+
+    /// Whether a read or write through the `BlockDevice` interface has failed since the last call.
+    /// easy-fs has no way to return the error, the caller of the filesystem checks it instead.
+    pub fn take_error(&self) -> bool {
+        self.io_error.swap(false, Ordering::Relaxed)
+    }
+
+    /// Fails with `QueueFull` if there is no free descriptor, `waiter` is then woken up
+    /// once a request completes.
+    fn submit(&self, block_id: usize, data: Option<&[u8]>, waiter: Option<usize>) -> virtio_drivers::Result<u64> {
+        let mut request = Box::new(BlockRequest {
+            req: BlkReq::default(),
+            resp: BlkResp::default(),
+            buf: vec![0u8; BLOCK_SZ].into_boxed_slice(),
+            block_id,
+            write: data.is_some(),
+            result: None,
+            waiters: Vec::new(),
+            abandoned: false,
+        });
+        let mut virtio = self.virtio.lock();
+        let request_ref: &mut BlockRequest = &mut request;
+        let token = unsafe {
+            match data {
+                Some(data) => {
+                    request_ref.buf.copy_from_slice(data);
+                    virtio.write_block_nb(block_id, &mut request_ref.req, &request_ref.buf, &mut request_ref.resp)
+                }
+                None => virtio.read_block_nb(block_id, &mut request_ref.req, &mut request_ref.buf, &mut request_ref.resp),
+            }
+        };
+        // still holding the device so that nothing completes before the request or the waiter is recorded
+        let mut inner = self.inner.lock();
+        let token = match token {
+            Ok(token) => token,
+            Err(virtio_drivers::Error::QueueFull) => {
+                if let Some(cid) = waiter {
+                    inner.queue_waiters.push(cid);
+                }
+                return Err(virtio_drivers::Error::QueueFull);
+            }
+            Err(err) => return Err(err),
+        };
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        inner.tokens.insert(token, id);
+        inner.requests.insert(id, request);
+        Ok(id)
+    }
+
+    /// Pop everything in the used ring, the caller holds the device lock.
+    fn complete_used(&self, virtio: &mut VirtIOBlk<VirtioHal, MmioTransport>) {
+        let mut inner = self.inner.lock();
+        let mut completed = false;
+        while let Some(token) = virtio.peek_used() {
+            let id = inner.tokens.remove(&token).expect("unknown VirtIOBlk token");
+            let request: &mut BlockRequest = inner.requests.get_mut(&id).unwrap();
+            let result = unsafe {
+                if request.write {
+                    virtio.complete_write_block(token, &request.req, &request.buf, &mut request.resp)
+                } else {
+                    virtio.complete_read_block(token, &request.req, &mut request.buf, &mut request.resp)
+                }
+            };
+            request.result = Some(result);
+            for cid in request.waiters.drain(..) {
+                lib_so::re_back(cid, 0);
+            }
+            if request.abandoned {
+                inner.requests.remove(&id);
+            }
+            completed = true;
+        }
+        if completed {
+            for cid in inner.queue_waiters.drain(..) {
+                lib_so::re_back(cid, 0);
+            }
+        }
+    }
+
+    fn handle_irq(&self) {
+        let mut virtio = self.virtio.lock();
+        virtio.ack_interrupt();
+        self.complete_used(&mut virtio);
+    }
+
+    fn poll_used(&self) {
+        let mut virtio = self.virtio.lock();
+        self.complete_used(&mut virtio);
+        drop(virtio);
+        core::hint::spin_loop();
+    }
+
+    /// Id of an unfinished write to `block_id`, the device may reorder the requests
+    /// so nothing else is sent for the block until it is done.
+    fn pending_write(inner: &BlockRequests, block_id: usize) -> Option<u64> {
+        inner
+            .requests
+            .iter()
+            .find(|(_, request)| request.write && request.block_id == block_id && request.result.is_none())
+            .map(|(&id, _)| id)
+    }
+
+    fn submit_sync(&self, block_id: usize, data: Option<&[u8]>) -> virtio_drivers::Result<Box<BlockRequest>> {
+        while Self::pending_write(&self.inner.lock(), block_id).is_some() {
+            self.poll_used();
+        }
+        let id = loop {
+            match self.submit(block_id, data, None) {
+                Err(virtio_drivers::Error::QueueFull) => self.poll_used(),
+                res => break res?,
+            }
+        };
+        loop {
+            let mut inner = self.inner.lock();
+            if inner.requests[&id].result.is_some() {
+                return Ok(inner.requests.remove(&id).unwrap());
+            }
+            drop(inner);
+            self.poll_used();
+        }
+    }
+
+    async fn submit_async(&self, block_id: usize, data: Option<&[u8]>) -> virtio_drivers::Result<Box<BlockRequest>> {
+        let mut helper = Box::new(ReadHelper::new());
+        let cid = lib_so::current_cid(true);
+        loop {
+            let mut inner = self.inner.lock();
+            match Self::pending_write(&inner, block_id) {
+                Some(id) => inner.requests.get_mut(&id).unwrap().waiters.push(cid),
+                None => break,
+            }
+            drop(inner);
+            helper.as_mut().await;
+        }
+        let id = loop {
+            match self.submit(block_id, data, Some(cid)) {
+                Err(virtio_drivers::Error::QueueFull) => helper.as_mut().await,
+                res => break res?,
+            }
+        };
+        let _guard = RequestGuard { device: self, id };
+        loop {
+            let mut inner = self.inner.lock();
+            let request = inner.requests.get_mut(&id).unwrap();
+            if request.result.is_some() {
+                return Ok(inner.requests.remove(&id).unwrap());
+            }
+            request.waiters.push(cid);
+            drop(inner);
+            helper.as_mut().await;
+        }
+    }
+
+    /// Read a block, the current kernel coroutine sleeps until the device interrupt.
+    pub async fn aread_block(&self, block_id: usize, buf: &mut [u8]) -> virtio_drivers::Result {
+        let request = self.submit_async(block_id, None).await?;
+        request.result.unwrap()?;
+        buf.copy_from_slice(&request.buf);
+        Ok(())
+    }
+
+    /// Write a block, the current kernel coroutine sleeps until the device interrupt.
+    pub async fn awrite_block(&self, block_id: usize, buf: &[u8]) -> virtio_drivers::Result {
+        let request = self.submit_async(block_id, Some(buf)).await?;
+        request.result.unwrap()
+    }
+}
+
+/// A failed request is logged and remembered for `take_error`, the buffer of a failed read is zeroed.
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let res = self.submit_sync(block_id, None).and_then(|request| {
+            request.result.unwrap()?;
+            buf.copy_from_slice(&request.buf);
+            Ok(())
+        });
+        if let Err(err) = res {
+            error!("[blk] failed to read block {}: {:?}", block_id, err);
+            self.io_error.store(true, Ordering::Relaxed);
+            buf.fill(0);
+        }
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        if let Err(err) = self.submit_sync(block_id, Some(buf)).and_then(|request| request.result.unwrap()) {
+            error!("[blk] failed to write block {}: {:?}", block_id, err);
+            self.io_error.store(true, Ordering::Relaxed);
+        }
+    }
+}
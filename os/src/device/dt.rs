@@ -0,0 +1,306 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+use device_tree::util::SliceRead;
+use device_tree::{DeviceTree, Node};
+use spin::Once;
+use virtio_drivers::transport::{
+    mmio::{MmioTransport, VirtIOHeader},
+    DeviceType, Transport,
+};
+use crate::config::{MEMORY_END, TRACE_SIZE};
+
+const DEVICE_TREE_MAGIC: u32 = 0xd00d_feed;
+
+#[repr(C)]
+struct DtbHeader {
+    be_magic: u32,
+    be_size: u32,
+}
+
+pub struct UartInfo {
+    pub base: usize,
+    pub size: usize,
+    pub irq: u16,
+}
+
+pub struct VirtioInfo {
+    pub base: usize,
+    pub size: usize,
+    pub irq: u16,
+    pub device_type: DeviceType,
+}
+
+/// Devices of the board, found in the device tree or the defaults of `board_qemu`/`board_lrv`.
+pub struct BoardInfo {
+    // end of the memory used by the kernel, the trace buffer follows it
+    pub memory_end: usize,
+    pub plic_base: usize,
+    pub plic_size: usize,
+    // (hart id, mode) of every PLIC context, indexed by the context id
+    pub plic_contexts: Vec<(usize, char)>,
+    pub uarts: Vec<UartInfo>,
+    pub virtio: Vec<VirtioInfo>,
+}
+
+impl BoardInfo {
+    pub fn virtio_device(&self, device_type: DeviceType) -> Option<&VirtioInfo> {
+        self.virtio.iter().find(|virtio| virtio.device_type == device_type)
+    }
+}
+
+impl Default for BoardInfo {
+    #[cfg(feature = "board_qemu")]
+    fn default() -> Self {
+        Self {
+            memory_end: MEMORY_END,
+            plic_base: 0xc00_0000,
+            plic_size: 0x400_0000,
+            plic_contexts: Vec::new(),
+            uarts: (0..4)
+                .map(|i| UartInfo { base: 0x1000_2000 + i * 0x1000, size: 0x1000, irq: 12 + i as u16 })
+                .collect(),
+            virtio: alloc::vec![VirtioInfo {
+                base: 0x1000_8000,
+                size: 0x1000,
+                irq: 8,
+                device_type: DeviceType::Network,
+            }],
+        }
+    }
+
+    #[cfg(feature = "board_lrv")]
+    fn default() -> Self {
+        Self {
+            memory_end: MEMORY_END,
+            plic_base: 0xc00_0000,
+            plic_size: 0x400_0000,
+            plic_contexts: Vec::new(),
+            uarts: (0..4)
+                .map(|i| UartInfo { base: 0x6000_1000 + i * 0x1000, size: 0x1000, irq: 4 + i as u16 })
+                .collect(),
+            virtio: Vec::new(),
+        }
+    }
+}
+
+static BOARD_INFO: Once<BoardInfo> = Once::new();
+
+pub fn board_info() -> &'static BoardInfo {
+    BOARD_INFO.call_once(BoardInfo::default)
+}
+
+// collected while walking the tree, resolved once all the nodes are seen
+#[derive(Default)]
+struct DtState {
+    info: BoardInfo,
+    // phandle of the interrupt controller of each hart
+    intc_harts: BTreeMap<u32, usize>,
+    // (phandle, cause) of every PLIC context
+    plic_interrupts: Vec<(u32, u32)>,
+    // node name of every uart in `info.uarts`
+    uart_names: Vec<String>,
+    // `stdout-path` of /chosen, a path or an alias
+    stdout_path: Option<String>,
+    aliases: BTreeMap<String, String>,
+}
+
+/// Find the devices in the device tree passed by the bootloader.
+/// Must be called with the paging off and before the frame allocator is initialized,
+/// the defaults of the board are kept if there is no device tree.
+pub fn init_dt(dtb: usize) {
+    let header = unsafe { &*(dtb as *const DtbHeader) };
+    let magic = u32::from_be(header.be_magic);
+    if magic != DEVICE_TREE_MAGIC {
+        warn!("no device tree at {:#x}", dtb);
+        return;
+    }
+    let size = u32::from_be(header.be_size);
+    let data = unsafe { core::slice::from_raw_parts(dtb as *const u8, size as usize) };
+    let dt = DeviceTree::load(data).expect("failed to parse device tree");
+    let mut state = DtState::default();
+    state.info.uarts.clear();
+    state.info.virtio.clear();
+    walk_dt_node(&dt.root, (2, 2), &mut state);
+
+    let DtState { mut info, intc_harts, plic_interrupts, uart_names, stdout_path, aliases } = state;
+    // the console belongs to the SBI, the kernel only drives the other uarts
+    if let Some(console) = stdout_path.as_deref().and_then(|path| console_name(path, &aliases)) {
+        if let Some(index) = uart_names.iter().position(|name| name == console) {
+            info.uarts.remove(index);
+        }
+    }
+    info.plic_contexts = plic_interrupts
+        .iter()
+        .map(|&(phandle, cause)| {
+            let hart_id = intc_harts.get(&phandle).copied().unwrap_or(usize::MAX);
+            let mode = match cause {
+                8 => 'U',
+                9 => 'S',
+                11 => 'M',
+                // the context is not connected
+                _ => '-',
+            };
+            (hart_id, mode)
+        })
+        .collect();
+    info!(
+        "[dt] memory end {:#x}, plic {:#x}, {} contexts, {} uarts, {} virtio devices",
+        info.memory_end,
+        info.plic_base,
+        info.plic_contexts.len(),
+        info.uarts.len(),
+        info.virtio.len()
+    );
+    BOARD_INFO.call_once(|| info);
+}
+
+/// Node name of the console, `path` may be followed by `:<options>`.
+fn console_name<'a>(path: &'a str, aliases: &'a BTreeMap<String, String>) -> Option<&'a str> {
+    let path = path.split(':').next()?;
+    let path = if path.starts_with('/') { path } else { aliases.get(path)?.as_str() };
+    path.rsplit('/').next()
+}
+
+/// Read the `index`th (address, size) pair of `reg`, the number of cells is given by the parent.
+fn read_reg(node: &Node, cells: (u32, u32), index: usize) -> Option<(usize, usize)> {
+    let reg = node.prop_raw("reg")?;
+    let read_cells = |pos: usize, n: u32| -> Option<usize> {
+        match n {
+            0 => Some(0),
+            1 => reg.as_slice().read_be_u32(pos).ok().map(|x| x as usize),
+            2 => reg.as_slice().read_be_u64(pos).ok().map(|x| x as usize),
+            _ => None,
+        }
+    };
+    let (addr_cells, size_cells) = cells;
+    let pos = index * (addr_cells + size_cells) as usize * 4;
+    let addr = read_cells(pos, addr_cells)?;
+    let size = read_cells(pos + addr_cells as usize * 4, size_cells)?;
+    Some((addr, size))
+}
+
+fn read_irq(node: &Node) -> Option<u16> {
+    node.prop_u32("interrupts").ok().map(|irq| irq as u16)
+}
+
+fn walk_dt_node(node: &Node, cells: (u32, u32), state: &mut DtState) {
+    if node.name == "chosen" {
+        if let Ok(bootargs) = node.prop_str("bootargs") {
+            debug!("bootargs: {}", bootargs);
+            crate::net::parse_bootargs(&mut crate::net::NET_CONFIG.lock(), bootargs);
+        }
+        if let Ok(stdout_path) = node.prop_str("stdout-path") {
+            state.stdout_path = Some(String::from(stdout_path));
+        }
+    }
+    if node.name == "aliases" {
+        for name in node.props.iter().map(|(name, _)| name) {
+            if let Ok(path) = node.prop_str(name) {
+                state.aliases.insert(name.clone(), String::from(path));
+            }
+        }
+    }
+    match node.prop_str("device_type") {
+        Ok("memory") => memory_probe(node, cells, state),
+        Ok("cpu") => cpu_probe(node, state),
+        _ => {}
+    }
+    if let Ok(compatible) = node.prop_str("compatible") {
+        if compatible == "virtio,mmio" {
+            virtio_probe(node, cells, state);
+        } else if compatible.contains("plic") {
+            plic_probe(node, cells, state);
+        } else if compatible.contains("16550") {
+            uart_probe(node, cells, state);
+        }
+    }
+    let child_cells = (
+        node.prop_u32("#address-cells").unwrap_or(cells.0),
+        node.prop_u32("#size-cells").unwrap_or(cells.1),
+    );
+    for child in node.children.iter() {
+        walk_dt_node(child, child_cells, state);
+    }
+}
+
+fn memory_probe(node: &Node, cells: (u32, u32), state: &mut DtState) {
+    extern "C" {
+        fn ekernel();
+    }
+    let (base, size) = match read_reg(node, cells, 0) {
+        Some(reg) => reg,
+        None => return,
+    };
+    // only the bank holding the kernel is used
+    if !(base..base + size).contains(&(ekernel as usize)) {
+        return;
+    }
+    // keep the tail of the memory for the trace buffer
+    match (base + size).checked_sub(TRACE_SIZE) {
+        Some(memory_end) if memory_end > ekernel as usize => state.info.memory_end = memory_end,
+        _ => warn!("[dt] memory {:#x}..{:#x} is too small for the trace buffer", base, base + size),
+    }
+}
+
+fn cpu_probe(node: &Node, state: &mut DtState) {
+    let hart_id = match node.prop_u32("reg") {
+        Ok(hart_id) => hart_id as usize,
+        Err(_) => return,
+    };
+    for child in node.children.iter() {
+        if child.prop_raw("interrupt-controller").is_some() {
+            if let Ok(phandle) = child.prop_u32("phandle") {
+                state.intc_harts.insert(phandle, hart_id);
+            }
+        }
+    }
+}
+
+fn plic_probe(node: &Node, cells: (u32, u32), state: &mut DtState) {
+    if let Some((base, size)) = read_reg(node, cells, 0) {
+        state.info.plic_base = base;
+        state.info.plic_size = size;
+    }
+    if let Some(interrupts) = node.prop_raw("interrupts-extended") {
+        let interrupts = interrupts.as_slice();
+        state.plic_interrupts = (0..interrupts.len() / 8)
+            .map(|i| {
+                (
+                    interrupts.read_be_u32(i * 8).unwrap(),
+                    interrupts.read_be_u32(i * 8 + 4).unwrap(),
+                )
+            })
+            .collect();
+    }
+}
+
+fn uart_probe(node: &Node, cells: (u32, u32), state: &mut DtState) {
+    if let (Some((base, size)), Some(irq)) = (read_reg(node, cells, 0), read_irq(node)) {
+        debug!("[dt] uart at {:#x}, irq {}", base, irq);
+        state.info.uarts.push(UartInfo { base, size, irq });
+        state.uart_names.push(node.name.clone());
+    }
+}
+
+fn virtio_probe(node: &Node, cells: (u32, u32), state: &mut DtState) {
+    let (base, size) = match read_reg(node, cells, 0) {
+        Some(reg) => reg,
+        None => return,
+    };
+    let header = NonNull::new(base as *mut VirtIOHeader).unwrap();
+    // the empty slots have device id 0 and are rejected here
+    let transport = match unsafe { MmioTransport::new(header) } {
+        Ok(transport) => transport,
+        Err(_) => return,
+    };
+    let device_type = transport.device_type();
+    debug!("[dt] virtio {:?} at {:#x}", device_type, base);
+    state.info.virtio.push(VirtioInfo {
+        base,
+        size,
+        irq: read_irq(node).unwrap_or(0),
+        device_type,
+    });
+}
@@ -1,9 +1,13 @@
 pub mod plic;
 pub mod uart;
+mod blk;
 mod bus;
+mod dt;
 mod net;
 
-pub use net::NetDevice;
+pub use blk::{blk_interrupt_handler, blk_irq, VirtIOBlock, BLOCK_DEVICE};
+pub use dt::{board_info, init_dt, BoardInfo, UartInfo, VirtioInfo};
+pub use net::{has_net_device, net_irq, set_mac_address, NetDevice};
 
 
 pub fn init() {
@@ -1,6 +1,6 @@
 
 use core::{any::Any, ptr::NonNull};
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec, vec::Vec};
 // const VIRTIO8: usize = 0x10008000;
 use lazy_static::*;
 use virtio_drivers::{
@@ -11,9 +11,10 @@ use virtio_drivers::{
     },
 };
 use crate::device::bus::virtio::VirtioHal;
+use smoltcp::phy::{self, Device, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
 use spin::Mutex;
 
-pub static mut NET_DEVICE_ADDR: usize = 0x10008000;
 const NET_QUEUE_SIZE: usize = 128;
 const NET_BUFFER_LEN: usize = 2048;
 
@@ -49,6 +50,56 @@ impl NetDevice {
     }
 }
 
+pub struct NetRxToken(Vec<u8>);
+
+pub struct NetTxToken;
+
+impl phy::RxToken for NetRxToken {
+    fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.0)
+    }
+}
+
+impl phy::TxToken for NetTxToken {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buf = vec![0u8; len];
+        let res = f(&mut buf);
+        NetDevice.transmit(&buf);
+        res
+    }
+}
+
+// glue for smoltcp, the frame is copied out so that the rx buffer can be recycled at once
+impl Device for NetDevice {
+    type RxToken<'a> = NetRxToken where Self: 'a;
+    type TxToken<'a> = NetTxToken where Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let buf = NetDevice.receive()?;
+        let data = buf.packet().to_vec();
+        NetDevice.recycle_rx_buffer(buf);
+        Some((NetRxToken(data), NetTxToken))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Some(NetTxToken)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.max_transmission_unit = 1514;
+        caps.max_burst_size = Some(1);
+        caps.medium = Medium::Ethernet;
+        caps
+    }
+}
+
 fn get_net_device() -> &'static mut Mutex<VirtIONet<VirtioHal, MmioTransport, NET_QUEUE_SIZE>> {
     unsafe {
         &mut *(NET_DEVICE as *mut Mutex<VirtIONet<VirtioHal, MmioTransport, NET_QUEUE_SIZE>>)
@@ -56,14 +107,55 @@ fn get_net_device() -> &'static mut Mutex<VirtIONet<VirtioHal, MmioTransport, NE
 }
 
 
+// virtio_net_config starts at this offset of the mmio registers, the mac comes first
+const VIRTIO_MMIO_CONFIG: usize = 0x100;
+
+/// Change the mac address of the device, return false if the device keeps the old one.
+/// Only legacy devices accept the write, which is what qemu provides for virtio-mmio by default.
+pub fn set_mac_address(mac: [u8; 6]) -> bool {
+    let base = match super::board_info().virtio_device(DeviceType::Network) {
+        Some(net) => net.base,
+        None => return false,
+    };
+    let config = (base + VIRTIO_MMIO_CONFIG) as *mut u8;
+    // keep the driver away while the config space is changed
+    let _net = get_net_device().lock();
+    let mut current = [0u8; 6];
+    for i in 0..mac.len() {
+        unsafe {
+            config.add(i).write_volatile(mac[i]);
+            current[i] = config.add(i).read_volatile();
+        }
+    }
+    current == mac
+}
+
+/// The irq of the net device, if there is one.
+pub fn net_irq() -> Option<u16> {
+    super::board_info().virtio_device(DeviceType::Network).map(|net| net.irq)
+}
+
+/// Whether the board has a virtio-net device, the network is down without one.
+pub fn has_net_device() -> bool {
+    super::board_info().virtio_device(DeviceType::Network).is_some()
+}
+
 pub fn init() {
+    let net_device_addr = match super::board_info().virtio_device(DeviceType::Network) {
+        Some(net) => net.base,
+        None => {
+            warn!("no virtio-net device, the network is down");
+            return;
+        }
+    };
     unsafe {
-        let header = NonNull::new(NET_DEVICE_ADDR as *mut VirtIOHeader).unwrap();
+        let header = NonNull::new(net_device_addr as *mut VirtIOHeader).unwrap();
         let transport = MmioTransport::new(header).unwrap();
-        debug!("NET_DEVICE_ADDR: {:#x}", NET_DEVICE_ADDR);
+        debug!("net device addr: {:#x}", net_device_addr);
         let virtio = VirtIONet::<VirtioHal, MmioTransport, NET_QUEUE_SIZE>
             ::new(transport, NET_BUFFER_LEN)
             .expect("can't create net device by virtio");
+        crate::net::NET_CONFIG.lock().mac = virtio.mac_address();
         let net = Arc::new(Mutex::new(virtio));
         NET_DEVICE = net.as_ref() as *const Mutex<VirtIONet<VirtioHal, MmioTransport, NET_QUEUE_SIZE>> as usize;
         core::mem::forget(net);
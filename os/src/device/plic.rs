@@ -2,76 +2,172 @@ use crate::trace::{push_trace, S_EXT_INTR_ENTER, S_EXT_INTR_EXIT};
 use crate::trap::{push_trap_record, UserTrapRecord, USER_EXT_INT_MAP};
 use crate::uart;
 use crate::net::net_interrupt_handler;
-use rv_plic::{Priority, PLIC};
+use super::{blk_interrupt_handler, blk_irq, board_info, net_irq};
 
-#[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
-pub const PLIC_BASE: usize = 0xc00_0000;
 #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
 pub const PLIC_PRIORITY_BIT: usize = 3;
 
-pub type Plic = PLIC<{ PLIC_BASE }, { PLIC_PRIORITY_BIT }>;
+const PLIC_PRIORITY_OFFSET: usize = 0;
+const PLIC_ENABLE_OFFSET: usize = 0x2000;
+const PLIC_ENABLE_STRIDE: usize = 0x80;
+const PLIC_CONTEXT_OFFSET: usize = 0x20_0000;
+const PLIC_CONTEXT_STRIDE: usize = 0x1000;
 
-pub fn get_context(hart_id: usize, mode: char) -> usize {
-    const MODE_PER_HART: usize = 3;
-    hart_id * MODE_PER_HART
-        + match mode {
-            'M' => 0,
-            'S' => 1,
-            'U' => 2,
-            _ => panic!("Wrong Mode"),
-        }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Priority(u32);
+
+impl Priority {
+    /// Threshold that lets every interrupt through.
+    pub fn any() -> Self {
+        Self(0)
+    }
+
+    /// Threshold that masks every interrupt.
+    pub fn never() -> Self {
+        Self((1 << PLIC_PRIORITY_BIT) - 1)
+    }
+
+    pub fn lowest() -> Self {
+        Self(1)
+    }
+
+    pub fn highest() -> Self {
+        Self((1 << PLIC_PRIORITY_BIT) - 1)
+    }
 }
 
-#[cfg(feature = "board_qemu")]
-pub fn init() {
-    use crate::task::hart_id;
+/// The PLIC found in the device tree, the registers follow the layout of the sifive PLIC.
+pub struct Plic;
+
+impl Plic {
+    fn base() -> usize {
+        board_info().plic_base
+    }
+
+    fn enable_reg(context: usize, irq: u16) -> (*mut u32, u32) {
+        let addr = Self::base() + PLIC_ENABLE_OFFSET + context * PLIC_ENABLE_STRIDE + (irq as usize / 32) * 4;
+        (addr as *mut u32, 1 << (irq % 32))
+    }
+
+    pub fn set_priority(irq: u16, priority: Priority) {
+        let addr = Self::base() + PLIC_PRIORITY_OFFSET + irq as usize * 4;
+        unsafe { (addr as *mut u32).write_volatile(priority.0) };
+    }
+
+    pub fn enable(context: usize, irq: u16) {
+        let (reg, mask) = Self::enable_reg(context, irq);
+        unsafe { reg.write_volatile(reg.read_volatile() | mask) };
+    }
+
+    pub fn disable(context: usize, irq: u16) {
+        let (reg, mask) = Self::enable_reg(context, irq);
+        unsafe { reg.write_volatile(reg.read_volatile() & !mask) };
+    }
+
+    /// Disable the 32 irqs sharing the `index`th enable register.
+    pub fn clear_enable(context: usize, index: usize) {
+        let addr = Self::base() + PLIC_ENABLE_OFFSET + context * PLIC_ENABLE_STRIDE + index * 4;
+        unsafe { (addr as *mut u32).write_volatile(0) };
+    }
+
+    /// Start of the page holding the threshold and the claim register of `context`.
+    pub fn context_address(context: usize) -> usize {
+        Self::base() + PLIC_CONTEXT_OFFSET + context * PLIC_CONTEXT_STRIDE
+    }
 
-    Plic::set_priority(12, Priority::lowest());
-    Plic::set_priority(13, Priority::lowest());
-    Plic::set_priority(14, Priority::lowest());
-    Plic::set_priority(15, Priority::lowest());
-    if hart_id() == 0 {
-        Plic::set_priority(8, Priority::lowest());
+    pub fn set_threshold(context: usize, threshold: Priority) {
+        let addr = Self::context_address(context);
+        unsafe { (addr as *mut u32).write_volatile(threshold.0) };
+    }
+
+    pub fn claim(context: usize) -> Option<u16> {
+        let addr = Self::context_address(context) + 4;
+        match unsafe { (addr as *mut u32).read_volatile() } {
+            0 => None,
+            irq => Some(irq as u16),
+        }
+    }
+
+    pub fn complete(context: usize, irq: u16) {
+        let addr = Self::context_address(context) + 4;
+        unsafe { (addr as *mut u32).write_volatile(irq as u32) };
     }
 }
 
-#[cfg(feature = "board_lrv")]
-pub fn init() {
-    Plic::set_priority(4, Priority::lowest());
-    Plic::set_priority(5, Priority::lowest());
-    Plic::set_priority(6, Priority::lowest());
-    Plic::set_priority(7, Priority::lowest());
+/// PLIC context of `hart_id` in `mode`, None if the hart can't take interrupts in that mode.
+pub fn get_context(hart_id: usize, mode: char) -> Option<usize> {
+    let contexts = &board_info().plic_contexts;
+    if !contexts.is_empty() {
+        return contexts.iter().position(|&context| context == (hart_id, mode));
+    }
+    // no device tree, every hart has M, S and U contexts
+    const MODE_PER_HART: usize = 3;
+    let offset = match mode {
+        'M' => 0,
+        'S' => 1,
+        'U' => 2,
+        _ => return None,
+    };
+    Some(hart_id * MODE_PER_HART + offset)
 }
 
-#[cfg(feature = "board_qemu")]
-pub fn init_hart(hart_id: usize) {
-    let context = get_context(hart_id, 'S');
-    Plic::enable(context, 12);
-    Plic::enable(context, 13);
-    Plic::enable(context, 14);
-    Plic::enable(context, 15);
-    if hart_id == 0 {
-        Plic::enable(context, 8);
+pub fn init() {
+    let board = board_info();
+    for uart in board.uarts.iter() {
+        Plic::set_priority(uart.irq, Priority::lowest());
+    }
+    if let Some(irq) = net_irq() {
+        Plic::set_priority(irq, Priority::lowest());
+    }
+    if let Some(irq) = blk_irq() {
+        Plic::set_priority(irq, Priority::lowest());
     }
-    Plic::set_threshold(context, Priority::any());
 }
 
-#[cfg(feature = "board_lrv")]
 pub fn init_hart(hart_id: usize) {
-    let context = get_context(hart_id, 'S');
-    Plic::clear_enable(context, 0);
-    Plic::clear_enable(get_context(hart_id, 'U'), 0);
-    Plic::enable(context, 4);
-    Plic::enable(context, 5);
-    Plic::enable(context, 6);
-    Plic::enable(context, 7);
+    let context = match get_context(hart_id, 'S') {
+        Some(context) => context,
+        None => {
+            warn!("[PLIC] no supervisor context for hart {}", hart_id);
+            return;
+        }
+    };
+    #[cfg(feature = "board_lrv")]
+    {
+        Plic::clear_enable(context, 0);
+        if let Some(u_context) = get_context(hart_id, 'U') {
+            Plic::clear_enable(u_context, 0);
+        }
+    }
+    for uart in board_info().uarts.iter() {
+        Plic::enable(context, uart.irq);
+    }
+    // the net and block devices are served by hart 0 only
+    if hart_id == 0 {
+        if let Some(irq) = net_irq() {
+            Plic::enable(context, irq);
+        }
+        if let Some(irq) = blk_irq() {
+            Plic::enable(context, irq);
+        }
+    }
     Plic::set_threshold(context, Priority::any());
-    Plic::set_threshold(get_context(hart_id, 'U'), Priority::any());
-    Plic::set_threshold(get_context(hart_id, 'M'), Priority::never());
+    #[cfg(feature = "board_lrv")]
+    {
+        if let Some(u_context) = get_context(hart_id, 'U') {
+            Plic::set_threshold(u_context, Priority::any());
+        }
+        if let Some(m_context) = get_context(hart_id, 'M') {
+            Plic::set_threshold(m_context, Priority::never());
+        }
+    }
 }
 
 pub fn handle_external_interrupt(hart_id: usize) {
-    let context = get_context(hart_id, 'S');
+    let context = match get_context(hart_id, 'S') {
+        Some(context) => context,
+        None => return,
+    };
     while let Some(irq) = Plic::claim(context) {
         push_trace(S_EXT_INTR_ENTER + irq as usize);
         let mut can_user_handle = false;
@@ -94,25 +190,16 @@ pub fn handle_external_interrupt(hart_id: usize) {
             // prioritize_task(*pid);
         }
         if !can_user_handle {
-            match irq {
-                #[cfg(feature = "board_qemu")]
-                8 | 12 | 13 | 14 | 15 => {
-                    if irq == 8 {
-                        // net io interrupt
-                        net_interrupt_handler();
-                    } else {
-                        uart::handle_interrupt(irq);
-                        trace!("[PLIC] irq {:?} handled by kenel", irq);
-                    }
-                }
-                #[cfg(feature = "board_lrv")]
-                4 | 5 | 6 | 7 => {
-                    uart::handle_interrupt(irq);
-                    // trace!("[PLIC] irq {:?} handled by kenel", irq);
-                }
-                _ => {
-                    warn!("[PLIC]: irq {:?} not supported!", irq);
-                }
+            if net_irq() == Some(irq) {
+                // net io interrupt
+                net_interrupt_handler();
+            } else if blk_irq() == Some(irq) {
+                blk_interrupt_handler();
+            } else if uart::irq_to_serial_id(irq).is_some() {
+                uart::handle_interrupt(irq);
+                trace!("[PLIC] irq {:?} handled by kenel", irq);
+            } else {
+                warn!("[PLIC]: irq {:?} not supported!", irq);
             }
             Plic::complete(context, irq);
         }
@@ -1,4 +1,6 @@
 use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use super::{board_info, UartInfo};
 use core::convert::Infallible;
 use embedded_hal::serial::{Read, Write};
 use lazy_static::*;
@@ -12,18 +14,6 @@ mod serial_config {
     pub use uart8250::{InterruptType, MmioUart8250};
     pub type SerialHardware = MmioUart8250<'static>;
     pub const FIFO_DEPTH: usize = 16;
-    pub const SERIAL_NUM: usize = 4;
-    pub const SERIAL_BASE_ADDRESS: usize = 0x1000_2000;
-    pub const SERIAL_ADDRESS_STRIDE: usize = 0x1000;
-    pub fn irq_to_serial_id(irq: u16) -> usize {
-        match irq {
-            12 => 0,
-            13 => 1,
-            14 => 2,
-            15 => 3,
-            _ => 0,
-        }
-    }
 }
 
 #[cfg(feature = "board_lrv")]
@@ -31,24 +21,18 @@ mod serial_config {
     pub use uart_xilinx::uart_16550::{InterruptType, MmioUartAxi16550};
     pub type SerialHardware = MmioUartAxi16550<'static>;
     pub const FIFO_DEPTH: usize = 16;
-    pub const SERIAL_NUM: usize = 4;
-    pub const SERIAL_BASE_ADDRESS: usize = 0x6000_1000;
-    pub const SERIAL_ADDRESS_STRIDE: usize = 0x1000;
-    pub fn irq_to_serial_id(irq: u16) -> usize {
-        match irq {
-            4 => 0,
-            5 => 1,
-            6 => 2,
-            7 => 3,
-            _ => 0,
-        }
-    }
 }
 
 pub use serial_config::*;
 
-pub fn get_base_addr_from_irq(irq: u16) -> usize {
-    SERIAL_BASE_ADDRESS + irq_to_serial_id(irq) * SERIAL_ADDRESS_STRIDE
+/// Index of the uart among the ones driven by the kernel, the console named by `stdout-path`
+/// belongs to the SBI and is not counted, the serial 0 is the first of the other uarts.
+pub fn irq_to_serial_id(irq: u16) -> Option<usize> {
+    board_info().uarts.iter().position(|uart| uart.irq == irq)
+}
+
+pub fn get_uart_from_irq(irq: u16) -> Option<&'static UartInfo> {
+    board_info().uarts.iter().find(|uart| uart.irq == irq)
 }
 pub struct BufferedSerial {
     pub hardware: SerialHardware,
@@ -199,10 +183,11 @@ impl Drop for BufferedSerial {
 
 #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
 lazy_static! {
-    pub static ref BUFFERED_SERIAL: [Mutex<BufferedSerial>; SERIAL_NUM] =
-        array_init::array_init(|i| Mutex::new(BufferedSerial::new(
-            SERIAL_BASE_ADDRESS + i * SERIAL_ADDRESS_STRIDE,
-        )));
+    pub static ref BUFFERED_SERIAL: Vec<Mutex<BufferedSerial>> = board_info()
+        .uarts
+        .iter()
+        .map(|uart| Mutex::new(BufferedSerial::new(uart.base)))
+        .collect();
 }
 
 #[cfg(feature = "board_lrv_seriallite")]
@@ -216,11 +201,9 @@ lazy_static! {
 
 #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
 pub fn init() {
-    for serial_id in 0..2 {
-        BUFFERED_SERIAL[serial_id].lock().hardware_init(115200);
-    }
-    for serial_id in 2..SERIAL_NUM {
-        BUFFERED_SERIAL[serial_id].lock().hardware_init(6_250_000);
+    for (serial_id, serial) in BUFFERED_SERIAL.iter().enumerate() {
+        let baud_rate = if serial_id < 2 { 115200 } else { 6_250_000 };
+        serial.lock().hardware_init(baud_rate);
     }
 }
 
@@ -230,9 +213,9 @@ pub fn init() {
 }
 
 pub fn handle_interrupt(irq: u16) {
-    BUFFERED_SERIAL[irq_to_serial_id(irq)]
-        .lock()
-        .interrupt_handler();
+    if let Some(serial_id) = irq_to_serial_id(irq) {
+        BUFFERED_SERIAL[serial_id].lock().interrupt_handler();
+    }
 }
 
 #[cfg(feature = "board_lrv_seriallite")]
@@ -265,10 +248,27 @@ pub fn handle_interrupt() {
 
 #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
 pub fn serial_putchar(serial_id: usize, c: u8) -> nb::Result<(), Infallible> {
-    BUFFERED_SERIAL[serial_id].lock().try_write(c)
+    match BUFFERED_SERIAL.get(serial_id) {
+        Some(serial) => serial.lock().try_write(c),
+        None => Err(nb::Error::WouldBlock),
+    }
 }
 
 #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
 pub fn serial_getchar(serial_id: usize) -> nb::Result<u8, Infallible> {
-    BUFFERED_SERIAL[serial_id].lock().try_read()
+    match BUFFERED_SERIAL.get(serial_id) {
+        Some(serial) => serial.lock().try_read(),
+        None => Err(nb::Error::WouldBlock),
+    }
+}
+
+/// Number of serial ports on the board.
+#[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
+pub fn serial_count() -> usize {
+    BUFFERED_SERIAL.len()
+}
+
+#[cfg(feature = "board_lrv_seriallite")]
+pub fn serial_count() -> usize {
+    1
 }
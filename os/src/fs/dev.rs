@@ -0,0 +1,116 @@
+use super::inode::OpenFlagsExt;
+use super::{File, Serial, Stdin, Stdout};
+use crate::mm::UserBuffer;
+use crate::syscall::{Errno, OpenFlags};
+use crate::trap::{push_trap_record, UserTrapRecord};
+use crate::uart::serial_count;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::{future::Future, pin::Pin};
+
+/// The directory holding the device nodes.
+pub const DEV_DIR: &str = "/dev/";
+
+/// Reads nothing and discards whatever is written.
+pub struct Null;
+
+impl File for Null {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        Ok(0)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        Ok(buf.len())
+    }
+
+    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        write_done()
+    }
+
+    fn aread(&self, _buf: UserBuffer, cid: usize, pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        read_done(cid, pid)
+    }
+}
+
+/// The devices never wait, their asynchronous writes are done before the future is returned.
+pub(super) fn write_done() -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+    Box::pin(async {})
+}
+
+/// The devices never wait, their asynchronous reads are done before the future is returned,
+/// which only wakes the reading coroutine `cid` of process `pid`.
+pub(super) fn read_done(cid: usize, pid: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+    Box::pin(async move {
+        let _ = push_trap_record(pid, UserTrapRecord {
+            cause: 1,
+            message: cid,
+        });
+    })
+}
+
+/// A device opened for reading and/or writing only.
+struct OpenedDevice {
+    device: Arc<dyn File + Send + Sync>,
+    readable: bool,
+    writable: bool,
+}
+
+impl File for OpenedDevice {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.device.read(buf)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.device.write(buf)
+    }
+
+    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        self.device.awrite(buf, pid, key)
+    }
+
+    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        self.device.aread(buf, cid, pid, key)
+    }
+}
+
+/// Open the device node `name` under `/dev/` for the access asked by `flags`,
+/// return EACCES if the device doesn't support it.
+pub fn open_device(name: &str, flags: OpenFlags) -> Result<Arc<dyn File + Send + Sync>, isize> {
+    let serial_id = name.strip_prefix("ttyS").and_then(|id| id.parse::<usize>().ok());
+    if let Some(serial_id) = serial_id {
+        if serial_id >= serial_count() {
+            return Err(Errno::ENODEV.into());
+        }
+    }
+    let device: Arc<dyn File + Send + Sync> = match (name, serial_id) {
+        ("stdin", _) => Arc::new(Stdin),
+        ("stdout", _) | ("stderr", _) => Arc::new(Stdout),
+        ("null", _) => Arc::new(Null),
+        (_, Some(0)) => Arc::new(Serial::<0>),
+        (_, Some(1)) => Arc::new(Serial::<1>),
+        (_, Some(2)) => Arc::new(Serial::<2>),
+        (_, Some(3)) => Arc::new(Serial::<3>),
+        _ => return Err(Errno::ENOENT.into()),
+    };
+    let (readable, writable) = flags.read_write();
+    if (readable && !device.readable()) || (writable && !device.writable()) {
+        return Err(Errno::EACCES.into());
+    }
+    Ok(Arc::new(OpenedDevice { device, readable, writable }))
+}
@@ -0,0 +1,295 @@
+use super::File;
+use crate::device::BLOCK_DEVICE;
+use crate::mm::UserBuffer;
+use crate::syscall::{Errno, OpenFlags, SEEK_CUR, SEEK_END, SEEK_SET};
+use crate::trap::{push_trap_record, UserTrapRecord};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::{future::Future, ops::Range, pin::Pin};
+use easy_fs::{block_cache_invalidate, BlockDevice, EasyFileSystem, Inode, BLOCK_SZ, MAX_FILE_SIZE, NAME_LIMIT};
+use lazy_static::*;
+use spin::Mutex;
+
+// one inode bitmap block holds 4096 files
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// A file of easy-fs opened by a process, the offset is shared by the dup-ed fds.
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    inner: Mutex<OSInodeInner>,
+}
+
+pub struct OSInodeInner {
+    offset: usize,
+    inode: Arc<Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
+        Self {
+            readable,
+            writable,
+            inner: Mutex::new(OSInodeInner { offset: 0, inode }),
+        }
+    }
+
+    fn read_to_user(&self, buf: UserBuffer) -> usize {
+        let mut inner = self.inner.lock();
+        let mut total_read_size = 0usize;
+        for slice in buf.buffers {
+            let read_size = inner.inode.read_at(inner.offset, slice);
+            if read_size == 0 {
+                break;
+            }
+            inner.offset += read_size;
+            total_read_size += read_size;
+        }
+        total_read_size
+    }
+
+    /// Stop at the first slice that can't be written in full, an error is only returned
+    /// if nothing has been written.
+    fn write_from_user(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        let mut total_write_size = 0usize;
+        for slice in buf.buffers.iter() {
+            let write_size = match inner.inode.write_at(inner.offset, *slice) {
+                Some(write_size) => write_size,
+                None if total_write_size > 0 => break,
+                None => return Err(Errno::ENOSPC.into()),
+            };
+            inner.offset += write_size;
+            total_write_size += write_size;
+            if write_size < slice.len() {
+                if total_write_size == 0 {
+                    return Err(Errno::EFBIG.into());
+                }
+                break;
+            }
+        }
+        Ok(total_write_size)
+    }
+}
+
+lazy_static! {
+    // None if there is no block device, the disk is formatted if it holds no easy-fs
+    pub static ref ROOT_INODE: Option<Arc<Inode>> = BLOCK_DEVICE.as_ref().map(|block_device| {
+        let block_device: Arc<dyn BlockDevice> = block_device.clone();
+        let efs = EasyFileSystem::open(block_device.clone()).unwrap_or_else(|| {
+            let num_blocks = BLOCK_DEVICE.as_ref().unwrap().num_blocks();
+            warn!("[fs] no easy-fs on the disk, formatting {} blocks", num_blocks);
+            EasyFileSystem::create(block_device, num_blocks as u32, INODE_BITMAP_BLOCKS)
+        });
+        Arc::new(EasyFileSystem::root_inode(&efs))
+    });
+}
+
+pub fn list_files() {
+    match ROOT_INODE.as_ref() {
+        Some(root_inode) => {
+            println!("/**** FILES ****");
+            for name in root_inode.ls() {
+                println!("{}", name);
+            }
+            println!("**************/");
+        }
+        None => warn!("[fs] no block device, the filesystem is not available"),
+    }
+}
+
+pub(super) trait OpenFlagsExt {
+    /// Do not check validity for simplicity
+    /// Return (readable, writable)
+    fn read_write(&self) -> (bool, bool);
+}
+
+impl OpenFlagsExt for OpenFlags {
+    fn read_write(&self) -> (bool, bool) {
+        if self.is_empty() {
+            (true, false)
+        } else if self.contains(OpenFlags::WRONLY) {
+            (false, true)
+        } else {
+            (true, true)
+        }
+    }
+}
+
+pub fn open_file(name: &str, flags: OpenFlags) -> Result<Arc<OSInode>, isize> {
+    let root_inode = ROOT_INODE.as_ref().ok_or(isize::from(Errno::ENODEV))?;
+    let (readable, writable) = flags.read_write();
+    let inode = match root_inode.find(name) {
+        Some(inode) => {
+            if flags.contains(OpenFlags::TRUNC) {
+                inode.clear();
+            }
+            inode
+        }
+        None if flags.contains(OpenFlags::CREATE) => {
+            if name.is_empty() {
+                return Err(Errno::ENOENT.into());
+            }
+            if name.len() > NAME_LIMIT {
+                return Err(Errno::ENAMETOOLONG.into());
+            }
+            // the name was checked, another process may have created it meanwhile
+            match root_inode.create(name) {
+                Some(inode) => inode,
+                None if root_inode.find(name).is_some() => return Err(Errno::EEXIST.into()),
+                None => return Err(Errno::ENOSPC.into()),
+            }
+        }
+        None => return Err(Errno::ENOENT.into()),
+    };
+    Ok(Arc::new(OSInode::new(readable, writable, inode)))
+}
+
+pub fn unlink_file(name: &str) -> Result<(), isize> {
+    let root_inode = ROOT_INODE.as_ref().ok_or(isize::from(Errno::ENODEV))?;
+    if root_inode.unlink(name) {
+        Ok(())
+    } else {
+        Err(Errno::ENOENT.into())
+    }
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        if !self.readable {
+            return Err(Errno::EBADF.into());
+        }
+        let len = self.read_to_user(buf);
+        check_io_error()?;
+        Ok(len)
+    }
+
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        if !self.writable {
+            return Err(Errno::EBADF.into());
+        }
+        let len = self.write_from_user(buf)?;
+        check_io_error()?;
+        Ok(len)
+    }
+
+    /// Data blocks are written bypassing the block cache, coroutine `key` of process `pid`
+    /// is woken up once the device acknowledges the last of them.
+    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        let mut data = if self.writable { copy_from_user(buf) } else { Vec::new() };
+        let mut inner = self.inner.lock();
+        let offset = inner.offset;
+        // the inode and the block map are updated synchronously, only the data goes async,
+        // nothing is written if the file can't grow
+        let end = offset.saturating_add(data.len()).min(MAX_FILE_SIZE);
+        if offset >= end || inner.inode.grow(end).is_none() {
+            data.clear();
+        }
+        data.truncate(end.saturating_sub(offset));
+        inner.offset += data.len();
+        let ranges = inner.inode.block_ranges(offset, data.len());
+        Box::pin(awrite_work(ranges, data, pid, key))
+    }
+
+    /// Data blocks are read bypassing the block cache, coroutine `cid` of process `pid`
+    /// is woken up once they are copied to `buf`.
+    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        let mut inner = self.inner.lock();
+        let offset = inner.offset;
+        let ranges = if self.readable { inner.inode.block_ranges(offset, buf.len()) } else { Vec::new() };
+        inner.offset += ranges.iter().map(|(_, range)| range.len()).sum::<usize>();
+        Box::pin(aread_work(ranges, buf, cid, pid))
+    }
+
+    fn lseek(&self, offset: isize, whence: usize) -> Result<usize, isize> {
+        let mut inner = self.inner.lock();
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => inner.offset as isize,
+            SEEK_END => inner.inode.size() as isize,
+            _ => return Err(Errno::EINVAL.into()),
+        };
+        match base.checked_add(offset) {
+            Some(new_offset) if new_offset >= 0 => {
+                inner.offset = new_offset as usize;
+                Ok(inner.offset)
+            }
+            _ => Err(Errno::EINVAL.into()),
+        }
+    }
+}
+
+/// EIO if the block device failed under easy-fs, which can't return the error by itself.
+fn check_io_error() -> Result<(), isize> {
+    match BLOCK_DEVICE.as_ref() {
+        Some(block_device) if block_device.take_error() => Err(Errno::EIO.into()),
+        _ => Ok(()),
+    }
+}
+
+fn copy_from_user(buf: UserBuffer) -> Vec<u8> {
+    let mut data = Vec::with_capacity(buf.len());
+    for slice in buf.buffers.iter() {
+        data.extend_from_slice(slice);
+    }
+    data
+}
+
+async fn aread_work(ranges: Vec<(usize, Range<usize>)>, buf: UserBuffer, cid: usize, pid: usize) {
+    let block_device = BLOCK_DEVICE.as_ref().unwrap();
+    let mut block = [0u8; BLOCK_SZ];
+    let mut user_iter = buf.into_iter();
+    for (block_id, range) in ranges {
+        block_cache_invalidate(block_id);
+        // the rest of the buffer is left untouched
+        if let Err(err) = block_device.aread_block(block_id, &mut block).await {
+            error!("[fs] async read of block {} failed: {:?}", block_id, err);
+            break;
+        }
+        for byte in block[range].iter() {
+            unsafe {
+                *user_iter.next().unwrap() = *byte;
+            }
+        }
+    }
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
+
+async fn awrite_work(ranges: Vec<(usize, Range<usize>)>, data: Vec<u8>, pid: usize, cid: usize) {
+    let block_device = BLOCK_DEVICE.as_ref().unwrap();
+    let mut block = [0u8; BLOCK_SZ];
+    let mut written = 0;
+    for (block_id, range) in ranges {
+        block_cache_invalidate(block_id);
+        // read-modify-write for a partial block
+        if range.len() < BLOCK_SZ {
+            if let Err(err) = block_device.aread_block(block_id, &mut block).await {
+                error!("[fs] async read of block {} failed: {:?}", block_id, err);
+                break;
+            }
+        }
+        let len = range.len();
+        block[range].copy_from_slice(&data[written..written + len]);
+        written += len;
+        if let Err(err) = block_device.awrite_block(block_id, &block).await {
+            error!("[fs] async write of block {} failed: {:?}", block_id, err);
+            break;
+        }
+    }
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
@@ -1,43 +1,126 @@
 use alloc::{
     collections::VecDeque,
     sync::{Arc, Weak},
+    vec::Vec,
 };
 use spin::Mutex;
 
+use crate::fs::ReadHelper;
 use crate::mm::UserBuffer;
-use crate::task::suspend_current_and_run_next;
+use crate::task::{add_task, block_current_and_run_next, current_task, suspend_current_and_run_next, TaskControlBlock};
+use crate::trap::{push_trap_record, UserTrapRecord};
 
 use super::File;
 
-const MAIL_BUFFER_SIZE: usize = 256;
-const MAILBOX_SIZE: usize = 16;
+pub const MAIL_BUFFER_SIZE: usize = 256;
+pub const MAILBOX_SIZE: usize = 16;
 use alloc::boxed::Box;
 use core::{future::Future, pin::Pin};
 
+#[derive(Clone)]
 pub struct MailBox {
-    inner: Mutex<MailBoxInner>,
+    inner: Arc<Mutex<MailBoxInner>>,
 }
 
 pub struct MailBoxInner {
     mails: VecDeque<Arc<Mutex<MailRingBuffer>>>,
+    // threads blocked in a synchronous read
+    read_tasks: VecDeque<Arc<TaskControlBlock>>,
+    // kernel coroutines doing an asynchronous read
+    read_cids: VecDeque<usize>,
+    // threads blocked in `sys_mailwrite` while the mailbox is full
+    write_tasks: VecDeque<Arc<TaskControlBlock>>,
+    // the owner has exited, no more mails are accepted
+    closed: bool,
+}
+
+impl MailBoxInner {
+    /// Only a mail whose write end has been closed is complete, taking it frees a slot
+    /// for the blocked writers.
+    fn pop_mail(&mut self) -> Option<Arc<Mutex<MailRingBuffer>>> {
+        let mail = match self.mails.front() {
+            Some(mail) if mail.lock().all_write_ends_closed() => self.mails.pop_front(),
+            _ => None,
+        };
+        if mail.is_some() {
+            for task in self.write_tasks.drain(..) {
+                add_task(task);
+            }
+        }
+        mail
+    }
 }
 
 impl MailBox {
     pub fn new() -> Self {
         Self {
-            inner: Mutex::new(MailBoxInner {
+            inner: Arc::new(Mutex::new(MailBoxInner {
                 mails: VecDeque::new(),
-            }),
+                read_tasks: VecDeque::new(),
+                read_cids: VecDeque::new(),
+                write_tasks: VecDeque::new(),
+                closed: false,
+            })),
         }
     }
 
-    pub fn create_socket(&self) -> Arc<Socket> {
+    /// Return None if there are already `MAILBOX_SIZE` mails in the mailbox.
+    pub fn create_socket(&self) -> Option<Arc<Socket>> {
+        let mut inner = self.inner.lock();
+        if inner.closed || inner.mails.len() >= MAILBOX_SIZE {
+            return None;
+        }
         debug!("create socket");
         let buffer = Arc::new(Mutex::new(MailRingBuffer::new()));
         let write_end = Arc::new(Socket::write_end_with_buffer(buffer.clone()));
         buffer.lock().set_write_end(&write_end);
-        self.inner.lock().mails.push_back(buffer);
-        write_end
+        inner.mails.push_back(buffer);
+        Some(write_end)
+    }
+
+    /// Create the write end of a new mail, blocking current thread while the mailbox is full.
+    /// Return None once the owner of the mailbox has exited.
+    pub fn create_socket_blocking(&self) -> Option<Arc<Socket>> {
+        loop {
+            if let Some(socket) = self.create_socket() {
+                return Some(socket);
+            }
+            let mut inner = self.inner.lock();
+            if inner.closed {
+                return None;
+            }
+            // a reader may have taken a mail since the check above
+            if inner.mails.len() < MAILBOX_SIZE {
+                continue;
+            }
+            inner.write_tasks.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+        }
+    }
+
+    /// Wake all the readers, should be called after the write end is dropped.
+    /// Each of them checks the mailbox again, those that get no mail wait again.
+    pub fn wake_reader(&self) {
+        let mut inner = self.inner.lock();
+        let cids: Vec<usize> = inner.read_cids.drain(..).collect();
+        for task in inner.read_tasks.drain(..) {
+            add_task(task);
+        }
+        drop(inner);
+        for cid in cids {
+            lib_so::re_back(cid, 0);
+        }
+    }
+
+    /// Called when the owner exits, the blocked writers give up.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock();
+        inner.closed = true;
+        inner.mails.clear();
+        for task in inner.write_tasks.drain(..) {
+            add_task(task);
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -49,47 +132,36 @@ impl MailBox {
     }
 }
 
+/// Copy a complete mail to user buffer, the bytes that do not fit in are discarded.
+fn copy_mail(mail: &Arc<Mutex<MailRingBuffer>>, buf: UserBuffer) -> usize {
+    let mut ring_buffer = mail.lock();
+    let mut buf_iter = buf.into_iter();
+    let mut read_size: usize = 0;
+    for _ in 0..ring_buffer.available_read() {
+        if let Some(byte_ref) = buf_iter.next() {
+            unsafe {
+                *byte_ref = ring_buffer.read_byte();
+            }
+            read_size += 1;
+        } else {
+            break;
+        }
+    }
+    read_size
+}
+
 impl File for MailBox {
     fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
-        let mut inner = self.inner.lock();
-        match inner.mails.front() {
-            Some(mail) => {
-                let mut buf_iter = buf.into_iter();
-                let mut read_size: usize = 0;
-
-                loop {
-                    let mut ring_buffer = mail.lock();
-                    let loop_read = ring_buffer.available_read();
-                    if loop_read == 0 {
-                        if ring_buffer.all_write_ends_closed() {
-                            drop(ring_buffer);
-                            inner.mails.pop_front();
-                            return Ok(read_size);
-                        }
-                        drop(ring_buffer);
-                        suspend_current_and_run_next();
-                        continue;
-                    }
-
-                    for _ in 0..loop_read {
-                        if let Some(byte_ref) = buf_iter.next() {
-                            unsafe {
-                                *byte_ref = ring_buffer.read_byte();
-                            }
-                            read_size += 1;
-                        } else {
-                            drop(ring_buffer);
-                            inner.mails.pop_front();
-                            return Ok(read_size);
-                        }
-                    }
-
-                    drop(ring_buffer);
-                    inner.mails.pop_front();
-                    return Ok(read_size);
-                }
+        loop {
+            let mut inner = self.inner.lock();
+            if let Some(mail) = inner.pop_mail() {
+                drop(inner);
+                return Ok(copy_mail(&mail, buf));
             }
-            None => Err(-1),
+            // register under the mailbox lock, so that a writer finishing right now can not miss us
+            inner.read_tasks.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
         }
     }
 
@@ -100,18 +172,41 @@ impl File for MailBox {
         unimplemented!();
     }
     fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
-        unimplemented!();
+        Box::pin(aread_work(self.clone(), buf, cid, pid))
     }
 
     fn readable(&self) -> bool {
-        todo!()
+        true
     }
 
     fn writable(&self) -> bool {
-        todo!()
+        false
     }
 }
 
+async fn aread_work(mail_box: MailBox, buf: UserBuffer, cid: usize, pid: usize) {
+    let mut helper = Box::new(ReadHelper::new());
+    let mail = loop {
+        let mut inner = mail_box.inner.lock();
+        if let Some(mail) = inner.pop_mail() {
+            break mail;
+        }
+        // register under the mailbox lock, so that a writer finishing right now can not miss us
+        let kernel_cid = lib_so::current_cid(true);
+        if !inner.read_cids.contains(&kernel_cid) {
+            inner.read_cids.push_back(kernel_cid);
+        }
+        drop(inner);
+        helper.as_mut().await;
+    };
+    copy_mail(&mail, buf);
+    // 将读协程加入到回调队列中，使得用户态的协程执行器能够唤醒读协程
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
+
 pub struct Socket {
     writable: bool,
     mail: Arc<Mutex<MailRingBuffer>>,
@@ -166,11 +261,11 @@ impl File for Socket {
     }
 
     fn readable(&self) -> bool {
-        todo!()
+        false
     }
 
     fn writable(&self) -> bool {
-        todo!()
+        true
     }
 }
 
@@ -1,13 +1,18 @@
+mod dev;
+mod inode;
 mod mail;
 mod pipe;
 mod serial;
 pub mod stdio;
 
 use crate::mm::UserBuffer;
+use crate::net::UDP;
+use crate::syscall::{Errno, OpenFlags};
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use core::{future::Future, pin::Pin, task::{Poll, Context}};
 
-pub use mail::{MailBox, Socket};
+pub use mail::{MailBox, Socket, MAILBOX_SIZE, MAIL_BUFFER_SIZE};
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
@@ -15,13 +20,40 @@ pub trait File: Send + Sync {
     fn write(&self, buf: UserBuffer) -> Result<usize, isize>;
     fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>;
     fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>;
-
+    /// Only udp sockets support `bind`/`sendto`/`recvfrom`.
+    fn as_udp(&self) -> Option<&UDP> {
+        None
+    }
+    /// Only regular files support `lseek`, return the new offset.
+    fn lseek(&self, _offset: isize, _whence: usize) -> Result<usize, isize> {
+        Err(Errno::ESPIPE.into())
+    }
 }
 
+use dev::{open_device, DEV_DIR};
+pub use inode::{list_files, OSInode, ROOT_INODE};
+use inode::{open_file, unlink_file};
 pub use pipe::{make_pipe, Pipe};
 pub use serial::Serial;
 pub use stdio::{Stdin, Stdout};
 
+/// Open `path` in the kernel namespace: the device nodes live under `/dev/`, the other
+/// paths name files in the root directory of easy-fs, with or without the leading `/`.
+pub fn open_path(path: &str, flags: OpenFlags) -> Result<Arc<dyn File + Send + Sync>, isize> {
+    match path.strip_prefix(DEV_DIR) {
+        Some(name) => open_device(name, flags),
+        None => Ok(open_file(path.strip_prefix('/').unwrap_or(path), flags)?),
+    }
+}
+
+/// Remove a file of easy-fs, the device nodes can't be removed.
+pub fn unlink_path(path: &str) -> Result<(), isize> {
+    if path.starts_with(DEV_DIR) {
+        return Err(Errno::EPERM.into());
+    }
+    unlink_file(path.strip_prefix('/').unwrap_or(path))
+}
+
 
 pub struct ReadHelper(usize);
 
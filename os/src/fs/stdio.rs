@@ -1,6 +1,6 @@
+use super::dev::{read_done, write_done};
 use super::File;
 use crate::mm::UserBuffer;
-use crate::print;
 use crate::uart::{serial_getchar, serial_putchar};
 use core::fmt::{self, Write};
 use alloc::boxed::Box;
@@ -11,8 +11,11 @@ pub struct Stdin;
 pub struct Stdout;
 
 impl File for Stdin {
+    /// At most one byte is read at a time.
     fn read(&self, mut user_buf: UserBuffer) -> Result<usize, isize> {
-        assert_eq!(user_buf.len(), 1);
+        if user_buf.len() == 0 {
+            return Ok(0);
+        }
         // busy loop
         if let Ok(ch) = serial_getchar(0) {
             unsafe {
@@ -26,11 +29,12 @@ impl File for Stdin {
     fn write(&self, _user_buf: UserBuffer) -> Result<usize, isize> {
         panic!("Cannot write to stdin!");
     }
-    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
-        unimplemented!();
+    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        write_done()
     }
-    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>{
-        unimplemented!();
+    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>{
+        let _ = self.read(buf);
+        read_done(cid, pid)
     }
 
     fn readable(&self) -> bool {
@@ -47,16 +51,20 @@ impl File for Stdout {
         panic!("Cannot read from stdout!");
     }
     fn write(&self, user_buf: UserBuffer) -> Result<usize, isize> {
+        // the bytes may be binary or cut in the middle of a character, they are not a str
         for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
+            for &byte in buffer.iter() {
+                let _ = serial_putchar(0, byte);
+            }
         }
         Ok(user_buf.len())
     }
-    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
-        unimplemented!();
+    fn awrite(&self, buf: UserBuffer, _pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>> {
+        let _ = self.write(buf);
+        write_done()
     }
-    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>{
-        unimplemented!();
+    fn aread(&self, _buf: UserBuffer, cid: usize, pid: usize, _key: usize) -> Pin<Box<dyn Future<Output = ()> + 'static + Send + Sync>>{
+        read_done(cid, pid)
     }
 
     fn readable(&self) -> bool {
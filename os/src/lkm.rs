@@ -34,6 +34,7 @@ fn add_lkm_image(){
     lib_so::init_current_cid(get_symbol_addr(&SHARED_ELF, "current_cid"));
     lib_so::init_max_prio_pid(get_symbol_addr(&SHARED_ELF, "max_prio_pid"));
     lib_so::init_update_prio(get_symbol_addr(&SHARED_ELF, "update_prio"));
+    lib_so::init_cancel(get_symbol_addr(&SHARED_ELF, "cancel"));
 }
 
 
@@ -9,7 +9,6 @@
 
 
 extern crate alloc;
-extern crate rv_plic;
 
 #[macro_use]
 extern crate bitflags;
@@ -61,6 +60,8 @@ pub fn rust_main(hart_id: usize, device_tree_addr: usize) -> ! {
         
         clear_bss();
         logger::init();
+        mm::init_heap();
+        device::init_dt(device_tree_addr);
         mm::init();
         debug!("[kernel {}] Hello, world!", hart_id);
         debug!("device_tree_addr: {:#x}", device_tree_addr);
@@ -68,7 +69,6 @@ pub fn rust_main(hart_id: usize, device_tree_addr: usize) -> ! {
         trace::init();
         trace::trace_test();
         trap::init();
-        // device::init_dt(device_tree_addr);
         device::init();
         plic::init();
         plic::init_hart(hart_id);
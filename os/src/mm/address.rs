@@ -153,6 +153,9 @@ impl VirtPageNum {
 }
 
 impl PhysAddr {
+    pub fn get_ref<T>(&self) -> &'static T {
+        unsafe { (self.0 as *const T).as_ref().unwrap() }
+    }
     pub fn get_mut<T>(&self) -> &'static mut T {
         unsafe { (self.0 as *mut T).as_mut().unwrap() }
     }
@@ -211,6 +214,10 @@ where
         self.r
     }
 
+    pub fn contains(&self, value: T) -> bool {
+        self.l <= value && value < self.r
+    }
+
     pub fn is_overlapped(&self, other: &Self) -> bool {
         (self.l <= other.l && other.l < self.r)
             || (self.l < other.r && other.r <= self.r)
@@ -46,6 +46,7 @@ pub fn init_heap() {
     // error!("memory {:#x}", unsafe{ &mut MEMORY as *mut u8 as usize });
     unsafe {
         EXECUTOR.ready_queue = vec![VecDeque::new(); lib_so::PRIO_NUM];
+        EXECUTOR.user_wake_hook = Some(crate::trap::wake_user_coroutine);
     }
 }
 
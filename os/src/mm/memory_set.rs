@@ -2,7 +2,11 @@ use super::{frame_alloc, FrameTracker};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, PAGE_SIZE, TRACE_SIZE, TRAMPOLINE, HEAP_BUFFER};
+use crate::config::{memory_end, CPU_NUM, PAGE_SIZE, TRACE_SIZE, TRAMPOLINE, HEAP_BUFFER, USER_TRAP_BUFFER, MMAP_BASE, MMAP_END};
+use crate::syscall::{Errno, MAP_FIXED, MAP_POPULATE};
+use crate::device::board_info;
+use crate::sbi::remote_sfence_vma;
+use crate::task::hart_id;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -37,17 +41,30 @@ lazy_static! {
 pub struct MemorySet {
     page_table: PageTable,
     areas: Vec<MapArea>,
+    // the user stacks of the threads start here, 0 if there are none
+    ustack_base: usize,
 }
 
 pub fn kernel_token() -> usize {
     KERNEL_SPACE.lock().token()
 }
 
+/// Flush the TLB of this hart and of the others, where the other threads of a process
+/// may still be running with the permissions that were just taken away.
+fn flush_tlb_all_harts() {
+    unsafe { sfence_vma_all() }
+    let others = ((1 << CPU_NUM) - 1) & !(1 << hart_id());
+    if others != 0 {
+        remote_sfence_vma(others, 0, usize::MAX);
+    }
+}
+
 impl MemorySet {
     pub fn new_bare() -> Self {
         Self {
             page_table: PageTable::new(),
             areas: Vec::new(),
+            ustack_base: 0,
         }
     }
     pub fn token(&self) -> usize {
@@ -76,8 +93,28 @@ impl MemorySet {
             self.areas.remove(idx);
         }
     }
+    /// Map the populated page of the user trap buffer, which the kernel writes through the
+    /// physical frame, in place of the copy a forked child inherits. Return false if no
+    /// frame is left.
+    pub fn map_user_trap_buffer(&mut self) -> bool {
+        self.remove_area_with_start_vpn(VirtAddr::from(USER_TRAP_BUFFER).floor());
+        let mut map_area = MapArea::new(
+            USER_TRAP_BUFFER.into(),
+            (USER_TRAP_BUFFER + PAGE_SIZE).into(),
+            MapType::Framed,
+            MapPermission::R | MapPermission::W | MapPermission::U,
+        );
+        unsafe { sfence_vma_all() }
+        if !map_area.map(&mut self.page_table) {
+            return false;
+        }
+        self.areas.push(map_area);
+        true
+    }
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+        if !map_area.map(&mut self.page_table) {
+            panic!("no frame left to map {:?}", map_area.vpn_range.get_start());
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
@@ -164,17 +201,18 @@ impl MemorySet {
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                memory_end().into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
             None,
         );
+        let board = board_info();
         debug!("mapping plic");
         memory_set.push(
             MapArea::new(
-                (0xc00_0000 as usize).into(),
-                (0x1000_0000 as usize).into(),
+                board.plic_base.into(),
+                (board.plic_base + board.plic_size).into(),
                 MapType::Mmio,
                 MapPermission::R | MapPermission::W,
             ),
@@ -182,32 +220,34 @@ impl MemorySet {
         );
 
         debug!("mapping virt device");
-        memory_set.push(
-            MapArea::new(
-                (0x1000_6000 as usize).into(),
-                (0x1000_9000 as usize).into(),
-                MapType::Mmio,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        for virtio in board.virtio.iter() {
+            memory_set.push(
+                MapArea::new(
+                    virtio.base.into(),
+                    (virtio.base + virtio.size).into(),
+                    MapType::Mmio,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
         debug!("mapping uart");
-        use crate::uart;
-        #[cfg(any(feature = "board_qemu", feature = "board_lrv"))]
-        memory_set.push(
-            MapArea::new(
-                (uart::SERIAL_BASE_ADDRESS).into(),
-                (uart::SERIAL_BASE_ADDRESS + uart::SERIAL_NUM * uart::SERIAL_ADDRESS_STRIDE).into(),
-                MapType::Mmio,
-                MapPermission::R | MapPermission::W,
-            ),
-            None,
-        );
+        for uart in board.uarts.iter() {
+            memory_set.push(
+                MapArea::new(
+                    uart.base.into(),
+                    (uart.base + uart.size).into(),
+                    MapType::Mmio,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
         debug!("mapping trace");
         memory_set.push(
             MapArea::new(
-                MEMORY_END.into(),
-                (MEMORY_END + TRACE_SIZE).into(),
+                memory_end().into(),
+                (memory_end() + TRACE_SIZE).into(),
                 MapType::Mmio,
                 MapPermission::R | MapPermission::W,
             ),
@@ -261,12 +301,13 @@ impl MemorySet {
         let mut user_stack_bottom: usize = max_end_va.into();
         // guard page
         user_stack_bottom += PAGE_SIZE;
+        memory_set.ustack_base = user_stack_bottom;
 
         // map trace
         memory_set.push(
             MapArea::new(
-                MEMORY_END.into(),
-                (MEMORY_END + TRACE_SIZE).into(),
+                memory_end().into(),
+                (memory_end() + TRACE_SIZE).into(),
                 MapType::Mmio,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
@@ -280,7 +321,7 @@ impl MemorySet {
             unsafe { *vdso_item_paddr = ptr; }
             debug!("get func {} ptr {:#x}", vdso_item.0.to_lowercase(), ptr);
         }
-        // 另外分配一个物理页，只存放 heap 的虚拟地址
+        // 另外分配一个物理页，存放 heap 的虚拟地址，之后是 trace 缓冲区的地址
         memory_set.push(
             MapArea::new(
                 HEAP_BUFFER.into(),
@@ -291,7 +332,10 @@ impl MemorySet {
         None);
         let sharedsche_paddr = translate_writable_va(memory_set.token(), HEAP_BUFFER)
             .unwrap() as *mut usize;
-        unsafe { *sharedsche_paddr = data_section_vir_addr; }
+        unsafe {
+            *sharedsche_paddr = data_section_vir_addr;
+            *sharedsche_paddr.add(1) = memory_end();
+        }
         debug!("map heap buffer done");
         unsafe { asm!("fence.i") }
         (
@@ -300,15 +344,29 @@ impl MemorySet {
             elf.header.pt2.entry_point() as usize,
         )
     }
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    /// The user frames are shared read-only with `user_space` instead of being copied,
+    /// the first store of either process to such a page copies it in `handle_page_fault`.
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         memory_set.add_user_module(&crate::lkm::SHARED_SCHE_MEMORYSET);
+        memory_set.ustack_base = user_space.ustack_base;
 
-        // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
+            let mut new_area = MapArea::from_another(area);
+            if area.is_cow_shareable() {
+                let pte_flags = area.pte_flags(false);
+                for (vpn, frame) in area.data_frames.iter() {
+                    if let Some(pte_flags) = pte_flags {
+                        user_space.page_table.remap(*vpn, frame.ppn, pte_flags);
+                        memory_set.page_table.map(*vpn, frame.ppn, pte_flags);
+                    }
+                    new_area.data_frames.insert(*vpn, frame.clone());
+                }
+                memory_set.areas.push(new_area);
+                continue;
+            }
             memory_set.push(new_area, None);
             // copy data from another space
             if area.map_type != MapType::Mmio {
@@ -321,9 +379,47 @@ impl MemorySet {
                 }
             }
         }
+        // the parent keeps running on the page table whose W bits were just cleared,
+        // its other threads too
+        flush_tlb_all_harts();
         unsafe { asm!("fence.i") }
         memory_set
     }
+    /// Resolve a page fault raised by an `access` (R, W or X) of a user page, return
+    /// false if the access is not allowed or no frame is left. A page of a lazy area is
+    /// allocated on the first touch, a store to a copy-on-write page gives the current
+    /// owner its own copy.
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        let area = match self.areas.iter_mut().find(|area| area.vpn_range.contains(vpn)) {
+            Some(area) if area.map_type == MapType::Framed && area.map_perm.contains(access) => area,
+            _ => return false,
+        };
+        let pte = match self.page_table.translate(vpn) {
+            Some(pte) if pte.is_valid() => pte,
+            _ => return area.map_one(&mut self.page_table, vpn),
+        };
+        // resolved by another thread in the meantime
+        if access != MapPermission::W || pte.writable() {
+            return true;
+        }
+        let frame = area.data_frames.get_mut(&vpn).unwrap();
+        // the last owner takes the frame back without copying
+        if Arc::strong_count(frame) > 1 {
+            let new_frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            *frame = Arc::new(new_frame);
+        }
+        let pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+        self.page_table.remap(vpn, frame.ppn, pte_flags);
+        unsafe { sfence_vma_all() }
+        true
+    }
     pub fn activate(&self) {
         let satp = self.page_table.token();
         unsafe {
@@ -347,68 +443,186 @@ impl MemorySet {
         false
     }
 
-    pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> Result<isize, isize> {
-        if port & !7 != 0 || port & 7 == 0 || len > 1 << 30 {
-            Err(-1)
-        } else {
-            let start_va: VirtAddr = VirtAddr::from(start);
-            if start_va != start_va.floor().into() {
-                return Err(-1);
-            }
-            let end_va: VirtAddr = VirtAddr::from(start + len).ceil().into();
+    /// Neither an area nor a page mapped outside of the areas, such as the trampoline
+    /// and the shared scheduler module, is in the range.
+    fn is_free_range(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        !self.is_mapped_area(start_vpn.into(), end_vpn.into())
+            && VPNRange::new(start_vpn, end_vpn)
+                .into_iter()
+                .all(|vpn| self.translate(vpn).map_or(true, |pte| !pte.is_valid()))
+    }
 
-            if self.is_mapped_area(start_va, end_va) {
-                return Err(-1);
+    /// First fit in `[MMAP_BASE, MMAP_END)`, nothing is mapped there but the areas.
+    fn find_free_range(&self, pages: usize) -> Option<VirtPageNum> {
+        let mut start_vpn = VirtAddr::from(MMAP_BASE).floor();
+        let max_vpn = VirtAddr::from(MMAP_END).floor();
+        loop {
+            let end_vpn = VirtPageNum(start_vpn.0 + pages);
+            if end_vpn > max_vpn {
+                return None;
+            }
+            let range = VPNRange::new(start_vpn, end_vpn);
+            let overlapped_end = self
+                .areas
+                .iter()
+                .filter(|area| area.vpn_range.is_overlapped(&range))
+                .map(|area| area.vpn_range.get_end())
+                .max();
+            match overlapped_end {
+                Some(area_end) => start_vpn = area_end,
+                None => return Some(start_vpn),
             }
-            self.insert_framed_area(
-                start_va,
-                end_va,
-                MapPermission::from_bits((port << 1 | 0b10000) as u8).unwrap(),
-            );
-
-            Ok((usize::from(end_va) - usize::from(start_va)) as isize)
         }
     }
 
-    pub fn munmap(&mut self, start: usize, len: usize) -> Result<isize, isize> {
-        let mut start_va: VirtAddr = VirtAddr::from(start);
-        if start_va != start_va.floor().into() {
-            return Err(-1);
+    /// Split the areas at `vpn` so that no area crosses it.
+    fn split_areas_at(&mut self, vpn: VirtPageNum) {
+        if let Some(idx) = self.areas.iter().position(|area| {
+            area.vpn_range.get_start() < vpn && area.vpn_range.contains(vpn)
+        }) {
+            let right = self.areas[idx].split_off(vpn);
+            self.areas.push(right);
         }
-        let end_va: VirtAddr = VirtAddr::from(start + len).ceil().into();
-
-        let mut to_unmap: Vec<usize> = Vec::new();
+    }
 
-        for (i, area) in self.areas.iter().enumerate() {
-            if area
-                .vpn_range
-                .is_overlapped(&VPNRange::new(start_va.into(), end_va.into()))
-            {
-                to_unmap.push(i);
-            }
-        }
+    /// Whether `[start_vpn, end_vpn)` is covered by user areas only, holes are allowed.
+    fn is_user_range(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let range = VPNRange::new(start_vpn, end_vpn);
+        self.areas
+            .iter()
+            .filter(|area| area.vpn_range.is_overlapped(&range))
+            .all(|area| area.map_type == MapType::Framed && area.map_perm.contains(MapPermission::U))
+    }
 
-        to_unmap.sort_by_key(|i| self.areas[*i].vpn_range.get_start());
+    /// Whether `[start_vpn, end_vpn)` reaches the user stacks and the trap contexts, or the
+    /// pages shared with the kernel above `MMAP_END`. The user only manages its ELF image
+    /// below the stacks and the mmap region.
+    fn is_kernel_range(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) -> bool {
+        let stacks = VPNRange::new(VirtAddr::from(self.ustack_base).floor(), VirtAddr::from(MMAP_BASE).floor());
+        VPNRange::new(start_vpn, end_vpn).is_overlapped(&stacks) || end_vpn > VirtAddr::from(MMAP_END).floor()
+    }
 
-        for i in &to_unmap {
-            if start_va == self.areas[*i].vpn_range.get_start().into() {
-                start_va = self.areas[*i].vpn_range.get_end().into();
+    /// Remove `[start_vpn, end_vpn)` from the user areas, which are split if needed.
+    fn unmap_user_range(&mut self, start_vpn: VirtPageNum, end_vpn: VirtPageNum) {
+        self.split_areas_at(start_vpn);
+        self.split_areas_at(end_vpn);
+        let range = VPNRange::new(start_vpn, end_vpn);
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            if self.areas[idx].vpn_range.is_overlapped(&range) {
+                let mut area = self.areas.remove(idx);
+                area.unmap(&mut self.page_table);
             } else {
-                return Err(-1);
+                idx += 1;
             }
         }
-        if start_va != end_va {
-            return Err(-1);
-        }
+        flush_tlb_all_harts();
+    }
 
-        to_unmap.sort_by(|l, r| r.cmp(l));
+    /// Map anonymous memory, `port` holds the R/W/X bits as `PROT_*` does, the pages of
+    /// `PROT_NONE` can not be accessed at all.
+    /// The pages are only allocated when touched unless `MAP_POPULATE` is given.
+    /// Without `MAP_FIXED`, `start` is a hint and the kernel picks another place if the
+    /// range is taken, with it the old user mappings in the range are replaced.
+    /// Return the start address of the mapping.
+    pub fn mmap(&mut self, start: usize, len: usize, port: usize, flags: usize) -> Result<usize, isize> {
+        if port & !7 != 0 || len == 0 || len > 1 << 30 || start % PAGE_SIZE != 0 {
+            return Err(Errno::EINVAL.into());
+        }
+        let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+        let hint_vpn = VirtAddr::from(start).floor();
+        let hint_end_vpn = match hint_vpn.0.checked_add(pages) {
+            Some(end) if start != 0 => Some(VirtPageNum(end)),
+            _ => None,
+        };
+        let start_vpn = if flags & MAP_FIXED != 0 {
+            let end_vpn = hint_end_vpn.ok_or(isize::from(Errno::EINVAL))?;
+            if !self.is_user_range(hint_vpn, end_vpn) || self.is_kernel_range(hint_vpn, end_vpn) {
+                return Err(Errno::EINVAL.into());
+            }
+            self.unmap_user_range(hint_vpn, end_vpn);
+            if !self.is_free_range(hint_vpn, end_vpn) {
+                return Err(Errno::EINVAL.into());
+            }
+            hint_vpn
+        } else {
+            match hint_end_vpn {
+                Some(end_vpn) if self.is_free_range(hint_vpn, end_vpn) && !self.is_kernel_range(hint_vpn, end_vpn) => {
+                    hint_vpn
+                }
+                _ => self.find_free_range(pages).ok_or(isize::from(Errno::ENOMEM))?,
+            }
+        };
+        let mut map_area = MapArea::new(
+            start_vpn.into(),
+            VirtPageNum(start_vpn.0 + pages).into(),
+            MapType::Framed,
+            MapPermission::from_bits((port << 1 | 0b10000) as u8).unwrap(),
+        );
+        if flags & MAP_POPULATE != 0 && !map_area.map(&mut self.page_table) {
+            map_area.unmap(&mut self.page_table);
+            return Err(Errno::ENOMEM.into());
+        }
+        self.areas.push(map_area);
+        Ok(VirtAddr::from(start_vpn).into())
+    }
 
-        for i in to_unmap {
-            self.areas[i].unmap(&mut self.page_table);
-            self.areas.remove(i);
+    /// Unmapping a range without mappings is not an error, as in Linux.
+    pub fn munmap(&mut self, start: usize, len: usize) -> Result<isize, isize> {
+        let start_va: VirtAddr = VirtAddr::from(start);
+        if !start_va.aligned() || len == 0 {
+            return Err(Errno::EINVAL.into());
+        }
+        let end_vpn = VirtAddr::from(start.checked_add(len).ok_or(isize::from(Errno::EINVAL))?).ceil();
+        if !self.is_user_range(start_va.floor(), end_vpn) || self.is_kernel_range(start_va.floor(), end_vpn) {
+            return Err(Errno::EINVAL.into());
         }
+        self.unmap_user_range(start_va.floor(), end_vpn);
+        Ok(0)
+    }
 
-        Ok(len as isize)
+    /// Change the permission of the whole pages in `[start, start + len)`, which must be
+    /// mapped by user areas. Pages still shared copy-on-write stay read-only.
+    pub fn mprotect(&mut self, start: usize, len: usize, port: usize) -> Result<isize, isize> {
+        let start_va: VirtAddr = VirtAddr::from(start);
+        if port & !7 != 0 || !start_va.aligned() || len == 0 {
+            return Err(Errno::EINVAL.into());
+        }
+        let start_vpn = start_va.floor();
+        let end_vpn = VirtAddr::from(start.checked_add(len).ok_or(isize::from(Errno::EINVAL))?).ceil();
+        if self.is_kernel_range(start_vpn, end_vpn) {
+            return Err(Errno::EACCES.into());
+        }
+        let range = VPNRange::new(start_vpn, end_vpn);
+        let mut covered = 0;
+        for area in self.areas.iter().filter(|area| area.vpn_range.is_overlapped(&range)) {
+            if area.map_type != MapType::Framed || !area.map_perm.contains(MapPermission::U) {
+                return Err(Errno::EACCES.into());
+            }
+            let l = area.vpn_range.get_start().max(start_vpn);
+            let r = area.vpn_range.get_end().min(end_vpn);
+            covered += r.0 - l.0;
+        }
+        if covered != end_vpn.0 - start_vpn.0 {
+            return Err(Errno::ENOMEM.into());
+        }
+        self.split_areas_at(start_vpn);
+        self.split_areas_at(end_vpn);
+        let map_perm = MapPermission::from_bits((port << 1 | 0b10000) as u8).unwrap();
+        for area in self.areas.iter_mut().filter(|area| area.vpn_range.is_overlapped(&range)) {
+            area.map_perm = map_perm;
+            for (vpn, frame) in area.data_frames.iter() {
+                let mapped = self.page_table.translate(*vpn).map_or(false, |pte| pte.is_valid());
+                match area.pte_flags(Arc::strong_count(frame) == 1) {
+                    Some(pte_flags) if mapped => self.page_table.remap(*vpn, frame.ppn, pte_flags),
+                    Some(pte_flags) => self.page_table.map(*vpn, frame.ppn, pte_flags),
+                    None if mapped => self.page_table.unmap(*vpn),
+                    None => {}
+                }
+            }
+        }
+        flush_tlb_all_harts();
+        Ok(0)
     }
 
     pub fn mmio_map(&mut self, start: usize, len: usize, port: usize) -> Result<isize, isize> {
@@ -564,7 +778,8 @@ impl MemorySet {
 
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    // frames shared with forked processes have more than one owner
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
 }
@@ -593,7 +808,30 @@ impl MapArea {
             map_perm: another.map_perm,
         }
     }
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    /// The kernel writes the trap contexts (mapped without U) and the user trap buffer
+    /// through their physical frames, so only the other user frames are shared on fork.
+    fn is_cow_shareable(&self) -> bool {
+        self.map_type == MapType::Framed
+            && self.map_perm.contains(MapPermission::U)
+            && self.vpn_range.get_start() != VirtAddr::from(USER_TRAP_BUFFER).floor()
+    }
+    /// The flags of the mapped pages, without W if `writable` is false. None for an area
+    /// of `PROT_NONE`, whose pages have no PTE since a valid PTE without R, W and X
+    /// points to the next level of the page table.
+    fn pte_flags(&self, writable: bool) -> Option<PTEFlags> {
+        let map_perm = if writable {
+            self.map_perm
+        } else {
+            self.map_perm - MapPermission::W
+        };
+        if map_perm.intersects(MapPermission::R | MapPermission::W | MapPermission::X) {
+            PTEFlags::from_bits(map_perm.bits)
+        } else {
+            None
+        }
+    }
+    /// Return false if no frame is left.
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
         let ppn: PhysPageNum;
         match self.map_type {
             MapType::Identical => {
@@ -603,25 +841,51 @@ impl MapArea {
                 ppn = PhysPageNum(vpn.0);
             }
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
                 trace!("map_one: vpn {:?} ppn {:?}", vpn, ppn);
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        if let Some(pte_flags) = self.pte_flags(true) {
+            page_table.map(vpn, ppn, pte_flags);
+        }
+        true
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         if let MapType::Framed = self.map_type {
-            self.data_frames.remove(&vpn);
+            // the pages of a lazy area are only mapped once touched
+            if self.data_frames.remove(&vpn).is_none() {
+                return;
+            }
         }
-        page_table.unmap(vpn);
+        // nor are those of PROT_NONE
+        if page_table.translate(vpn).map_or(false, |pte| pte.is_valid()) {
+            page_table.unmap(vpn);
+        }
+    }
+    /// Move `[vpn, end)` with its frames to a new area, `vpn` must be inside.
+    pub fn split_off(&mut self, vpn: VirtPageNum) -> Self {
+        let right = Self {
+            vpn_range: VPNRange::new(vpn, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&vpn),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), vpn);
+        right
     }
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    /// Return false if no frame is left, the pages mapped so far are kept.
+    pub fn map(&mut self, page_table: &mut PageTable) -> bool {
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            if !self.map_one(page_table, vpn) {
+                return false;
+            }
         }
+        true
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
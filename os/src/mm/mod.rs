@@ -10,14 +10,18 @@ pub use frame_allocator::{frame_alloc, FrameTracker, frame_alloc_more, frame_dea
 pub use memory_set::remap_test;
 pub use memory_set::{MapPermission, MemorySet, KERNEL_SPACE, kernel_token};
 pub use page_table::{
-    translate_writable_va, translated_byte_buffer, translated_refmut, translated_str,
+    read_user, read_user_str, translate_writable_va, translated_byte_buffer, translated_str, write_user, write_user_bytes,
     PageTableEntry, UserBuffer, UserBufferIterator, PageTable
 };
 use page_table::PTEFlags;
 pub use heap_allocator::MutAllocator;
 
-pub fn init() {
+/// The heap is needed to parse the device tree, which is done before `init`.
+pub fn init_heap() {
     heap_allocator::init_heap();
+}
+
+pub fn init() {
     frame_allocator::init_frame_allocator();
     KERNEL_SPACE.lock().activate();
 }
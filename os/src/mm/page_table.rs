@@ -1,4 +1,4 @@
-use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use super::{frame_alloc, FrameTracker, MapPermission, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -117,6 +117,14 @@ impl PageTable {
         let flags = flags | PTEFlags::A | PTEFlags::D;
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
     }
+    /// Change the frame or the flags of a mapped vpn, the caller flushes the TLB.
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+        let pte = self.find_pte_create(vpn).unwrap();
+        assert!(pte.is_valid(), "vpn {:?} is invalid before remapping", vpn);
+        #[cfg(feature = "board_lrv")]
+        let flags = flags | PTEFlags::A | PTEFlags::D;
+        *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+    }
     #[allow(unused)]
     pub fn unmap(&mut self, vpn: VirtPageNum) {
         // TODO: Add remote TLB shootdown
@@ -140,11 +148,27 @@ impl PageTable {
     }
 }
 
+/// The kernel accesses user memory through the physical frames, so a page not populated
+/// yet or shared copy-on-write is faulted in first, as if the user touched it.
+fn fault_in(page_table: &PageTable, token: usize, vpn: VirtPageNum, write: bool) {
+    match page_table.translate(vpn) {
+        Some(pte) if pte.is_valid() && (pte.writable() || !write) => {}
+        _ => {
+            let va = VirtAddr::from(vpn).into();
+            // a buffer of a read-only page may still be read
+            if !(write && crate::task::handle_page_fault(token, va, MapPermission::W)) {
+                crate::task::handle_page_fault(token, va, MapPermission::R);
+            }
+        }
+    }
+}
+
 pub fn translate_writable_va(token: usize, va: usize) -> Result<usize, isize> {
     let va = VirtAddr::from(va);
     let vpn = va.floor();
     let page_table = PageTable::from_token(token);
-    let pte = page_table.translate(vpn).unwrap();
+    fault_in(&page_table, token, vpn, true);
+    let pte = page_table.translate(vpn).ok_or(-1)?;
     if !pte.writable() || !pte.is_valid() {
         return Err(-1);
     }
@@ -154,24 +178,29 @@ pub fn translate_writable_va(token: usize, va: usize) -> Result<usize, isize> {
     Ok(usize::from(pa))
 }
 
+/// The user pages of `ptr..ptr + len`, Err if one of them is not mapped.
+/// With `write` they are destinations, copy-on-write pages are copied first and
+/// read-only pages are refused; sources are never copied.
 pub fn translated_byte_buffer(
     token: usize,
     ptr: *const u8,
     len: usize,
+    write: bool,
 ) -> Result<Vec<&'static mut [u8]>, isize> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len).ok_or(-1)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
+        fault_in(&page_table, token, vpn, write);
         let pte = page_table.translate(vpn);
         if pte.is_none() {
             return Err(-1);
         }
         let pte = pte.unwrap();
-        if !pte.readable() || !pte.is_valid() {
+        if !pte.readable() || !pte.is_valid() || (write && !pte.writable()) {
             return Err(-1);
         }
         let ppn = pte.ppn();
@@ -193,6 +222,7 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
+        fault_in(&page_table, token, VirtAddr::from(va).floor(), false);
         let ch: u8 = *(page_table
             .translate_va(VirtAddr::from(va))
             .unwrap()
@@ -207,13 +237,50 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
     string
 }
 
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+/// Copy a `T` from user memory, None if any byte of it is not mapped.
+pub fn read_user<T: Copy>(token: usize, ptr: *const T) -> Option<T> {
+    let buffers = translated_byte_buffer(token, ptr as *const u8, core::mem::size_of::<T>(), false).ok()?;
+    let mut value = core::mem::MaybeUninit::<T>::uninit();
+    let mut dst = value.as_mut_ptr() as *mut u8;
+    for buffer in buffers {
+        unsafe {
+            core::ptr::copy_nonoverlapping(buffer.as_ptr(), dst, buffer.len());
+            dst = dst.add(buffer.len());
+        }
+    }
+    Some(unsafe { value.assume_init() })
+}
+
+/// Copy `value` to user memory, None if any byte of the destination is not mapped or read-only.
+pub fn write_user<T: Copy>(token: usize, ptr: *mut T, value: &T) -> Option<()> {
+    let buffers = translated_byte_buffer(token, ptr as *const u8, core::mem::size_of::<T>(), true).ok()?;
+    UserBuffer::new(buffers).write_value(value);
+    Some(())
+}
+
+/// Copy `bytes` to user memory, None if any byte of the destination is not mapped or read-only.
+pub fn write_user_bytes(token: usize, ptr: *mut u8, bytes: &[u8]) -> Option<()> {
+    let buffers = translated_byte_buffer(token, ptr, bytes.len(), true).ok()?;
+    let mut written = 0;
+    for buffer in buffers {
+        buffer.copy_from_slice(&bytes[written..written + buffer.len()]);
+        written += buffer.len();
+    }
+    Some(())
+}
+
+/// Copy a null-terminated string from user memory, None if it runs into an unmapped page.
+pub fn read_user_str(token: usize, ptr: *const u8) -> Option<String> {
+    let mut string = String::new();
+    let mut va = ptr as usize;
+    loop {
+        let ch = read_user(token, va as *const u8)?;
+        if ch == 0 {
+            return Some(string);
+        }
+        string.push(ch as char);
+        va = va.checked_add(1)?;
+    }
 }
 
 pub struct UserBuffer {
@@ -231,6 +298,18 @@ impl UserBuffer {
         }
         total
     }
+    /// Copy the bytes of `value` to the buffer, those that do not fit in are discarded.
+    pub fn write_value<T: Copy>(&mut self, value: &T) {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+        };
+        let mut written = 0;
+        for buffer in self.buffers.iter_mut() {
+            let n = buffer.len().min(bytes.len() - written);
+            buffer[..n].copy_from_slice(&bytes[written..written + n]);
+            written += n;
+        }
+    }
 }
 
 impl IntoIterator for UserBuffer {
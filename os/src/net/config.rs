@@ -0,0 +1,59 @@
+use spin::Mutex;
+use crate::syscall::IfConfig;
+
+lazy_static::lazy_static! {
+    // defaults of the qemu user network, overridden by the device tree and the virtio-net config space
+    pub static ref NET_CONFIG: Mutex<IfConfig> = Mutex::new(IfConfig {
+        mac: [0x52, 0x54, 0x00, 0x12, 0x34, 0x56],
+        ip: [10, 0, 2, 15],
+        netmask: [255, 255, 255, 0],
+        gateway: [10, 0, 2, 2],
+    });
+}
+
+/// Length of the network prefix, None if the netmask is not contiguous.
+pub fn prefix_len(netmask: [u8; 4]) -> Option<u8> {
+    let mask = u32::from_be_bytes(netmask);
+    let len = mask.leading_ones();
+    if mask.checked_shl(len).unwrap_or(0) == 0 {
+        Some(len as u8)
+    } else {
+        None
+    }
+}
+
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut addr = [0u8; 4];
+    let mut parts = s.split('.');
+    for byte in addr.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(addr)
+}
+
+/// Take the address from the kernel command line, in the form of
+/// `ip=<client-ip>:<server-ip>:<gw-ip>:<netmask>` like linux, empty fields are left unchanged.
+pub fn parse_bootargs(config: &mut IfConfig, bootargs: &str) {
+    let ip = match bootargs.split_whitespace().find_map(|arg| arg.strip_prefix("ip=")) {
+        Some(ip) => ip,
+        None => return,
+    };
+    let fields: alloc::vec::Vec<&str> = ip.split(':').collect();
+    let field = |i: usize| fields.get(i).copied().filter(|s| !s.is_empty());
+    if let Some(ip) = field(0).and_then(parse_ipv4) {
+        config.ip = ip;
+    }
+    if let Some(gateway) = field(2).and_then(parse_ipv4) {
+        config.gateway = gateway;
+    }
+    if let Some(netmask) = field(3).and_then(parse_ipv4) {
+        if prefix_len(netmask).is_some() {
+            config.netmask = netmask;
+        } else {
+            warn!("ignore invalid netmask {}", field(3).unwrap());
+        }
+    }
+}
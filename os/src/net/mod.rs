@@ -1,107 +1,162 @@
+mod config;
 mod port_table;
 mod tcp;
+mod udp;
 mod socket;
 
-use spin::Mutex;
-use alloc::{sync::Arc, vec, collections::BTreeMap};
-use lose_net_stack::{results::Packet, LoseStack, MacAddress, TcpFlags, IPv4};
-use socket::{get_socket, push_data, get_s_a_by_index};
-use port_table::check_accept;
-use crate::device::NetDevice;
-
-pub use port_table::{accept, listen, port_acceptable, PortFd};
-pub struct NetStack(Mutex<LoseStack>);
+use core::sync::atomic::{AtomicU16, Ordering};
+use spin::{Mutex, MutexGuard};
+use alloc::{vec, vec::Vec};
+use smoltcp::iface::{Config, Interface, SocketHandle, SocketSet};
+use smoltcp::socket::{tcp, Socket};
+use smoltcp::time::Instant;
+use smoltcp::wire::{EthernetAddress, IpAddress, IpCidr, Ipv4Address};
+use crate::device::{has_net_device, set_mac_address, NetDevice};
+use crate::syscall::{Errno, IfConfig};
+use crate::timer::get_time_us;
 
+pub use config::{parse_bootargs, prefix_len, NET_CONFIG};
+pub use port_table::{accept, listen, PortFd};
+pub use socket::forget_waiter;
+pub use tcp::TCP;
+pub use udp::UDP;
 
+pub struct NetStack {
+    pub iface: Interface,
+    pub sockets: SocketSet<'static>,
+    // closed by the user, kept until the teardown with the remote is finished
+    pub closing: Vec<SocketHandle>,
+}
 
 impl NetStack {
     pub fn new() -> Self {
-        unsafe {
-            NetStack(Mutex::new(LoseStack::new(
-                IPv4::new(10, 0, 2, 15),
-                MacAddress::new([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]),
-            )))
+        let if_config = *NET_CONFIG.lock();
+        let mut config = Config::new();
+        config.hardware_addr = Some(EthernetAddress(if_config.mac).into());
+        config.random_seed = get_time_us() as u64;
+        let iface = Interface::new(config, &mut NetDevice);
+        let mut net_stack = NetStack {
+            iface,
+            sockets: SocketSet::new(vec![]),
+            closing: Vec::new(),
+        };
+        net_stack.set_config(&if_config);
+        net_stack
+    }
+
+    /// Replace the address of the interface, the netmask must have been checked by `prefix_len`.
+    /// The connections bound to the old address are not moved and will time out.
+    pub fn set_config(&mut self, config: &IfConfig) {
+        self.iface.set_hardware_addr(EthernetAddress(config.mac).into());
+        let prefix_len = prefix_len(config.netmask).unwrap();
+        self.iface.update_ip_addrs(|ip_addrs| {
+            ip_addrs.clear();
+            ip_addrs
+                .push(IpCidr::new(IpAddress::Ipv4(Ipv4Address(config.ip)), prefix_len))
+                .unwrap();
+        });
+        if config.gateway == [0; 4] {
+            self.iface.routes_mut().remove_default_ipv4_route();
+        } else {
+            self.iface
+                .routes_mut()
+                .add_default_ipv4_route(Ipv4Address(config.gateway))
+                .unwrap();
         }
     }
 }
 
 lazy_static::lazy_static! {
-    pub static ref LOSE_NET_STACK: Arc<NetStack> = Arc::new(NetStack::new());
-    pub static ref ASYNC_RDMP: Arc<Mutex<BTreeMap<usize, usize>>> = Arc::new(Mutex::new(BTreeMap::new()));
+    // None if the board has no virtio-net device
+    pub static ref NET_STACK: Option<Mutex<NetStack>> = has_net_device().then(|| Mutex::new(NetStack::new()));
 }
 
+/// Whether the network is up, the socket syscalls fail with ENETDOWN otherwise.
+pub fn net_available() -> bool {
+    NET_STACK.is_some()
+}
 
-pub fn net_interrupt_handler() {
-    match NetDevice.receive() {
-        Some(buf) => {
-            let packet = LOSE_NET_STACK
-                .0
-                .lock()
-                .analysis(buf.packet());
-            match packet {
-                Packet::ARP(arp_packet) => {
-                    let lose_stack: spin::MutexGuard<LoseStack> = LOSE_NET_STACK.0.lock();
-                    let reply_packet = arp_packet
-                        .reply_packet(lose_stack.ip, lose_stack.mac)
-                        .expect("can't build reply");
-                    let reply_data = reply_packet.build_data();
-                    NetDevice.transmit(&reply_data)
-                }
-        
-                Packet::TCP(tcp_packet) => {
-                    
-                    let target = tcp_packet.source_ip;
-                    let lport = tcp_packet.dest_port;
-                    let rport = tcp_packet.source_port;
-                    let flags = tcp_packet.flags;
-                    debug!("[TCP] target: {}, lport: {}, rport: {}", target, lport, rport);
-                    if flags.contains(TcpFlags::S) {
-                        // if it has a port to accept, then response the request
-                        if check_accept(lport, &tcp_packet).is_some() {
-                            let mut reply_packet = tcp_packet.ack();
-                            reply_packet.flags = TcpFlags::S | TcpFlags::A;
-                            NetDevice.transmit(&reply_packet.build_data());
-                        } else {
-                            error!("check accept failed");
-                        }
-                        NetDevice.recycle_rx_buffer(buf);
-                        return;
-                    } else if tcp_packet.flags.contains(TcpFlags::F) {
-                        // tcp disconnected
-                        let reply_packet = tcp_packet.ack();
-                        NetDevice.transmit(&reply_packet.build_data());
-        
-                        let mut end_packet: lose_net_stack::packets::tcp::TCPPacket = reply_packet.ack();
-                        end_packet.flags |= TcpFlags::F;
-                        NetDevice.transmit(&end_packet.build_data());
-                    } else if tcp_packet.flags.contains(TcpFlags::A) && tcp_packet.data_len == 0 {
-                        let reply_packet = tcp_packet.ack();
-                        NetDevice.transmit(&reply_packet.build_data());
-                        NetDevice.recycle_rx_buffer(buf);
-                        return;
-                    } else {
-                        let reply_packet = tcp_packet.ack();
-                        NetDevice.transmit(&reply_packet.build_data());
-                    }
-        
-                    if let Some(socket_index) = get_socket(target, lport, rport) {
-                        let packet_seq = tcp_packet.seq;
-                        if let Some((seq, ack)) = get_s_a_by_index(socket_index) {
-                            debug!("packet_seq: {}, ack: {}", packet_seq, ack);
-                            if ack == packet_seq && tcp_packet.data_len > 0 {
-                                debug!("push data: {}, {}", socket_index, tcp_packet.data_len);
-                                push_data(socket_index, &tcp_packet);
-                            }
-                        }
-                    }
-                }
-                _ => {}
-
-            }
-            NetDevice.recycle_rx_buffer(buf);
+/// Lock the stack of a socket, the sockets are only created when the network is up.
+pub fn lock_net_stack() -> MutexGuard<'static, NetStack> {
+    NET_STACK.as_ref().expect("the network is down").lock()
+}
+
+/// Apply a new address to the running interface, see `NetStack::set_config`.
+/// Fails with ENETDOWN without a net device, EIO if the device refuses the new mac.
+pub fn set_net_config(config: IfConfig) -> Result<(), isize> {
+    if !net_available() {
+        return Err(Errno::ENETDOWN.into());
+    }
+    if config.mac != NET_CONFIG.lock().mac && !set_mac_address(config.mac) {
+        return Err(Errno::EIO.into());
+    }
+    let mut net_stack = lock_net_stack();
+    net_stack.set_config(&config);
+    *NET_CONFIG.lock() = config;
+    drop(net_stack);
+    net_poll();
+    Ok(())
+}
+
+const EPHEMERAL_PORT_START: u16 = 49152;
+
+static NEXT_EPHEMERAL_PORT: AtomicU16 = AtomicU16::new(EPHEMERAL_PORT_START);
+
+/// Whether a socket of the same protocol already uses local `port`.
+pub fn port_in_use(sockets: &SocketSet, port: u16, udp: bool) -> bool {
+    sockets.iter().any(|(_, socket)| match socket {
+        Socket::Udp(socket) => udp && socket.endpoint().port == port,
+        Socket::Tcp(socket) => {
+            !udp && (socket.listen_endpoint().port == port
+                || socket.local_endpoint().map_or(false, |endpoint| endpoint.port == port))
+        }
+        _ => false,
+    })
+}
+
+/// Local port for the sockets that are not bound explicitly, None if all of them are in use.
+pub fn alloc_ephemeral_port(sockets: &SocketSet, udp: bool) -> Option<u16> {
+    for _ in EPHEMERAL_PORT_START..=u16::MAX {
+        let port = NEXT_EPHEMERAL_PORT
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |port| {
+                Some(if port == u16::MAX { EPHEMERAL_PORT_START } else { port + 1 })
+            })
+            .unwrap();
+        if !port_in_use(sockets, port, udp) {
+            return Some(port);
         }
-        None => {
-            debug!("do nothing");
-        },
     }
-}
\ No newline at end of file
+    None
+}
+
+pub fn now() -> Instant {
+    Instant::from_micros(get_time_us() as i64)
+}
+
+/// Drive the interface: receive the pending frames, retransmit and flush the send buffers.
+/// Everyone waiting on a socket is woken up if anything happened, and checks the socket again.
+pub fn net_poll() {
+    let mut net_stack = match NET_STACK.as_ref() {
+        Some(net_stack) => net_stack.lock(),
+        None => return,
+    };
+    let NetStack { iface, sockets, closing } = &mut *net_stack;
+    let changed = iface.poll(now(), &mut NetDevice, sockets);
+    closing.retain(|&handle| {
+        if sockets.get::<tcp::Socket>(handle).state() == tcp::State::Closed {
+            sockets.remove(handle);
+            false
+        } else {
+            true
+        }
+    });
+    drop(net_stack);
+    if changed {
+        socket::wake_all();
+        port_table::wake_acceptors();
+    }
+}
+
+pub fn net_interrupt_handler() {
+    net_poll();
+}
@@ -1,14 +1,22 @@
 use crate::{fs::File, task::add_task};
+use crate::syscall::Errno;
 use crate::task::TaskControlBlock;
 use alloc::{sync::Arc, vec::Vec};
 use spin::Mutex;
 use lazy_static::lazy_static;
-use lose_net_stack::packets::tcp::TCPPacket;
-use super::tcp::TCP;
+use smoltcp::iface::{SocketHandle, SocketSet};
+use smoltcp::socket::tcp;
+use super::tcp::{new_tcp_socket, TCP};
+use super::lock_net_stack;
+
+// number of sockets listening on a port at the same time
+const LISTEN_BACKLOG: usize = 16;
+
 pub struct Port {
     pub port: u16,
-    pub receivable: bool,
-    pub schedule: Option<Arc<TaskControlBlock>>,
+    pub backlog: Vec<SocketHandle>,
+    // threads blocked in accept
+    pub waiters: Vec<Arc<TaskControlBlock>>,
 }
 
 lazy_static! {
@@ -16,8 +24,17 @@ lazy_static! {
         unsafe { Mutex::new(Vec::new()) };
 }
 
+fn listen_socket(sockets: &mut SocketSet<'static>, port: u16) -> Option<SocketHandle> {
+    let mut socket = new_tcp_socket();
+    socket.listen(port).ok()?;
+    Some(sockets.add(socket))
+}
+
 pub fn listen(port: u16) -> Option<usize> {
     let mut listen_table = LISTEN_TABLE.lock();
+    if listen_table.iter().flatten().any(|x| x.port == port) {
+        return None;
+    }
     let mut index = usize::MAX;
     for i in 0..listen_table.len() {
         if listen_table[i].is_none() {
@@ -26,10 +43,15 @@ pub fn listen(port: u16) -> Option<usize> {
         }
     }
 
+    let mut net_stack = lock_net_stack();
+    let mut backlog = Vec::new();
+    for _ in 0..LISTEN_BACKLOG {
+        backlog.push(listen_socket(&mut net_stack.sockets, port)?);
+    }
     let listen_port = Port {
         port,
-        receivable: false,
-        schedule: None,
+        backlog,
+        waiters: Vec::new(),
     };
 
     if index == usize::MAX {
@@ -41,81 +63,56 @@ pub fn listen(port: u16) -> Option<usize> {
     }
 }
 
-// can accept request
-pub fn accept(listen_index: usize, task: Arc<TaskControlBlock>) {
+/// Take an established connection of the port, or leave `task` registered to be woken up
+/// when the state of the backlog changes.
+pub fn accept(listen_index: usize, task: Arc<TaskControlBlock>) -> Result<Option<TCP>, isize> {
     let mut listen_table = LISTEN_TABLE.lock();
-    assert!(listen_index < listen_table.len());
-    let listen_port = listen_table[listen_index].as_mut();
-    assert!(listen_port.is_some());
-    let listen_port = listen_port.unwrap();
-    listen_port.receivable = true;
-    listen_port.schedule = Some(task);
-}
-
-pub fn port_acceptable(listen_index: usize) -> bool {
-    let mut listen_table = LISTEN_TABLE.lock();
-    assert!(listen_index < listen_table.len());
-
-    let listen_port = listen_table[listen_index].as_mut();
-    listen_port.map_or(false, |x| x.receivable)
-}
-
-// check whether it can accept request
-pub fn check_accept(port: u16, tcp_packet: &TCPPacket) -> Option<()> {
-    let mut listen_table = LISTEN_TABLE.lock();
-    let mut listen_ports: Vec<&mut Option<Port>> = listen_table
-            .iter_mut()
-            .filter(|x| match x {
-                Some(t) => t.port == port && t.receivable == true,
-                None => false,
-            })
-            .collect();
-    if listen_ports.len() == 0 {
-        debug!("no listen");
-        None
-    } else {
-        let listen_port = listen_ports[0].as_mut().unwrap();
-        let task = listen_port.schedule.clone().unwrap();
-        
-        if accept_connection(port, tcp_packet, task) {
-            listen_port.receivable = false;
-            add_task(listen_port.schedule.take().unwrap());
-            Some(())
-        } else {
-            None
+    let listen_port = match listen_table.get_mut(listen_index) {
+        Some(Some(listen_port)) => listen_port,
+        _ => return Err(Errno::EINVAL.into()),
+    };
+    // register before looking at the backlog, a connection established after the check
+    // wakes us up through `wake_acceptors`
+    if !listen_port.waiters.iter().any(|waiter| Arc::ptr_eq(waiter, &task)) {
+        listen_port.waiters.push(task.clone());
+    }
+    let mut net_stack = lock_net_stack();
+    let mut established = None;
+    for (i, &handle) in listen_port.backlog.iter().enumerate() {
+        let socket = net_stack.sockets.get_mut::<tcp::Socket>(handle);
+        match socket.state() {
+            tcp::State::Listen | tcp::State::SynReceived => {}
+            // reset by the remote before being accepted
+            tcp::State::Closed => {
+                let _ = socket.listen(listen_port.port);
+            }
+            _ => {
+                established = Some(i);
+                break;
+            }
         }
-        
-        
     }
+    let i = match established {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    listen_port.waiters.retain(|waiter| !Arc::ptr_eq(waiter, &task));
+    let handle = listen_port.backlog.remove(i);
+    if let Some(new_handle) = listen_socket(&mut net_stack.sockets, listen_port.port) {
+        listen_port.backlog.push(new_handle);
+    }
+    drop(net_stack);
+    debug!("[accept] port: {}, handle: {:?}", listen_port.port, handle);
+    Ok(Some(TCP::new(handle)))
 }
 
-pub fn accept_connection(_port: u16, tcp_packet: &TCPPacket, task: Arc<TaskControlBlock>) -> bool {
-    let process = task.process.upgrade().unwrap();
-    let mut inner = process.acquire_inner_lock();
-    let fd = inner.alloc_fd();
-    debug!("[accept_connection]: local fd: {}, sport: {}, dport: {}", fd, tcp_packet.dest_port, tcp_packet.source_port);
-    match TCP::new(
-        tcp_packet.source_ip,
-        tcp_packet.dest_port,
-        tcp_packet.source_port,
-        0,
-        tcp_packet.seq + 1,
-    ) {
-        Some(tcp_socket) => {
-            inner.fd_table[fd] = Some(Arc::new(tcp_socket));
-            let cx = task.acquire_inner_lock().get_trap_cx();
-            cx.x[10] = fd;
-            true
-        }
-        _ => {
-            debug!("invaild accept req");
-            false
+pub fn wake_acceptors() {
+    let mut listen_table = LISTEN_TABLE.lock();
+    for listen_port in listen_table.iter_mut().flatten() {
+        for task in listen_port.waiters.drain(..) {
+            add_task(task);
         }
     }
-
-    
-
-   
 }
 
 
@@ -130,7 +127,18 @@ impl PortFd {
 
 impl Drop for PortFd {
     fn drop(&mut self) {
-        LISTEN_TABLE.lock()[self.0] = None
+        if let Some(listen_port) = LISTEN_TABLE.lock()[self.0].take() {
+            // the blocked acceptors find the port closed
+            for task in listen_port.waiters {
+                add_task(task);
+            }
+            // reset the connections not accepted yet
+            let mut net_stack = lock_net_stack();
+            for handle in listen_port.backlog {
+                net_stack.sockets.get_mut::<tcp::Socket>(handle).abort();
+                net_stack.closing.push(handle);
+            }
+        }
     }
 }
 
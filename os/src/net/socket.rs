@@ -1,21 +1,39 @@
-use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
-use lose_net_stack::{IPv4, packets::tcp::TCPPacket};
+use smoltcp::iface::SocketHandle;
 use spin::Mutex;
 
-use crate::{task::{TaskControlBlock, add_task}, net::ASYNC_RDMP};
+use crate::task::{TaskControlBlock, add_task};
 
-// TODO: specify the protocol, TCP or UDP
+// shared by TCP and UDP, the protocol state lives in the smoltcp socket set
 pub struct Socket {
-    pub raddr: IPv4,                // remote address
-    pub lport: u16,                 // local port
-    pub rport: u16,                 // rempote port
-    pub buffers: VecDeque<Vec<u8>>, // datas
-    pub seq: u32,
-    pub ack: u32,
-    pub block_task: Option<Arc<TaskControlBlock>>,
+    pub handle: SocketHandle,                      // handle in the smoltcp socket set
+    pub sent: usize,                               // bytes queued for sending so far
+    pub read_waiters: Vec<usize>,                  // kernel coroutines waiting for data
+    pub write_waiters: Vec<usize>,                 // kernel coroutines waiting for the remote ack
+    pub block_tasks: Vec<Arc<TaskControlBlock>>,   // threads blocked on the socket
+    pub error: Option<isize>,                      // failure of an async connect, reported by the next read/write
+}
+
+impl Socket {
+    /// Wake `cid` the next time anything happens on the socket, registered at most once.
+    pub fn wait_read(&mut self, cid: usize) {
+        if !self.read_waiters.contains(&cid) {
+            self.read_waiters.push(cid);
+        }
+    }
+
+    pub fn wait_write(&mut self, cid: usize) {
+        if !self.write_waiters.contains(&cid) {
+            self.write_waiters.push(cid);
+        }
+    }
+
+    /// Must be followed by `block_current_and_run_next`.
+    pub fn block(&mut self, task: Arc<TaskControlBlock>) {
+        self.block_tasks.push(task);
+    }
 }
 
 const MAX_SOCKETS_NUM: usize = 512;
@@ -30,43 +48,12 @@ pub fn get_mutex_socket(index: usize) -> Option<Arc<Mutex<Socket>>> {
     socket_table.get(index).map_or(None, |x| (*x).clone())
 }
 
-pub fn get_s_a_by_index(index: usize) -> Option<(u32, u32)> {
-    let socket_table = SOCKET_TABLE.lock();
-
-    assert!(index < socket_table.len());
-
-    socket_table.get(index).map_or(None, |x| match x {
-        Some(x) => {
-            let socket = x.lock();
-            return Some((socket.seq, socket.ack));
-        }
-        None => None
-    })
+/// Whether `socket` is still the one registered at `index`, i.e. it has not been closed.
+pub fn is_socket_open(index: usize, socket: &Arc<Mutex<Socket>>) -> bool {
+    get_mutex_socket(index).map_or(false, |x| Arc::ptr_eq(&x, socket))
 }
 
-
-pub fn get_socket(raddr: IPv4, lport: u16, rport: u16) -> Option<usize> {
-    let socket_table = SOCKET_TABLE.lock();
-    for i in 0..socket_table.len() {
-        let sock = &socket_table[i];
-        if sock.is_none() {
-            continue;
-        }
-
-        let sock = sock.as_ref().unwrap().lock();
-        if sock.raddr == raddr && sock.lport == lport && sock.rport == rport {
-            return Some(i);
-        }
-    }
-    None
-}
-
-
-pub fn add_socket(raddr: IPv4, lport: u16, rport: u16, seq: u32, ack: u32) -> Option<usize> {
-    if get_socket(raddr, lport, rport).is_some() {
-        return None;
-    }
-
+pub fn add_socket(handle: SocketHandle) -> usize {
     let mut socket_table = SOCKET_TABLE.lock();
     let mut index = usize::MAX;
     for i in 0..socket_table.len() {
@@ -77,21 +64,20 @@ pub fn add_socket(raddr: IPv4, lport: u16, rport: u16, seq: u32, ack: u32) -> Op
     }
 
     let socket = Socket {
-        raddr,
-        lport,
-        rport,
-        buffers: VecDeque::new(),
-        seq: seq,
-        ack: ack,
-        block_task: None,
+        handle,
+        sent: 0,
+        read_waiters: Vec::new(),
+        write_waiters: Vec::new(),
+        block_tasks: Vec::new(),
+        error: None,
     };
 
     if index == usize::MAX {
         socket_table.push(Some(Arc::new(Mutex::new(socket))));
-        Some(socket_table.len() - 1)
+        socket_table.len() - 1
     } else {
         socket_table[index] = Some(Arc::new(Mutex::new(socket)));
-        Some(index)
+        index
     }
 }
 
@@ -99,43 +85,35 @@ pub fn remove_socket(index: usize) {
     let mut socket_table = SOCKET_TABLE.lock();
 
     assert!(socket_table.len() > index);
-    socket_table[index] = None;
-}
-
-pub fn push_data(index: usize, packet: &TCPPacket) {
-    let mut socket_table = SOCKET_TABLE.lock();
-    if socket_table.len() <= index || socket_table[index].is_none() {
-        return;
+    if let Some(socket) = socket_table[index].take() {
+        // let the waiters find out the socket is closed
+        wake_socket(&mut socket.lock());
     }
-    assert!(socket_table.len() > index);
-    assert!(socket_table[index].is_some());
-    let mut socket = socket_table[index].as_mut().unwrap().lock();
-    socket.buffers.push_back(packet.data.to_vec());
-    socket.ack = packet.seq + packet.data_len as u32;
-    socket.seq = packet.ack;
-    debug!("[push_data] index: {}, socket.ack:{}, socket.seq:{}", index, socket.ack, socket.seq);
-    match socket.block_task.take() {
-        Some(task) => {
-            debug!("wake read task");
-            add_task(task);
-        }
-        _ => {
+}
 
-        }
+fn wake_socket(socket: &mut Socket) {
+    for task in socket.block_tasks.drain(..) {
+        add_task(task);
     }
-
-    if let Some(cid) = ASYNC_RDMP.lock().remove(&index) {
-        // debug!("wake read coroutine task");
+    for cid in socket.read_waiters.drain(..).chain(socket.write_waiters.drain(..)) {
         lib_so::re_back(cid, 0);
     }
-
 }
 
-// pub fn pop_data(index: usize) -> Option<Vec<u8>> {
-//     let mut socket_table = SOCKET_TABLE.lock();
-
-//     assert!(socket_table.len() > index);
-//     assert!(socket_table[index].is_some());
+/// Forget kernel coroutine `cid` wherever it waits, it has been cancelled.
+pub fn forget_waiter(cid: usize) {
+    let socket_table = SOCKET_TABLE.lock();
+    for socket in socket_table.iter().flatten() {
+        let mut socket = socket.lock();
+        socket.read_waiters.retain(|&waiter| waiter != cid);
+        socket.write_waiters.retain(|&waiter| waiter != cid);
+    }
+}
 
-//     socket_table[index].as_mut().unwrap().buffers.pop_front()
-// }
\ No newline at end of file
+/// Wake up everyone waiting on a socket, they will check the state of the socket by themselves.
+pub fn wake_all() {
+    let socket_table = SOCKET_TABLE.lock();
+    for socket in socket_table.iter().flatten() {
+        wake_socket(&mut socket.lock());
+    }
+}
@@ -1,53 +1,130 @@
 
 use alloc::boxed::Box;
 use alloc::vec;
-use lose_net_stack::packets::tcp::TCPPacket;
-use lose_net_stack::IPv4;
-use lose_net_stack::MacAddress;
-use lose_net_stack::TcpFlags;
-use super::socket::get_mutex_socket;
-use super::socket::{add_socket, get_s_a_by_index, remove_socket};
-use super::LOSE_NET_STACK;
+use alloc::vec::Vec;
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::tcp;
+use smoltcp::time::Duration;
+use smoltcp::wire::IpEndpoint;
+use super::socket::{add_socket, get_mutex_socket, is_socket_open, remove_socket};
+use super::{alloc_ephemeral_port, lock_net_stack, net_poll, NetStack};
 use crate::fs::ReadHelper;
-use crate::net::ASYNC_RDMP;
+use crate::mm::UserBuffer;
+use crate::syscall::Errno;
 use crate::task::block_current_and_run_next;
 use crate::task::current_task;
-use crate::task::suspend_current_and_run_next;
 use crate::trap::UserTrapRecord;
 use crate::trap::push_trap_record;
-use crate::{device::NetDevice, fs::File};
+use crate::fs::File;
+
+const TCP_RX_BUFFER_SIZE: usize = 8192;
+const TCP_TX_BUFFER_SIZE: usize = 8192;
+// the handshake is given up if the remote does not answer in time
+const TCP_CONNECT_TIMEOUT_SECS: u64 = 5;
+
+pub fn new_tcp_socket() -> tcp::Socket<'static> {
+    tcp::Socket::new(
+        tcp::SocketBuffer::new(vec![0; TCP_RX_BUFFER_SIZE]),
+        tcp::SocketBuffer::new(vec![0; TCP_TX_BUFFER_SIZE]),
+    )
+}
 
 pub struct TCP {
-    pub target: IPv4,
-    pub sport: u16,
-    pub dport: u16,
-    pub seq: u32,
-    pub ack: u32,
+    pub handle: SocketHandle,
     pub socket_index: usize,
 }
 
 impl TCP {
-    pub fn new(target: IPv4, sport: u16, dport: u16, seq: u32, ack: u32) -> Option<Self> {
-        match add_socket(target, sport, dport, seq, ack) {
-            Some(index) => {
-                Some(
-                    Self {
-                        target,
-                        sport,
-                        dport,
-                        seq,
-                        ack,
-                        socket_index: index,
-                    }
-                )
-            }
-            _ => {
-                None
+    pub fn new(handle: SocketHandle) -> Self {
+        Self {
+            handle,
+            socket_index: add_socket(handle),
+        }
+    }
+
+    /// Start an active open to `remote` from an ephemeral port, the SYN is sent at once.
+    pub fn connect(remote: IpEndpoint) -> Result<Self, isize> {
+        let mut socket = new_tcp_socket();
+        socket.set_timeout(Some(Duration::from_secs(TCP_CONNECT_TIMEOUT_SECS)));
+        let mut net_stack = lock_net_stack();
+        let NetStack { iface, sockets, .. } = &mut *net_stack;
+        let port = alloc_ephemeral_port(sockets, false).ok_or(isize::from(Errno::EADDRNOTAVAIL))?;
+        socket
+            .connect(iface.context(), remote, port)
+            .map_err(|_| isize::from(Errno::EADDRNOTAVAIL))?;
+        let handle = sockets.add(socket);
+        drop(net_stack);
+        let tcp = Self::new(handle);
+        net_poll();
+        Ok(tcp)
+    }
+
+    /// Block until the handshake started by `connect` is finished.
+    pub fn wait_established(&self) -> Result<(), isize> {
+        let socket = get_mutex_socket(self.socket_index).unwrap();
+        loop {
+            let mut net_stack = lock_net_stack();
+            let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if let Some(res) = handshake_result(tcp_socket) {
+                return res;
             }
+            socket.lock().block(current_task().unwrap());
+            drop(net_stack);
+            block_current_and_run_next();
         }
     }
+
+    /// Wake coroutine `cid` of process `pid` once the handshake is finished or failed.
+    pub fn aconnect(&self, cid: usize, pid: usize) -> core::pin::Pin<Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        Box::pin(async_connect(self.socket_index, self.handle, cid, pid))
+    }
+}
+
+fn copy_from_user(buf: &UserBuffer) -> Vec<u8> {
+    let mut data = vec![0u8; buf.len()];
+    let mut left = 0;
+    for i in 0..buf.buffers.len() {
+        data[left..(left + buf.buffers[i].len())].copy_from_slice(buf.buffers[i]);
+        left += buf.buffers[i].len();
+    }
+    data
 }
 
+/// Return None while the handshake is in progress.
+fn handshake_result(socket: &mut tcp::Socket) -> Option<Result<(), isize>> {
+    match socket.state() {
+        tcp::State::SynSent | tcp::State::SynReceived => None,
+        // reset by the remote or timeout
+        tcp::State::Closed => Some(Err(Errno::ECONNREFUSED.into())),
+        _ => {
+            socket.set_timeout(None);
+            Some(Ok(()))
+        }
+    }
+}
+
+/// Return None if there is nothing to read yet, Some(0) if the remote has closed the connection.
+fn recv_to_user(socket: &mut tcp::Socket, buf: &mut UserBuffer) -> Option<usize> {
+    if socket.can_recv() {
+        let mut len = 0;
+        for buffer in buf.buffers.iter_mut() {
+            match socket.recv_slice(buffer) {
+                Ok(n) => {
+                    len += n;
+                    if n < buffer.len() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        Some(len)
+    } else if !socket.may_recv() {
+        Some(0)
+    } else {
+        None
+    }
+}
 
 impl File for TCP {
     fn readable(&self) -> bool {
@@ -58,123 +135,176 @@ impl File for TCP {
         true
     }
 
-    fn read(&self, mut buf: crate::mm::UserBuffer) -> Result<usize, isize> {
+    fn read(&self, mut buf: UserBuffer) -> Result<usize, isize> {
         let socket = get_mutex_socket(self.socket_index).unwrap();
+        if let Some(errno) = socket.lock().error.take() {
+            return Err(errno);
+        }
         loop {
-            let mut mutex_socket = socket.lock();
-            if let Some(data) = mutex_socket.buffers.pop_front() {
-                drop(mutex_socket);
-                let data_len = data.len();
-                let mut left = 0;
-                for i in 0..buf.buffers.len() {
-                    let buffer_i_len = buf.buffers[i].len().min(data_len - left);
-
-                    buf.buffers[i][..buffer_i_len]
-                        .copy_from_slice(&data[left..(left + buffer_i_len)]);
-
-                    left += buffer_i_len;
-                    if left == data_len {
-                        break;
-                    }
-                }
-                return Ok(left);
-            } else {
-                let current = current_task().unwrap();
-                mutex_socket.block_task = Some(current);
-                drop(mutex_socket);
-                block_current_and_run_next();
+            let mut net_stack = lock_net_stack();
+            let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if let Some(len) = recv_to_user(tcp_socket, &mut buf) {
+                drop(net_stack);
+                // announce the reopened receive window
+                net_poll();
+                return Ok(len);
             }
+            socket.lock().block(current_task().unwrap());
+            drop(net_stack);
+            block_current_and_run_next();
         }
     }
 
-    fn write(&self, buf: crate::mm::UserBuffer) -> Result<usize, isize> {
-        let lose_net_stack = LOSE_NET_STACK.0.lock();
-
-        let mut data = vec![0u8; buf.len()];
-
+    fn write(&self, buf: UserBuffer) -> Result<usize, isize> {
+        let data = copy_from_user(&buf);
+        debug!("socket send len: {}", data.len());
+        let socket = get_mutex_socket(self.socket_index).unwrap();
+        if let Some(errno) = socket.lock().error.take() {
+            return Err(errno);
+        }
         let mut left = 0;
-        for i in 0..buf.buffers.len() {
-            data[left..(left + buf.buffers[i].len())].copy_from_slice(buf.buffers[i]);
-            left += buf.buffers[i].len();
+        loop {
+            let mut net_stack = lock_net_stack();
+            let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if !tcp_socket.may_send() {
+                return if left > 0 { Ok(left) } else { Err(Errno::EPIPE.into()) };
+            }
+            let len = tcp_socket.send_slice(&data[left..]).unwrap_or(0);
+            left += len;
+            socket.lock().sent += len;
+            drop(net_stack);
+            net_poll();
+            if left == data.len() {
+                return Ok(left);
+            }
+            // wait for the remote to ack and free the send buffer
+            let mut net_stack = lock_net_stack();
+            let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(self.handle);
+            if tcp_socket.can_send() || !tcp_socket.may_send() {
+                continue;
+            }
+            socket.lock().block(current_task().unwrap());
+            drop(net_stack);
+            block_current_and_run_next();
         }
-
-        let len = data.len();
-        debug!("socket send len: {}", len);
-
-        // get sock and sequence
-        let (seq, ack) = get_s_a_by_index(self.socket_index).map_or((0, 0), |x| x);
-        debug!("[TCP write] seq: {}, ack: {}", seq, ack);
-        let tcp_packet = TCPPacket {
-            source_ip: lose_net_stack.ip,
-            source_mac: lose_net_stack.mac,
-            source_port: self.sport,
-            dest_ip: self.target,
-            dest_mac: MacAddress::new([0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
-            dest_port: self.dport,
-            data_len: len,
-            seq,
-            ack,
-            flags: TcpFlags::A,
-            win: 65535,
-            urg: 0,
-            data: data.as_ref(),
-        };
-        NetDevice.transmit(&tcp_packet.build_data());
-        Ok(len)
     }
 
-    fn awrite(&self, buf: crate::mm::UserBuffer, pid: usize, key: usize) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
-        todo!()
+    /// `key` is the coroutine of process `pid` that will be woken up
+    /// once all the data is acknowledged by the remote.
+    fn awrite(&self, buf: UserBuffer, pid: usize, key: usize) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        let data = copy_from_user(&buf);
+        debug!("socket async send len: {}", data.len());
+        Box::pin(async_write(self.socket_index, self.handle, data, key, pid))
     }
 
-    fn aread(&self, mut buf: crate::mm::UserBuffer, cid: usize, pid: usize, key: usize) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
-        Box::pin(async_read(self.socket_index, buf, cid, pid))
-
+    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, key: usize) -> core::pin::Pin<alloc::boxed::Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        Box::pin(async_read(self.socket_index, self.handle, buf, cid, pid))
     }
 }
 
 impl Drop for TCP {
     fn drop(&mut self) {
-        remove_socket(self.socket_index)
+        let mut net_stack = lock_net_stack();
+        // send FIN, the socket is removed from the set once the teardown is finished
+        net_stack.sockets.get_mut::<tcp::Socket>(self.handle).close();
+        net_stack.closing.push(self.handle);
+        remove_socket(self.socket_index);
+        drop(net_stack);
+        net_poll();
     }
 }
 
 
-async fn async_read(socket_index: usize, mut buf: crate::mm::UserBuffer, cid: usize, pid: usize) {
+async fn async_read(socket_index: usize, handle: SocketHandle, mut buf: UserBuffer, cid: usize, pid: usize) {
     let mut helper = Box::new(ReadHelper::new());
     let socket = get_mutex_socket(socket_index).unwrap();
-    // info!("async read!: {}", socket_index);
     loop {
-        let mut mutex_socket = socket.lock();
-        // info!("async get lock!: {}", socket_index);
-        if let Some(data) = mutex_socket.buffers.pop_front() {
-            drop(mutex_socket);
-            let data_len = data.len();
-            let mut left = 0;
-            for i in 0..buf.buffers.len() {
-                let buffer_i_len = buf.buffers[i].len().min(data_len - left);
-
-                buf.buffers[i][..buffer_i_len]
-                    .copy_from_slice(&data[left..(left + buffer_i_len)]);
+        let mut net_stack = lock_net_stack();
+        if !is_socket_open(socket_index, &socket) {
+            break;
+        }
+        let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(handle);
+        if recv_to_user(tcp_socket, &mut buf).is_some() {
+            break;
+        }
+        socket.lock().wait_read(lib_so::current_cid(true));
+        drop(net_stack);
+        helper.as_mut().await;
+    }
+    net_poll();
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
 
-                left += buffer_i_len;
-                if left == data_len {
-                    break;
-                }
-            }
+async fn async_write(socket_index: usize, handle: SocketHandle, data: Vec<u8>, cid: usize, pid: usize) {
+    let mut helper = Box::new(ReadHelper::new());
+    let socket = get_mutex_socket(socket_index).unwrap();
+    let mut left = 0;
+    // `sent` of the socket after the last byte is queued
+    let mut end = 0;
+    loop {
+        let mut net_stack = lock_net_stack();
+        if !is_socket_open(socket_index, &socket) {
+            break;
+        }
+        let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(handle);
+        if !tcp_socket.may_send() {
             break;
-        } else {
-            // info!("suspend current coroutine!: {}", socket_index);
-            ASYNC_RDMP.lock().insert(socket_index, lib_so::current_cid(true));
+        }
+        if left < data.len() {
+            let len = tcp_socket.send_slice(&data[left..]).unwrap_or(0);
+            left += len;
+            let mut mutex_socket = socket.lock();
+            mutex_socket.sent += len;
+            end = mutex_socket.sent;
             drop(mutex_socket);
-            // suspend_current_and_run_next();
-            helper.as_mut().await;
+            drop(net_stack);
+            net_poll();
+            net_stack = lock_net_stack();
+            if !is_socket_open(socket_index, &socket) {
+                break;
+            }
+        }
+        let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(handle);
+        let mut mutex_socket = socket.lock();
+        // the bytes still in the send buffer are not acknowledged yet
+        let acked = mutex_socket.sent - tcp_socket.send_queue();
+        if left == data.len() && acked >= end {
+            break;
         }
+        mutex_socket.wait_write(lib_so::current_cid(true));
+        drop(mutex_socket);
+        drop(net_stack);
+        helper.as_mut().await;
     }
-    // info!("wake: {}", cid);
-    
     let _ = push_trap_record(pid, UserTrapRecord {
         cause: 1,
         message: cid,
     });
-}
\ No newline at end of file
+}
+
+async fn async_connect(socket_index: usize, handle: SocketHandle, cid: usize, pid: usize) {
+    let mut helper = Box::new(ReadHelper::new());
+    let socket = get_mutex_socket(socket_index).unwrap();
+    loop {
+        let mut net_stack = lock_net_stack();
+        if !is_socket_open(socket_index, &socket) {
+            break;
+        }
+        let tcp_socket = net_stack.sockets.get_mut::<tcp::Socket>(handle);
+        if let Some(res) = handshake_result(tcp_socket) {
+            // there is nobody to return the error to, keep it for the next read/write
+            socket.lock().error = res.err();
+            break;
+        }
+        socket.lock().wait_write(lib_so::current_cid(true));
+        drop(net_stack);
+        helper.as_mut().await;
+    }
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
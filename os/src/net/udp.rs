@@ -0,0 +1,200 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use smoltcp::iface::SocketHandle;
+use smoltcp::socket::udp;
+use smoltcp::wire::{IpAddress, IpEndpoint};
+use super::socket::{add_socket, get_mutex_socket, is_socket_open, remove_socket};
+use super::{alloc_ephemeral_port, lock_net_stack, net_poll, port_in_use};
+use crate::fs::{File, ReadHelper};
+use crate::mm::UserBuffer;
+use crate::syscall::{Errno, SockAddr};
+use crate::task::{block_current_and_run_next, current_task};
+use crate::trap::{push_trap_record, UserTrapRecord};
+
+const UDP_PACKET_NUM: usize = 16;
+pub const UDP_MAX_PAYLOAD: usize = 1472;
+
+pub struct UDP {
+    pub handle: SocketHandle,
+    pub socket_index: usize,
+}
+
+impl UDP {
+    pub fn new() -> Self {
+        let socket = udp::Socket::new(
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKET_NUM],
+                vec![0; UDP_PACKET_NUM * UDP_MAX_PAYLOAD],
+            ),
+            udp::PacketBuffer::new(
+                vec![udp::PacketMetadata::EMPTY; UDP_PACKET_NUM],
+                vec![0; UDP_PACKET_NUM * UDP_MAX_PAYLOAD],
+            ),
+        );
+        let handle = lock_net_stack().sockets.add(socket);
+        Self {
+            handle,
+            socket_index: add_socket(handle),
+        }
+    }
+
+    /// Bind to `port`, an ephemeral port is chosen if `port` is 0.
+    pub fn bind(&self, port: u16) -> Result<(), isize> {
+        let mut net_stack = lock_net_stack();
+        if net_stack.sockets.get::<udp::Socket>(self.handle).is_open() {
+            return Err(Errno::EINVAL.into());
+        }
+        let port = if port == 0 {
+            alloc_ephemeral_port(&net_stack.sockets, true).ok_or(isize::from(Errno::EADDRINUSE))?
+        } else if port_in_use(&net_stack.sockets, port, true) {
+            return Err(Errno::EADDRINUSE.into());
+        } else {
+            port
+        };
+        net_stack
+            .sockets
+            .get_mut::<udp::Socket>(self.handle)
+            .bind(port)
+            .map_err(|_| Errno::EINVAL.into())
+    }
+
+    pub fn sendto(&self, buf: UserBuffer, addr: &SockAddr) -> Result<usize, isize> {
+        let len = buf.len();
+        if len > UDP_MAX_PAYLOAD {
+            return Err(Errno::EMSGSIZE.into());
+        }
+        let mut data = vec![0u8; len];
+        let mut left = 0;
+        for buffer in buf.buffers.iter() {
+            data[left..(left + buffer.len())].copy_from_slice(buffer);
+            left += buffer.len();
+        }
+        let [a, b, c, d] = addr.ip;
+        let remote = IpEndpoint::new(IpAddress::v4(a, b, c, d), addr.port);
+        if !lock_net_stack().sockets.get::<udp::Socket>(self.handle).is_open() {
+            self.bind(0)?;
+        }
+        let socket = get_mutex_socket(self.socket_index).unwrap();
+        loop {
+            let mut net_stack = lock_net_stack();
+            let udp_socket = net_stack.sockets.get_mut::<udp::Socket>(self.handle);
+            if udp_socket.can_send() {
+                udp_socket
+                    .send_slice(&data, remote)
+                    .map_err(|_| isize::from(Errno::EINVAL))?;
+                drop(net_stack);
+                net_poll();
+                return Ok(len);
+            }
+            socket.lock().block(current_task().unwrap());
+            drop(net_stack);
+            net_poll();
+            block_current_and_run_next();
+        }
+    }
+
+    /// Receive a datagram, the part that does not fit in `buf` is discarded.
+    pub fn recvfrom(&self, mut buf: UserBuffer, mut addr: Option<UserBuffer>) -> Result<usize, isize> {
+        let socket = get_mutex_socket(self.socket_index).unwrap();
+        loop {
+            let mut net_stack = lock_net_stack();
+            let udp_socket = net_stack.sockets.get_mut::<udp::Socket>(self.handle);
+            if !udp_socket.is_open() {
+                return Err(Errno::ENOTCONN.into());
+            }
+            if let Some(len) = recv_to_user(udp_socket, &mut buf, &mut addr) {
+                return Ok(len);
+            }
+            socket.lock().block(current_task().unwrap());
+            drop(net_stack);
+            block_current_and_run_next();
+        }
+    }
+
+    pub fn arecvfrom(&self, buf: UserBuffer, addr: Option<UserBuffer>, cid: usize, pid: usize) -> core::pin::Pin<Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        Box::pin(async_recvfrom(self.socket_index, self.handle, buf, addr, cid, pid))
+    }
+}
+
+fn recv_to_user(socket: &mut udp::Socket, buf: &mut UserBuffer, addr: &mut Option<UserBuffer>) -> Option<usize> {
+    let (payload, endpoint) = socket.recv().ok()?;
+    let mut len = 0;
+    for buffer in buf.buffers.iter_mut() {
+        let n = buffer.len().min(payload.len() - len);
+        buffer[..n].copy_from_slice(&payload[len..(len + n)]);
+        len += n;
+        if len == payload.len() {
+            break;
+        }
+    }
+    if let Some(addr) = addr {
+        #[allow(unreachable_patterns)]
+        let ip = match endpoint.addr {
+            IpAddress::Ipv4(ip) => ip.0,
+            _ => [0; 4],
+        };
+        addr.write_value(&SockAddr::new(ip, endpoint.port));
+    }
+    Some(len)
+}
+
+impl File for UDP {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    fn read(&self, buf: UserBuffer) -> Result<usize, isize> {
+        self.recvfrom(buf, None)
+    }
+
+    fn write(&self, _buf: UserBuffer) -> Result<usize, isize> {
+        // no default remote address, use sendto instead
+        Err(Errno::ENOTCONN.into())
+    }
+
+    fn awrite(&self, _buf: UserBuffer, _pid: usize, _key: usize) -> core::pin::Pin<Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        Box::pin(async {})
+    }
+
+    fn aread(&self, buf: UserBuffer, cid: usize, pid: usize, _key: usize) -> core::pin::Pin<Box<dyn core::future::Future<Output = ()> + 'static + Send + Sync>> {
+        self.arecvfrom(buf, None, cid, pid)
+    }
+
+    fn as_udp(&self) -> Option<&UDP> {
+        Some(self)
+    }
+}
+
+impl Drop for UDP {
+    fn drop(&mut self) {
+        let mut net_stack = lock_net_stack();
+        net_stack.sockets.remove(self.handle);
+        remove_socket(self.socket_index);
+    }
+}
+
+async fn async_recvfrom(socket_index: usize, handle: SocketHandle, mut buf: UserBuffer, mut addr: Option<UserBuffer>, cid: usize, pid: usize) {
+    let mut helper = Box::new(ReadHelper::new());
+    let socket = get_mutex_socket(socket_index).unwrap();
+    loop {
+        let mut net_stack = lock_net_stack();
+        if !is_socket_open(socket_index, &socket) {
+            break;
+        }
+        let udp_socket = net_stack.sockets.get_mut::<udp::Socket>(handle);
+        if !udp_socket.is_open() || recv_to_user(udp_socket, &mut buf, &mut addr).is_some() {
+            break;
+        }
+        socket.lock().wait_read(lib_so::current_cid(true));
+        drop(net_stack);
+        helper.as_mut().await;
+    }
+    let _ = push_trap_record(pid, UserTrapRecord {
+        cause: 1,
+        message: cid,
+    });
+}
@@ -40,6 +40,12 @@ pub fn shutdown() -> ! {
     panic!("It should shutdown!");
 }
 
+/// Flush the TLB entries of `[start, start + size)` on the harts in `hart_mask`,
+/// the whole TLB if `size` is `usize::MAX`.
+pub fn remote_sfence_vma(hart_mask: usize, start: usize, size: usize) {
+    sbi_call(SBI_REMOTE_SFENCE_VMA, &hart_mask as *const _ as usize, start, size);
+}
+
 pub fn send_ipi(ptr: usize) {
     push_trace(SEND_IPI_ENTER);
     sbi_call(SBI_SEND_IPI, ptr, 0, 0);
@@ -1,5 +1,7 @@
 mod mutex;
 mod condvar;
+mod semaphore;
 
 pub use mutex::{SimpleMutex, MutexSpin, MutexBlocking};
-pub use condvar::Condvar;
\ No newline at end of file
+pub use condvar::Condvar;
+pub use semaphore::Semaphore;
\ No newline at end of file
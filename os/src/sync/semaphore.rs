@@ -0,0 +1,45 @@
+use spin::Mutex;
+use alloc::{collections::VecDeque, sync::Arc};
+
+use crate::task::{TaskControlBlock, add_task, current_task, block_current_and_run_next};
+
+pub struct Semaphore {
+    pub inner: Mutex<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    // negative count is the number of waiting tasks
+    pub count: isize,
+    pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(res_count: usize) -> Self {
+        Self {
+            inner: Mutex::new(SemaphoreInner {
+                count: res_count as isize,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn up(&self) {
+        let mut inner = self.inner.lock();
+        inner.count += 1;
+        if inner.count <= 0 {
+            if let Some(task) = inner.wait_queue.pop_front() {
+                add_task(task);
+            }
+        }
+    }
+
+    pub fn down(&self) {
+        let mut inner = self.inner.lock();
+        inner.count -= 1;
+        if inner.count < 0 {
+            inner.wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            block_current_and_run_next();
+        }
+    }
+}
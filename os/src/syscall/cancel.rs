@@ -0,0 +1,65 @@
+use crate::net::forget_waiter;
+use crate::syscall::WRMAP;
+use crate::task::current_process;
+use crate::timer::cancel_timers;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::future::Future;
+use lazy_static::*;
+use spin::Mutex;
+
+// (pid, cid of the user coroutine) -> cids of the kernel coroutines doing its asynchronous works,
+// a coroutine may have several of them in flight
+lazy_static! {
+    static ref ASYNC_WORKS: Mutex<BTreeMap<(usize, usize), Vec<usize>>> = Mutex::new(BTreeMap::new());
+}
+
+/// Spawn the kernel coroutine doing the asynchronous work for coroutine `cid` of process `pid`,
+/// so that it can be found by `sys_cancel_async`.
+pub fn spawn_async_work<F>(pid: usize, cid: usize, work: F)
+where
+    F: Future<Output = ()> + 'static + Send + Sync,
+{
+    // hold the map, the work can't finish and unregister before it is registered
+    let mut works = ASYNC_WORKS.lock();
+    let handle = lib_so::spawn(
+        move || async move {
+            work.await;
+            let mut works = ASYNC_WORKS.lock();
+            let kernel_cid = lib_so::current_cid(true);
+            if let Some(kernel_cids) = works.get_mut(&(pid, cid)) {
+                kernel_cids.retain(|&other| other != kernel_cid);
+                if kernel_cids.is_empty() {
+                    works.remove(&(pid, cid));
+                }
+            }
+        },
+        0,
+        0,
+        lib_so::CoroutineKind::KernSyscall,
+    );
+    works.entry((pid, cid)).or_default().push(handle.cid());
+}
+
+/// Forget everything the kernel keeps for coroutine `cid` of current process: the kernel
+/// coroutines doing its asynchronous works and the wakeups registered for them, its timers
+/// and its asynchronous waitpid. The coroutine won't be woken by any of them afterwards.
+pub fn sys_cancel_async(cid: usize) -> isize {
+    let process = current_process().unwrap();
+    let pid = process.getpid();
+    process.acquire_inner_lock().async_waiters.retain(|&(_, waiter)| waiter != cid);
+    drop(process);
+    cancel_timers(pid, cid);
+    let kernel_cids = match ASYNC_WORKS.lock().remove(&(pid, cid)) {
+        Some(kernel_cids) => kernel_cids,
+        None => return 0,
+    };
+    // a running work is dropped when it returns Pending, it may register once more before that,
+    // the stale entry only leads to a wakeup that is ignored
+    for &kernel_cid in kernel_cids.iter() {
+        lib_so::cancel(kernel_cid, 0);
+        forget_waiter(kernel_cid);
+    }
+    WRMAP.lock().retain(|_, waiter| !kernel_cids.contains(waiter));
+    0
+}
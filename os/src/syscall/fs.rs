@@ -1,9 +1,11 @@
 use core::cmp::min;
 
-use crate::fs::{make_pipe, File};
-use crate::task::{current_process, current_task, current_user_token};
+use crate::fs::{make_pipe, open_path, unlink_path, File, MAIL_BUFFER_SIZE};
+use crate::syscall::{Errno, OpenFlags};
+use super::cancel::spawn_async_work;
+use crate::task::{current_process, current_task, current_user_token, pid2process};
 use crate::{
-    mm::{translated_byte_buffer, translated_refmut, UserBuffer},
+    mm::{translated_byte_buffer, translated_str, write_user, UserBuffer},
     // task::find_task,
 };
 use lazy_static::*;
@@ -13,6 +15,9 @@ use alloc::{
 };
 use spin::Mutex;
 
+// upper bound of the fds given to dup2
+const MAX_FD: usize = 1024;
+
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq)]
 pub struct AsyncKey {
     pub pid: usize,
@@ -34,29 +39,36 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize, key: usize, pid: usize)
     let process = current_process().unwrap();
     let inner = process.acquire_inner_lock();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
         // release Task lock manually to avoid deadlock
         drop(inner);
+        if !file.writable() {
+            return Errno::EBADF.into();
+        }
         if key == usize::MAX {
-            if let Ok(buffers) = translated_byte_buffer(token, buf, len) {
+            if let Ok(buffers) = translated_byte_buffer(token, buf, len, false) {
                 match file.write(UserBuffer::new(buffers)) {
                     Ok(write_len) => write_len as isize,
-                    Err(_) => -2,
+                    Err(_) => Errno::EIO.into(),
                 }
             } else {
-                -3
+                Errno::EFAULT.into()
             }
         } else {
-            
-            let work = file.awrite(UserBuffer::new(translated_byte_buffer(token, buf, len).unwrap()), pid, key);
-            lib_so::spawn(move || work, 0, 0, lib_so::CoroutineKind::KernSyscall);
+            let buffers = match translated_byte_buffer(token, buf, len, false) {
+                Ok(buffers) => buffers,
+                Err(_) => return Errno::EFAULT.into(),
+            };
+            let work = file.awrite(UserBuffer::new(buffers), pid, key);
+            // an asynchronous send wakes coroutine `key` when it is done, which may cancel it
+            spawn_async_work(process.getpid(), key, work);
             0
         }
     } else {
-        -4
+        Errno::EBADF.into()
     }
 }
 
@@ -70,41 +82,120 @@ pub fn sys_read(fd: usize, buf: *const u8, len: usize, key: usize, cid: usize) -
     let inner = process.acquire_inner_lock();
     // info!("test1: {}", fd);
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
         // release Task lock manually to avoid deadlock
         drop(inner);
+        if !file.readable() {
+            return Errno::EBADF.into();
+        }
         if key == usize::MAX && cid == usize::MAX {
-            if let Ok(buffers) = translated_byte_buffer(token, buf, len) {
+            if let Ok(buffers) = translated_byte_buffer(token, buf, len, true) {
                 match file.read(UserBuffer::new(buffers)) {
                     Ok(read_len) => read_len as isize,
-                    Err(_) => -2,
+                    Err(_) => Errno::EIO.into(),
                 }
             } else {
-                -3
+                Errno::EFAULT.into()
             }
         } else {
             // info!("test2: {}", fd);
-            let work = file.aread(UserBuffer::new(translated_byte_buffer(token, buf, len).unwrap()), cid, pid, key);
-            lib_so::spawn(move || work, 0, 0, lib_so::CoroutineKind::KernSyscall);
+            let buffers = match translated_byte_buffer(token, buf, len, true) {
+                Ok(buffers) => buffers,
+                Err(_) => return Errno::EFAULT.into(),
+            };
+            let work = file.aread(UserBuffer::new(buffers), cid, pid, key);
+            spawn_async_work(pid, cid, work);
             // info!("test3: {}", fd);
             0
         }
     } else {
-        -4
+        Errno::EBADF.into()
+    }
+}
+
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return Errno::EINVAL.into(),
+    };
+    match open_path(path.as_str(), flags) {
+        Ok(file) => {
+            let process = current_process().unwrap();
+            let mut inner = process.acquire_inner_lock();
+            let fd = inner.alloc_fd();
+            inner.fd_table[fd] = Some(file);
+            fd as isize
+        }
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let process = current_process().unwrap();
+    let inner = process.acquire_inner_lock();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Errno::EBADF.into(),
+    };
+    drop(inner);
+    match file.lseek(offset, whence) {
+        Ok(offset) => offset as isize,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_unlink(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = translated_str(token, path);
+    match unlink_path(path.as_str()) {
+        Ok(()) => 0,
+        Err(errno) => errno,
     }
 }
 
+pub fn sys_dup(fd: usize) -> isize {
+    let process = current_process().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Errno::EBADF.into(),
+    };
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+/// Make `new_fd` refer to the file of `old_fd`, the file `new_fd` referred to is closed.
+pub fn sys_dup2(old_fd: usize, new_fd: usize) -> isize {
+    let process = current_process().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    let file = match inner.fd_table.get(old_fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Errno::EBADF.into(),
+    };
+    if new_fd >= MAX_FD {
+        return Errno::EBADF.into();
+    }
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
 pub fn sys_close(fd: usize) -> isize {
     let task = current_process().unwrap();
     let mut inner = task.acquire_inner_lock();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if inner.fd_table[fd].is_none() {
-        return -1;
+        return Errno::EBADF.into();
     }
     inner.fd_table[fd].take();
     0
@@ -119,60 +210,73 @@ pub fn sys_pipe(pipe: *mut usize) -> isize {
     inner.fd_table[read_fd] = Some(pipe_read);
     let write_fd = inner.alloc_fd();
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    // the store may copy a copy-on-write page, which takes the process lock
+    drop(inner);
+    if write_user(token, pipe as *mut [usize; 2], &[read_fd, write_fd]).is_none() {
+        let mut inner = task.acquire_inner_lock();
+        inner.fd_table[read_fd].take();
+        inner.fd_table[write_fd].take();
+        return Errno::EFAULT.into();
+    }
     0
 }
 
+/// Send a mail of at most `MAIL_BUFFER_SIZE` bytes to process `pid`,
+/// blocking while the receiver's mailbox is full.
 pub fn sys_mailwrite(pid: usize, buf: *mut u8, len: usize) -> isize {
-    // let token = current_user_token();
-    // if let Some(receive_task) = find_task(pid) {
-    //     debug!("find task");
-    //     if receive_task.acquire_inner_lock().is_mailbox_full() {
-    //         return -1;
-    //     } else if len == 0 {
-    //         return 0;
-    //     }
-    //
-    //     if let Ok(buffers) = translated_byte_buffer(token, buf, min(len, 256)) {
-    //         let socket = receive_task.create_socket();
-    //         match socket.write(UserBuffer::new(buffers)) {
-    //             Ok(write_len) => write_len as isize,
-    //             Err(_) => -1,
-    //         }
-    //     } else {
-    //         -1
-    //     }
-    // } else {
-    //     debug!("not find task");
-    //     -1
-    // }
-    -1
+    let token = current_user_token();
+    let receiver = match pid2process(pid) {
+        Some(receiver) => receiver,
+        None => return Errno::ESRCH.into(),
+    };
+    let mail_box = receiver.acquire_inner_lock().mail_box.clone();
+    drop(receiver);
+    if len == 0 {
+        return 0;
+    }
+    let buffers = match translated_byte_buffer(token, buf, min(len, MAIL_BUFFER_SIZE), false) {
+        Ok(buffers) => buffers,
+        Err(_) => return Errno::EFAULT.into(),
+    };
+    // the receiver may exit while we are waiting
+    let socket = match mail_box.create_socket_blocking() {
+        Some(socket) => socket,
+        None => return Errno::ESRCH.into(),
+    };
+    let ret = match socket.write(UserBuffer::new(buffers)) {
+        Ok(write_len) => write_len as isize,
+        Err(_) => Errno::EIO.into(),
+    };
+    // the mail is complete once the write end is closed
+    drop(socket);
+    mail_box.wake_reader();
+    ret
 }
 
-pub fn sys_mailread(buf: *mut u8, len: usize) -> isize {
-    // let token = current_user_token();
-    // let task = current_task().unwrap();
-    // debug!(
-    //     "mail box empty ? {}",
-    //     task.acquire_inner_lock().is_mailbox_empty()
-    // );
-    // if task.acquire_inner_lock().is_mailbox_empty() {
-    //     return -1;
-    // } else if len == 0 {
-    //     return 0;
-    // }
-    // let mail_box = task.acquire_inner_lock().mail_box.clone();
-    // if let Ok(buffers) = translated_byte_buffer(token, buf, min(len, 256)) {
-    //     match mail_box.read(UserBuffer::new(buffers)) {
-    //         Ok(read_len) => {
-    //             debug!("mail read {} len", read_len);
-    //             read_len as isize
-    //         }
-    //         Err(_) => -1,
-    //     }
-    // } else {
-    //     -1
-    // }
-    -1
+/// Receive a mail from the mailbox of current process, bytes beyond `len` are discarded.
+/// The synchronous form blocks until a mail arrives, the asynchronous form notifies
+/// coroutine `cid` through the user trap queue.
+pub fn sys_mailread(buf: *mut u8, len: usize, key: usize, cid: usize) -> isize {
+    let token = current_user_token();
+    let process = current_process().unwrap();
+    let pid = process.getpid();
+    let mail_box = process.acquire_inner_lock().mail_box.clone();
+    drop(process);
+    let buffers = match translated_byte_buffer(token, buf, min(len, MAIL_BUFFER_SIZE), true) {
+        Ok(buffers) => buffers,
+        Err(_) => return Errno::EFAULT.into(),
+    };
+    if key == usize::MAX && cid == usize::MAX {
+        if len == 0 {
+            return 0;
+        }
+        match mail_box.read(UserBuffer::new(buffers)) {
+            Ok(read_len) => read_len as isize,
+            Err(_) => Errno::EIO.into(),
+        }
+    } else {
+        let work = mail_box.aread(UserBuffer::new(buffers), cid, pid, key);
+        spawn_async_work(pid, cid, work);
+        0
+    }
 }
@@ -1,9 +1,18 @@
+const SYSCALL_DUP2: usize = 23;
+const SYSCALL_DUP: usize = 24;
+const SYSCALL_UNLINK: usize = 35;
+const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_PIPE: usize = 59;
+const SYSCALL_LSEEK: usize = 62;
 const SYSCALL_READ: usize = 63;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_YIELD: usize = 124;
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
 const SYSCALL_SET_PRIORITY: usize = 140;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
@@ -11,6 +20,7 @@ const SYSCALL_MUNMAP: usize = 215;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MPROTECT: usize = 226;
 const SYSCALL_WAITPID: usize = 260;
 const SYSCALL_SPAWN: usize = 400;
 const SYSCALL_MAILREAD: usize = 401;
@@ -21,10 +31,13 @@ const SYSCALL_SEND_MSG: usize = 601;
 const SYSCALL_SET_TIMER: usize = 602;
 const SYSCALL_CLAIM_EXT_INT: usize = 603;
 const SYSCALL_SET_EXT_INT_ENABLE: usize = 604;
+const SYSCALL_CANCEL_ASYNC: usize = 605;
+const SYSCALL_CANCEL_TIMER: usize = 606;
 const SYSCALL_THREAD_CREATE: usize = 1000;
 const SYSCALL_GETTID: usize = 1001;
 const SYSCALL_WAITTID: usize = 1002;
 const SYSCALL_HANG: usize = 1003;
+const SYSCALL_THREAD_DETACH: usize = 1004;
 
 const SYSCALL_MUTEX_CREATE: usize = 1010;
 const SYSCALL_MUTEX_LOCK: usize = 1011;
@@ -38,41 +51,63 @@ const SYSCALL_CONDVAR_WAIT: usize = 1032;
 
 const SYSCALL_LISTEN: usize = 1200;
 const SYSCALL_ACCEPT: usize = 1201;
+const SYSCALL_SOCKET: usize = 1202;
+const SYSCALL_BIND: usize = 1203;
+const SYSCALL_SENDTO: usize = 1204;
+const SYSCALL_RECVFROM: usize = 1205;
+const SYSCALL_CONNECT: usize = 1206;
+const SYSCALL_GET_IFCONFIG: usize = 1207;
+const SYSCALL_SET_IFCONFIG: usize = 1208;
 
 mod fs;
 mod process;
 mod thread;
 mod sync;
 mod net;
+mod signal;
+mod cancel;
 
 use crate::trace::{push_trace, TRACE_SYSCALL_ENTER, TRACE_SYSCALL_EXIT};
 use fs::*;
 use process::*;
 use sync::*;
-pub use crate::syscall::thread::{sys_gettid, sys_thread_create, sys_waittid, sys_hang};
+use signal::*;
+use cancel::sys_cancel_async;
+pub use crate::syscall::thread::{sys_gettid, sys_thread_create, sys_thread_detach, sys_waittid, sys_hang};
 pub use fs::{WRMAP, AsyncKey};
-use net::{sys_accept, sys_listen};
+pub use ::syscall::{Errno, IfConfig, OpenFlags, SignalAction, SignalFlags, SockAddr, MAP_FIXED, MAP_POPULATE, MAX_SIG, SIGKILL, SIGSEGV, SIG_DFL, SIG_IGN, SEEK_CUR, SEEK_END, SEEK_SET, SOCK_DGRAM, SOCK_STREAM};
+use net::{sys_accept, sys_bind, sys_connect, sys_get_ifconfig, sys_listen, sys_recvfrom, sys_sendto, sys_set_ifconfig, sys_socket};
 
 pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
     trace!("syscall {}, args {:x?}", syscall_id, args);
     push_trace(TRACE_SYSCALL_ENTER + syscall_id);
     let ret = match syscall_id {
+        SYSCALL_DUP2 => sys_dup2(args[0], args[1]),
+        SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_UNLINK => sys_unlink(args[0] as *const u8),
+        SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
         SYSCALL_PIPE => sys_pipe(args[0] as *mut usize),
+        SYSCALL_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
         SYSCALL_READ => sys_read(args[0], args[1] as *const u8, args[2], args[3], args[4]),
         SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2], args[3], args[4]),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
         SYSCALL_YIELD => sys_yield(),
+        SYSCALL_KILL => sys_kill(args[0], args[1]),
+        SYSCALL_SIGACTION => sys_sigaction(args[0], args[1] as *const SignalAction, args[2] as *mut SignalAction),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0]),
+        SYSCALL_SIGRETURN => sys_sigreturn(),
         SYSCALL_GET_TIME => sys_get_time(args[0], args[1]),
         SYSCALL_SET_PRIORITY => sys_set_priority(args[0] as isize),
-        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2]),
+        SYSCALL_MMAP => sys_mmap(args[0], args[1], args[2], args[3]),
         SYSCALL_MUNMAP => sys_munmap(args[0], args[1]),
+        SYSCALL_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
         SYSCALL_GETPID => sys_getpid(),
         SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
+        SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize, args[2] as *const usize),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2], args[3]),
         SYSCALL_SPAWN => sys_spawn(args[0] as *const u8),
-        SYSCALL_MAILREAD => sys_mailread(args[0] as *mut u8, args[1]),
+        SYSCALL_MAILREAD => sys_mailread(args[0] as *mut u8, args[1], args[2], args[3]),
         SYSCALL_MAILWRITE => sys_mailwrite(args[0], args[1] as *mut u8, args[2]),
         SYSCALL_FLUSH_TRACE => sys_flush_trace(),
         SYSCALL_INIT_USER_TRAP => sys_init_user_trap(args[0]),
@@ -80,19 +115,35 @@ pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
         SYSCALL_SET_TIMER => sys_set_timer(args[0], args[1]),
         SYSCALL_CLAIM_EXT_INT => sys_claim_ext_int(args[0]),
         SYSCALL_SET_EXT_INT_ENABLE => sys_set_ext_int_enable(args[0], args[1]),
+        SYSCALL_CANCEL_ASYNC => sys_cancel_async(args[0]),
+        SYSCALL_CANCEL_TIMER => sys_cancel_timer(args[0]),
         SYSCALL_THREAD_CREATE => sys_thread_create(args[0], args[1]),
         SYSCALL_GETTID => sys_gettid(),
-        SYSCALL_WAITTID => sys_waittid(args[0]) as isize,
+        SYSCALL_WAITTID => sys_waittid(args[0], args[1] as *mut i32),
         SYSCALL_HANG => sys_hang(),
+        SYSCALL_THREAD_DETACH => sys_thread_detach(args[0]),
         SYSCALL_MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         SYSCALL_MUTEX_LOCK => sys_mutex_lock(args[0]),
         SYSCALL_MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
+        SYSCALL_SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
+        SYSCALL_SEMAPHORE_UP => sys_semaphore_up(args[0]),
+        SYSCALL_SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
         SYSCALL_CONDVAR_CREATE => sys_condvar_create(args[0]),
         SYSCALL_CONDVAR_SIGNAL => sys_condvar_signal(args[0]),
         SYSCALL_CONDVAR_WAIT => sys_condvar_wait(args[0], args[1]),
         SYSCALL_LISTEN => sys_listen(args[0] as u16),
         SYSCALL_ACCEPT => sys_accept(args[0]),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_SOCKET => sys_socket(args[0]),
+        SYSCALL_BIND => sys_bind(args[0], args[1]),
+        SYSCALL_SENDTO => sys_sendto(args[0], args[1] as *const u8, args[2], args[3] as *const SockAddr),
+        SYSCALL_RECVFROM => sys_recvfrom(args[0], args[1] as *const u8, args[2], args[3] as *mut SockAddr, args[4], args[5]),
+        SYSCALL_CONNECT => sys_connect(args[0], args[1], args[2], args[3]),
+        SYSCALL_GET_IFCONFIG => sys_get_ifconfig(args[0] as *mut IfConfig),
+        SYSCALL_SET_IFCONFIG => sys_set_ifconfig(args[0] as *const IfConfig),
+        _ => {
+            warn!("Unsupported syscall_id: {}", syscall_id);
+            Errno::ENOSYS.into()
+        }
     };
     push_trace(TRACE_SYSCALL_EXIT + syscall_id);
     ret
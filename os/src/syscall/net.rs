@@ -1,9 +1,18 @@
-use crate::task::{current_process, current_task, current_trap_cx, block_current_and_run_next, suspend_current_and_run_next};
+use crate::task::{current_process, current_task, block_current_and_run_next};
 use alloc::sync::Arc;
-use crate::net::{accept, listen, port_acceptable, PortFd};
+use crate::fs::File;
+use crate::mm::{read_user, translated_byte_buffer, write_user, UserBuffer};
+use crate::net::{accept, listen, net_available, prefix_len, set_net_config, PortFd, NET_CONFIG, TCP, UDP};
+use smoltcp::wire::{IpAddress, IpEndpoint};
+use crate::syscall::{Errno, IfConfig, SockAddr, SOCK_DGRAM};
+use crate::task::current_user_token;
+use super::cancel::spawn_async_work;
 
 // listen a port
 pub fn sys_listen(port: u16) -> isize {
+    if !net_available() {
+        return Errno::ENETDOWN.into();
+    }
     match listen(port) {
         Some(port_index) => {
             let process = current_process().unwrap();
@@ -15,28 +24,186 @@ pub fn sys_listen(port: u16) -> isize {
             // NOTICE: this return the port index, not the fd
             port_index as isize
         }
-        None => -1,
+        None => Errno::EADDRINUSE.into(),
     }
 }
 
 // accept a tcp connection
 pub fn sys_accept(port_index: usize) -> isize {
     debug!("accepting port {}", port_index);
+    loop {
+        match accept(port_index, current_task().unwrap()) {
+            Ok(Some(tcp_socket)) => {
+                let process = current_process().unwrap();
+                let mut inner = process.acquire_inner_lock();
+                let fd = inner.alloc_fd();
+                inner.fd_table[fd] = Some(Arc::new(tcp_socket));
+                debug!("[accept] local fd: {}", fd);
+                return fd as isize;
+            }
+            Ok(None) => block_current_and_run_next(),
+            Err(errno) => return errno,
+        }
+    }
+}
 
-    let task = current_task().unwrap();
-    accept(port_index, task);
-    // suspend_current_and_run_next();
-    block_current_and_run_next();
+// open a tcp connection to `ip:port`, `ip` is a host-order integer whose most significant byte
+// is the first octet of the address, as given by `u32::from_be_bytes`
+pub fn sys_connect(ip: usize, port: usize, key: usize, cid: usize) -> isize {
+    if !net_available() {
+        return Errno::ENETDOWN.into();
+    }
+    if port == 0 || port > u16::MAX as usize {
+        return Errno::EINVAL.into();
+    }
+    let [a, b, c, d] = (ip as u32).to_be_bytes();
+    let remote = IpEndpoint::new(IpAddress::v4(a, b, c, d), port as u16);
+    let tcp_socket = match TCP::connect(remote) {
+        Ok(tcp_socket) => tcp_socket,
+        Err(errno) => return errno,
+    };
+    let process = current_process().unwrap();
+    if key == usize::MAX && cid == usize::MAX {
+        if let Err(errno) = tcp_socket.wait_established() {
+            return errno;
+        }
+    } else {
+        // the fd is returned at once, the coroutine is woken up when the handshake is finished,
+        // a failed handshake is reported by the next read/write on the fd
+        let work = tcp_socket.aconnect(cid, process.getpid());
+        spawn_async_work(process.getpid(), cid, work);
+    }
+    let mut inner = process.acquire_inner_lock();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(Arc::new(tcp_socket));
+    fd as isize
+}
 
-    // net_interrupt_handler();
-    // NOTICE: There does not have interrupt handler, just call it munually.
-    loop {
-        
-        if !port_acceptable(port_index) {
-            break;
+fn get_file(fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
+    let process = current_process().unwrap();
+    let inner = process.acquire_inner_lock();
+    inner.fd_table.get(fd).and_then(|file| file.clone())
+}
+
+// create a socket, only SOCK_DGRAM is supported, use listen/accept/connect for tcp
+pub fn sys_socket(sock_type: usize) -> isize {
+    if sock_type != SOCK_DGRAM {
+        return Errno::EINVAL.into();
+    }
+    if !net_available() {
+        return Errno::ENETDOWN.into();
+    }
+    let udp = UDP::new();
+    let process = current_process().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(Arc::new(udp));
+    fd as isize
+}
+
+pub fn sys_bind(fd: usize, port: usize) -> isize {
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return Errno::EBADF.into(),
+    };
+    let udp = match file.as_udp() {
+        Some(udp) => udp,
+        None => return Errno::ENOTSOCK.into(),
+    };
+    if port > u16::MAX as usize {
+        return Errno::EINVAL.into();
+    }
+    match udp.bind(port as u16) {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
+
+pub fn sys_sendto(fd: usize, buf: *const u8, len: usize, addr: *const SockAddr) -> isize {
+    let token = current_user_token();
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return Errno::EBADF.into(),
+    };
+    let udp = match file.as_udp() {
+        Some(udp) => udp,
+        None => return Errno::ENOTSOCK.into(),
+    };
+    let addr = match read_user(token, addr) {
+        Some(addr) => addr,
+        None => return Errno::EFAULT.into(),
+    };
+    let buffers = match translated_byte_buffer(token, buf, len, false) {
+        Ok(buffers) => buffers,
+        Err(_) => return Errno::EFAULT.into(),
+    };
+    match udp.sendto(UserBuffer::new(buffers), &addr) {
+        Ok(len) => len as isize,
+        Err(errno) => errno,
+    }
+}
+
+// the address of the sender is written to `addr` if it is not null
+pub fn sys_recvfrom(fd: usize, buf: *const u8, len: usize, addr: *mut SockAddr, key: usize, cid: usize) -> isize {
+    let token = current_user_token();
+    let pid = current_process().unwrap().getpid();
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return Errno::EBADF.into(),
+    };
+    let udp = match file.as_udp() {
+        Some(udp) => udp,
+        None => return Errno::ENOTSOCK.into(),
+    };
+    let addr = if addr.is_null() {
+        None
+    } else {
+        match translated_byte_buffer(token, addr as *const u8, core::mem::size_of::<SockAddr>(), true) {
+            Ok(buffers) => Some(UserBuffer::new(buffers)),
+            Err(_) => return Errno::EFAULT.into(),
         }
+    };
+    let buffers = match translated_byte_buffer(token, buf, len, true) {
+        Ok(buffers) => buffers,
+        Err(_) => return Errno::EFAULT.into(),
+    };
+    if key == usize::MAX && cid == usize::MAX {
+        match udp.recvfrom(UserBuffer::new(buffers), addr) {
+            Ok(len) => len as isize,
+            Err(errno) => errno,
+        }
+    } else {
+        let work = udp.arecvfrom(UserBuffer::new(buffers), addr, cid, pid);
+        spawn_async_work(pid, cid, work);
+        0
     }
-    debug!("recived!!!!");
-    let cx = current_trap_cx();
-    cx.x[10] as isize
-}
\ No newline at end of file
+}
+
+pub fn sys_get_ifconfig(config: *mut IfConfig) -> isize {
+    if !net_available() {
+        return Errno::ENETDOWN.into();
+    }
+    let token = current_user_token();
+    let if_config = *NET_CONFIG.lock();
+    match write_user(token, config, &if_config) {
+        Some(()) => 0,
+        None => Errno::EFAULT.into(),
+    }
+}
+
+// change the address of the interface at runtime
+pub fn sys_set_ifconfig(config: *const IfConfig) -> isize {
+    let token = current_user_token();
+    let config = match read_user(token, config) {
+        Some(config) => config,
+        None => return Errno::EFAULT.into(),
+    };
+    // a multicast mac or an invalid netmask would leave the interface unusable
+    if config.mac[0] & 1 != 0 || config.ip == [0; 4] || prefix_len(config.netmask).is_none() {
+        return Errno::EINVAL.into();
+    }
+    match set_net_config(config) {
+        Ok(()) => 0,
+        Err(errno) => errno,
+    }
+}
@@ -1,12 +1,13 @@
-use alloc::sync::Arc;
+use alloc::string::String;
 use lib_so::update_prio;
 use crate::config::{CPU_NUM, MEMORY_END};
 use crate::loader::get_app_data_by_name;
 use crate::{mm, println};
 use crate::plic::{get_context, Plic};
-use crate::task::{add_task, current_task, current_process, current_user_token, exit_current_and_run_next, hart_id, mmap, munmap, set_current_priority, suspend_current_and_run_next, WAIT_LOCK, current_trap_cx};
+use crate::task::{add_task, block_current_and_run_next, current_task, current_process, current_user_token, exit_current_and_run_next, hart_id, mmap, mprotect, munmap, set_current_priority, suspend_current_and_run_next, WAIT_LOCK, current_trap_cx};
 use crate::timer::get_time;
-use crate::trap::{push_trap_record, UserTrapRecord};
+use crate::trap::{push_trap_record, UserTrapError, UserTrapRecord};
+use crate::syscall::Errno;
 use alloc::vec::Vec;
 use core::mem::size_of;
 use core::ptr::null;
@@ -25,7 +26,7 @@ pub fn sys_yield() -> isize {
 pub fn sys_set_priority(prio: isize) -> isize {
     match set_current_priority(prio) {
         Ok(prio) => prio,
-        Err(err) => err,
+        Err(_) => Errno::EINVAL.into(),
     }
 }
 
@@ -33,22 +34,30 @@ pub fn sys_get_time(time: usize, tz: usize) -> isize {
     let token = current_user_token();
     let mut pas: Vec<*mut usize> = Vec::new();
     match mm::translate_writable_va(token, time) {
-        Err(_) => return -1,
+        Err(_) => return Errno::EFAULT.into(),
         Ok(pa) => pas.push(pa as *mut usize),
     }
     match mm::translate_writable_va(token, time + size_of::<usize>()) {
-        Err(_) => return -1,
+        Err(_) => return Errno::EFAULT.into(),
         Ok(pa) => pas.push(pa as *mut usize),
     }
     get_time(pas, tz)
 }
 
-pub fn sys_mmap(start: usize, len: usize, port: usize) -> isize {
-    mmap(start, len, port).unwrap_or(-1)
+/// Return the start address of the mapping, `start` is only a hint without `MAP_FIXED`.
+pub fn sys_mmap(start: usize, len: usize, port: usize, flags: usize) -> isize {
+    match mmap(start, len, port, flags) {
+        Ok(start) => start as isize,
+        Err(errno) => errno,
+    }
 }
 
 pub fn sys_munmap(start: usize, len: usize) -> isize {
-    munmap(start, len).unwrap_or(-1)
+    munmap(start, len).unwrap_or_else(|errno| errno)
+}
+
+pub fn sys_mprotect(start: usize, len: usize, port: usize) -> isize {
+    mprotect(start, len, port).unwrap_or_else(|errno| errno)
 }
 
 pub fn sys_getpid() -> isize {
@@ -73,75 +82,131 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
+/// Read a null-terminated array of C strings from user space, a null `ptr` is an empty array.
+/// Return -EFAULT if the array or one of the strings is not mapped.
+fn translated_str_array(token: usize, mut ptr: *const usize) -> Result<Vec<String>, isize> {
+    let mut strings = Vec::new();
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    loop {
+        let str_ptr = mm::read_user(token, ptr).ok_or(isize::from(Errno::EFAULT))?;
+        if str_ptr == 0 {
+            break;
+        }
+        strings.push(mm::read_user_str(token, str_ptr as *const u8).ok_or(isize::from(Errno::EFAULT))?);
+        unsafe {
+            ptr = ptr.add(1);
+        }
+    }
+    Ok(strings)
+}
+
+/// Return -EFAULT if `path` or the arrays are not mapped, -ENOENT if there is no such app,
+/// -E2BIG if the arguments and environment do not fit in the new user stack.
+/// The current program is kept in all of these cases.
+pub fn sys_exec(path: *const u8, args: *const usize, envs: *const usize) -> isize {
     let token = current_user_token();
-    let path = mm::translated_str(token, path);
-    debug!("EXEC {}", &path);
+    let path = match mm::read_user_str(token, path) {
+        Some(path) => path,
+        None => return Errno::EFAULT.into(),
+    };
+    let args = match translated_str_array(token, args) {
+        Ok(args) => args,
+        Err(err) => return err,
+    };
+    let envs = match translated_str_array(token, envs) {
+        Ok(envs) => envs,
+        Err(err) => return err,
+    };
+    debug!("EXEC {} {:?}", &path, &args);
     if let Some(data) = get_app_data_by_name(path.as_str()) {
         let task = current_process().unwrap();
-        task.exec(data);
-        0
+        match task.exec(data, args, envs) {
+            Ok(()) => 0,
+            Err(err) => err,
+        }
     } else {
         warn!("exec failed!");
-        -1
+        Errno::ENOENT.into()
     }
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Reap a zombie child `pid`, or any child if `pid` is -1.
+/// The calling thread blocks until a child exits, unless `cid` is given: then 0 is returned
+/// right away and coroutine `cid` is woken up once a child exits, to reap it with another call.
+/// If there is not a child process whose pid is same as given, return -ECHILD.
+/// The exit code is not stored if `exit_code_ptr` is null, -EFAULT is returned if it can't be
+/// stored, the child is reaped in both cases.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, _key: usize, cid: usize) -> isize {
     trace!("sys_waitpid {}", pid);
     let process = current_process().unwrap();
-    // find a child process
-    let _ = WAIT_LOCK.lock();
-    // ---- hold current PCB lock
-    let mut inner = process.acquire_inner_lock();
-    if inner
-        .children
-        .iter()
-        .find(|p| pid == -1 || pid as usize == p.getpid())
-        .is_none()
-    {
-        return -1;
-        // ---- release current PCB lock
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily hold child PCB lock
-        p.acquire_inner_lock().is_zombie() && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB lock
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after removing from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily hold child lock
-        let exit_code = child.acquire_inner_lock().exit_code;
-        // ++++ release child PCB lock
-        *mm::translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
+    loop {
+        // find a child process, exiting children take WAIT_LOCK before waking the waiters
+        let wl = WAIT_LOCK.lock();
+        // ---- hold current PCB lock
+        let mut inner = process.acquire_inner_lock();
+        if inner
+            .children
+            .iter()
+            .find(|p| pid == -1 || pid as usize == p.getpid())
+            .is_none()
+        {
+            return Errno::ECHILD.into();
+            // ---- release current PCB lock
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily hold child PCB lock
+            p.acquire_inner_lock().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+            // ++++ release child PCB lock
+        });
+        if let Some((idx, _)) = pair {
+            let child = inner.children.remove(idx);
+            // whoever killed the child or looked it up by pid may still hold it for a moment,
+            // it is deallocated once the last reference is dropped
+            let found_pid = child.getpid();
+            // ++++ temporarily hold child lock
+            let exit_code = child.acquire_inner_lock().exit_code;
+            // ++++ release child PCB lock
+            let token = inner.memory_set.token();
+            // ---- release current PCB lock, the store may copy a copy-on-write page
+            drop(inner);
+            drop(wl);
+            if !exit_code_ptr.is_null() && mm::write_user(token, exit_code_ptr, &exit_code).is_none() {
+                return Errno::EFAULT.into();
+            }
+            return found_pid as isize;
+        }
+        if cid != usize::MAX {
+            inner.async_waiters.push((pid, cid));
+            return 0;
+        }
+        inner.wait_queue.push_back(current_task().unwrap());
+        drop(inner);
+        drop(wl);
+        block_current_and_run_next();
     }
-    // ---- release current PCB lock automatically
 }
 
-pub fn sys_spawn(file: *const u8) -> isize {
-    // trace!("SPAWN start");
-    // let current_task = current_task().unwrap();
-    // match current_task.spawn(file) {
-    //     Ok(new_task) => {
-    //         let new_pid = new_task.pid.0;
-    //         add_task(new_task);
-    //         debug!("new_task via spawn {:?}", new_pid);
-    //         new_pid as isize
-    //     }
-    //     Err(_) => {
-    //         warn!("spawn failed!");
-    //         -1
-    //     }
-    // }
-    -1
+pub fn sys_spawn(path: *const u8) -> isize {
+    let token = current_user_token();
+    let path = match mm::read_user_str(token, path) {
+        Some(path) => path,
+        None => return Errno::EFAULT.into(),
+    };
+    debug!("SPAWN {}", &path);
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        let new_process = current_process().unwrap().spawn(data, path);
+        let new_pid = new_process.getpid();
+        let task = new_process.acquire_inner_lock().get_task(0);
+        update_prio(new_pid + 1, 0);
+        add_task(task);
+        debug!("new_task {:?} via spawn", new_pid);
+        new_pid as isize
+    } else {
+        warn!("spawn failed!");
+        Errno::ENOENT.into()
+    }
 }
 
 pub fn sys_flush_trace() -> isize {
@@ -174,22 +239,21 @@ pub fn sys_init_user_trap(user_trap_handler_tid: usize) -> isize {
             return errno;
         }
     }
-    -1
 }
 
 pub fn sys_send_msg(pid: usize, msg: usize) -> isize {
-    if push_trap_record(
+    match push_trap_record(
         pid,
         UserTrapRecord {
             cause: pid << 4,
             message: msg,
         },
-    )
-    .is_ok()
-    {
-        0
-    } else {
-        -1
+    ) {
+        Ok(()) => 0,
+        Err(UserTrapError::TaskNotFound) => Errno::ESRCH.into(),
+        Err(UserTrapError::TrapUninitialized) => Errno::EINVAL.into(),
+        Err(UserTrapError::TrapBufferFull) => Errno::EAGAIN.into(),
+        Err(UserTrapError::TrapThreadBusy) => Errno::EBUSY.into(),
     }
 }
 
@@ -202,12 +266,19 @@ pub fn sys_set_timer(time_us: usize, cid: usize) -> isize {
     0
 }
 
+/// Remove the timers set by coroutine `cid` of current process.
+pub fn sys_cancel_timer(cid: usize) -> isize {
+    let pid = current_process().unwrap().pid.0;
+    crate::timer::cancel_timers(pid, cid);
+    0
+}
+
 pub fn sys_claim_ext_int(device_id: usize) -> isize {
     let device_id = device_id as u16;
     let current_process = current_process().unwrap();
     let mut inner = current_process.acquire_inner_lock();
     if !inner.is_user_trap_enabled() {
-        return -1;
+        return Errno::EINVAL.into();
     }
     use crate::plic;
     use crate::trap::USER_EXT_INT_MAP;
@@ -216,6 +287,10 @@ pub fn sys_claim_ext_int(device_id: usize) -> isize {
         Some(info) => {
             let mut map = USER_EXT_INT_MAP.lock();
             if !map.contains_key(&device_id) {
+                // the board can't deliver interrupts to user mode
+                if (0..CPU_NUM).any(|hart_id| plic::get_context(hart_id, 'U').is_none()) {
+                    return Errno::ENODEV.into();
+                }
                 let pid = current_process.getpid();
                 debug!(
                     "[syscall claim] mapping device {} to pid {}",
@@ -224,47 +299,32 @@ pub fn sys_claim_ext_int(device_id: usize) -> isize {
                 map.insert(device_id, pid);
                 info.devices.push((device_id, false));
                 for hart_id in 0..CPU_NUM {
-                    let claim_addr = Plic::context_address(plic::get_context(hart_id, 'U'));
+                    let claim_addr = Plic::context_address(plic::get_context(hart_id, 'U').unwrap());
                     if inner
                         .memory_set
                         .mmio_map(claim_addr, crate::config::PAGE_SIZE, 0b11)
                         .is_err()
                     {
                         warn!("[syscall claim] map plic claim reg failed!");
-                        return -6;
+                        return Errno::ENOMEM.into();
                     }
                 }
             }
             use crate::uart;
-            match device_id {
-                #[cfg(feature = "board_qemu")]
-                13 | 14 | 15 => {
-                    let base_address = uart::get_base_addr_from_irq(device_id);
-                    match inner
-                        .memory_set
-                        .mmio_map(base_address, uart::SERIAL_ADDRESS_STRIDE, 0x3)
-                    {
-                        Ok(_) => base_address as isize,
-                        Err(_) => -2,
-                    }
-                }
-                #[cfg(feature = "board_lrv")]
-                5 | 6 | 7 => {
-                    let base_address = uart::get_base_addr_from_irq(device_id);
-                    match inner
-                        .memory_set
-                        .mmio_map(base_address, uart::SERIAL_ADDRESS_STRIDE, 0x3)
-                    {
-                        Ok(_) => base_address as isize,
-                        Err(_) => -2,
+            match uart::get_uart_from_irq(device_id) {
+                // the console stays in the kernel
+                Some(uart) if uart::irq_to_serial_id(device_id) != Some(0) => {
+                    match inner.memory_set.mmio_map(uart.base, uart.size, 0x3) {
+                        Ok(_) => uart.base as isize,
+                        Err(_) => Errno::ENOMEM.into(),
                     }
                 }
-                _ => -4,
+                _ => Errno::ENODEV.into(),
             }
         }
         None => {
             warn!("[syscall claim] user trap info is None!");
-            -5
+            Errno::EINVAL.into()
         }
     }
 }
@@ -276,7 +336,7 @@ pub fn sys_set_ext_int_enable(device_id: usize, enable: usize) -> isize {
     let current_process = current_process().unwrap();
     let mut inner = current_process.acquire_inner_lock();
     if !inner.is_user_trap_enabled() {
-        return -1;
+        return Errno::EINVAL.into();
     }
     use crate::trap::USER_EXT_INT_MAP;
     let user_trap_info = &mut inner.user_trap_info;
@@ -287,13 +347,19 @@ pub fn sys_set_ext_int_enable(device_id: usize, enable: usize) -> isize {
                     for (dev_id, en) in &mut info.devices {
                         if *dev_id == device_id {
                             *en = is_enable;
+                            let u_context = match get_context(hart_id(), 'U') {
+                                Some(context) => context,
+                                None => return Errno::ENODEV.into(),
+                            };
                             if is_enable {
-                                Plic::enable(get_context(hart_id(), 'U'), device_id);
+                                Plic::enable(u_context, device_id);
                                 for hart in 0..CPU_NUM {
-                                    Plic::disable(get_context(hart, 'S'), device_id);
+                                    if let Some(s_context) = get_context(hart, 'S') {
+                                        Plic::disable(s_context, device_id);
+                                    }
                                 }
                             } else {
-                                Plic::disable(get_context(hart_id(), 'U'), device_id);
+                                Plic::disable(u_context, device_id);
                             }
                         }
                     }
@@ -305,16 +371,16 @@ pub fn sys_set_ext_int_enable(device_id: usize, enable: usize) -> isize {
                         device_id,
                         current_process.getpid()
                     );
-                    return -1;
+                    return Errno::EPERM.into();
                 }
             } else {
                 warn!("[sys set ext] device not claimed!");
-                return -2;
+                return Errno::ENODEV.into();
             }
         }
         None => {
             warn!("[syscall claim] user trap info is None!");
-            -5
+            Errno::EINVAL.into()
         }
     }
 }
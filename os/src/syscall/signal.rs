@@ -0,0 +1,76 @@
+use crate::mm::{read_user, write_user};
+use crate::syscall::{Errno, SignalAction, SignalFlags, SIGKILL};
+use crate::task::{current_process, current_user_token, kill_process, pid2process, pop_signal_frame, queue_signals_to_user_trap, signal_flag, take_fatal_signal};
+
+// the signal is acted on when the main thread of the target returns to user space,
+// or right away by its user trap handler thread. A fatal signal to another process
+// terminates it at once, wherever its threads are blocked
+pub fn sys_kill(pid: usize, signum: usize) -> isize {
+    let process = match pid2process(pid) {
+        Some(process) => process,
+        None => return Errno::ESRCH.into(),
+    };
+    if signum == 0 {
+        return 0;
+    }
+    let flag = signal_flag(signum);
+    if flag.is_empty() {
+        return Errno::EINVAL.into();
+    }
+    let mut inner = process.acquire_inner_lock();
+    if inner.is_zombie() {
+        return Errno::ESRCH.into();
+    }
+    inner.signals.pending |= flag;
+    queue_signals_to_user_trap(pid, &mut inner);
+    if pid != current_process().unwrap().getpid() {
+        if let Some(exit_code) = take_fatal_signal(&mut inner) {
+            drop(inner);
+            kill_process(&process, exit_code);
+        }
+    }
+    0
+}
+
+pub fn sys_sigaction(signum: usize, action: *const SignalAction, old_action: *mut SignalAction) -> isize {
+    if signal_flag(signum).is_empty() || (signum == SIGKILL && !action.is_null()) {
+        return Errno::EINVAL.into();
+    }
+    let token = current_user_token();
+    // copy the new action before taking the lock, translating may fault in the page
+    let action = if action.is_null() {
+        None
+    } else {
+        match read_user(token, action) {
+            Some(action) => Some(action),
+            None => return Errno::EFAULT.into(),
+        }
+    };
+    let process = current_process().unwrap();
+    let old = process.acquire_inner_lock().signals.actions[signum];
+    // nothing is changed if the old action can't be stored
+    if !old_action.is_null() && write_user(token, old_action, &old).is_none() {
+        return Errno::EFAULT.into();
+    }
+    if let Some(action) = action {
+        let mut inner = process.acquire_inner_lock();
+        inner.signals.actions[signum] = action;
+        queue_signals_to_user_trap(process.getpid(), &mut inner);
+    }
+    0
+}
+
+pub fn sys_sigprocmask(mask: usize) -> isize {
+    let process = current_process().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    let old = inner.signals.set_mask(SignalFlags::from_bits_truncate(mask as u32));
+    queue_signals_to_user_trap(process.getpid(), &mut inner);
+    old.bits() as isize
+}
+
+pub fn sys_sigreturn() -> isize {
+    match pop_signal_frame() {
+        Some(a0) => a0 as isize,
+        None => Errno::EFAULT.into(),
+    }
+}
@@ -1,5 +1,6 @@
 use crate::task::current_process;
-use crate::sync::{SimpleMutex, MutexSpin, MutexBlocking, Condvar};
+use crate::sync::{SimpleMutex, MutexSpin, MutexBlocking, Condvar, Semaphore};
+use crate::syscall::Errno;
 use alloc::sync::Arc;
 
 pub fn sys_mutex_create(blocking: bool) -> isize {
@@ -28,7 +29,10 @@ pub fn sys_mutex_create(blocking: bool) -> isize {
 pub fn sys_mutex_lock(mutex_id: usize) -> isize {
     let process = current_process().unwrap();
     let process_inner = process.acquire_inner_lock();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Arc::clone(mutex),
+        _ => return Errno::EINVAL.into(),
+    };
     drop(process_inner);
     drop(process);
     mutex.lock();
@@ -38,13 +42,63 @@ pub fn sys_mutex_lock(mutex_id: usize) -> isize {
 pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
     let process = current_process().unwrap();
     let process_inner = process.acquire_inner_lock();
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Arc::clone(mutex),
+        _ => return Errno::EINVAL.into(),
+    };
     drop(process_inner);
     drop(process);
     mutex.unlock();
     0
 }
 
+pub fn sys_semaphore_create(res_count: usize) -> isize {
+    let process = current_process().unwrap();
+    let mut process_inner = process.acquire_inner_lock();
+    let id = if let Some(id) = process_inner
+        .semaphore_list
+        .iter()
+        .enumerate()
+        .find(|(_, item)| item.is_none())
+        .map(|(id, _)| id)
+    {
+        process_inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(res_count)));
+        id
+    } else {
+        process_inner
+            .semaphore_list
+            .push(Some(Arc::new(Semaphore::new(res_count))));
+        process_inner.semaphore_list.len() - 1
+    };
+    id as isize
+}
+
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let process = current_process().unwrap();
+    let process_inner = process.acquire_inner_lock();
+    let sem = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(sem)) => Arc::clone(sem),
+        _ => return Errno::EINVAL.into(),
+    };
+    drop(process_inner);
+    drop(process);
+    sem.up();
+    0
+}
+
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let process = current_process().unwrap();
+    let process_inner = process.acquire_inner_lock();
+    let sem = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(sem)) => Arc::clone(sem),
+        _ => return Errno::EINVAL.into(),
+    };
+    drop(process_inner);
+    drop(process);
+    sem.down();
+    0
+}
+
 pub fn sys_condvar_create(_arg: usize) -> isize {
     let process = current_process().unwrap();
     let mut process_inner = process.acquire_inner_lock();
@@ -69,7 +123,10 @@ pub fn sys_condvar_create(_arg: usize) -> isize {
 pub fn sys_condvar_signal(condvar_id: usize) -> isize {
     let process = current_process().unwrap();
     let process_inner = process.acquire_inner_lock();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
+    let condvar = match process_inner.condvar_list.get(condvar_id) {
+        Some(Some(condvar)) => Arc::clone(condvar),
+        _ => return Errno::EINVAL.into(),
+    };
     drop(process_inner);
     drop(process);
     condvar.signal();
@@ -79,8 +136,14 @@ pub fn sys_condvar_signal(condvar_id: usize) -> isize {
 pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
     let process = current_process().unwrap();
     let process_inner = process.acquire_inner_lock();
-    let condvar = Arc::clone(process_inner.condvar_list[condvar_id].as_ref().unwrap());
-    let mutex = Arc::clone(process_inner.mutex_list[mutex_id].as_ref().unwrap());
+    let condvar = match process_inner.condvar_list.get(condvar_id) {
+        Some(Some(condvar)) => Arc::clone(condvar),
+        _ => return Errno::EINVAL.into(),
+    };
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => Arc::clone(mutex),
+        _ => return Errno::EINVAL.into(),
+    };
     drop(process_inner);
     drop(process);
     condvar.wait_with_mutex(mutex);
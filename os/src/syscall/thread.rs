@@ -1,6 +1,7 @@
-use crate::{mm::kernel_token, task::{add_task, current_task, TaskControlBlock, remove_uintr_task}, trap::{trap_handler, TrapContext}};
+use crate::{mm::{kernel_token, write_user}, task::{add_task, current_task, current_user_token, TaskControlBlock, remove_uintr_task}, trap::{trap_handler, TrapContext}};
 use alloc::sync::Arc;
 use crate::task::{block_current_and_run_next, current_process, suspend_current_and_run_next, take_current_task, WAIT_LOCK, WAITTID_LOCK};
+use crate::syscall::Errno;
 
 pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
     let task = current_task().unwrap();
@@ -78,47 +79,80 @@ pub fn sys_gettid() -> isize {
         .tid as isize
 }
 
-/// thread does not exist, return -1
-/// thread has not exited yet, return -2
-/// otherwise, return thread's exit code
-pub fn sys_waittid(tid: usize) -> i32 {
-    // debug!("wait start: {}", tid);
-    // warn!("wait tid: {}", tid);
+/// thread does not exist, return -ESRCH
+/// thread waits for itself, return -EDEADLK
+/// thread is detached, return -EINVAL
+/// otherwise block until the thread exits, reap it and store its exit code at `exit_code_ptr`
+/// unless it is null, return -EFAULT if it can't be stored, the thread is reaped anyway
+pub fn sys_waittid(tid: usize, exit_code_ptr: *mut i32) -> isize {
     let task = current_task().unwrap();
     let process = task.process.upgrade().unwrap();
-    let wtl = WAITTID_LOCK.lock();
-    // warn!("wait tid: {} 2", tid);
-    let mut process_inner = process.acquire_inner_lock();
-    let task_inner = task.acquire_inner_lock();
     // a thread cannot wait for itself
-    if task_inner.res.as_ref().unwrap().tid == tid {
-        drop(wtl);
-        return -1;
+    if task.acquire_inner_lock().res.as_ref().unwrap().tid == tid {
+        return Errno::EDEADLK.into();
     }
-    let mut exit_code: Option<i32> = None;
-    let waited_task = process_inner.tasks[tid].as_ref();
-    if let Some(waited_task) = waited_task {
-        let inner = waited_task.acquire_inner_lock();
-        // warn!("wait tid: {} 3", tid);
-        if let Some(waited_exit_code) = inner.exit_code {
-            exit_code = Some(waited_exit_code);
+    loop {
+        let wtl = WAITTID_LOCK.lock();
+        let mut process_inner = process.acquire_inner_lock();
+        let waited_task = match process_inner.tasks.get(tid).and_then(|t| t.as_ref()) {
+            Some(waited_task) => waited_task,
+            // waited thread does not exist
+            None => return Errno::ESRCH.into(),
+        };
+        let waited_inner = waited_task.acquire_inner_lock();
+        if waited_inner.detached {
+            return Errno::EINVAL.into();
         }
-    } else {
+        if let Some(exit_code) = waited_inner.exit_code {
+            drop(waited_inner);
+            // dealloc the exited thread
+            process_inner.dealloc_tid(tid);
+            process_inner.tasks[tid] = None;
+            drop(process_inner);
+            drop(wtl);
+            // translating may fault in the page, which takes the process lock
+            if !exit_code_ptr.is_null() && write_user(current_user_token(), exit_code_ptr, &exit_code).is_none() {
+                return Errno::EFAULT.into();
+            }
+            return 0;
+        }
+        drop(waited_inner);
+        // the exiting thread takes WAITTID_LOCK before waking the queue
+        process_inner.thread_wait_queue.push_back(Arc::clone(&task));
+        drop(process_inner);
         drop(wtl);
-        // waited thread does not exist
-        return -1;
+        block_current_and_run_next();
     }
-    if let Some(exit_code) = exit_code {
-        // dealloc the exited thread
+}
+
+/// thread does not exist, return -ESRCH
+/// the main thread or an already detached thread, return -EINVAL
+/// otherwise the thread is reaped as soon as it exits, threads joining it get -EINVAL
+pub fn sys_thread_detach(tid: usize) -> isize {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let _wtl = WAITTID_LOCK.lock();
+    let mut process_inner = process.acquire_inner_lock();
+    let detached_task = match process_inner.tasks.get(tid).and_then(|t| t.as_ref()) {
+        Some(detached_task) => Arc::clone(detached_task),
+        None => return Errno::ESRCH.into(),
+    };
+    let mut detached_inner = detached_task.acquire_inner_lock();
+    if tid == 0 || detached_inner.detached {
+        return Errno::EINVAL.into();
+    }
+    if detached_inner.exit_code.is_some() {
+        // already exited, nobody else may join it
+        drop(detached_inner);
         process_inner.dealloc_tid(tid);
         process_inner.tasks[tid] = None;
-        drop(wtl);
-        exit_code
     } else {
-        // debug!("wait end: {}", tid);
-        // warn!("wait tid: {} end", tid);
-        drop(wtl);
-        // waited thread has not exited
-        -2
+        detached_inner.detached = true;
+        drop(detached_inner);
+    }
+    // the joiners see the thread detached or gone and return
+    for task in process_inner.thread_wait_queue.drain(..) {
+        add_task(task);
     }
-}
\ No newline at end of file
+    0
+}
@@ -6,9 +6,10 @@ mod processor;
 mod switch;
 mod task;
 mod process;
+mod signal;
 
 use crate::loader::get_app_data_by_name;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use alloc::vec::Vec;
 use lazy_static::*;
 
@@ -19,13 +20,15 @@ pub use context::TaskContext;
 pub use pid::{pid_alloc, KernelStack, PidHandle};
 pub use pool::{add_task, fetch_task, prioritize_task, pid2process, add_user_intr_task, remove_uintr_task};
 pub use processor::{
-    current_task, current_process, current_trap_cx, current_user_token, hart_id, mmap, munmap, run_tasks, schedule,
+    current_task, current_process, current_trap_cx, current_user_token, handle_page_fault, hart_id, mmap, mprotect, munmap, run_tasks, schedule,
     set_current_priority, take_current_task, current_trap_cx_user_va
 };
 pub use task::{TaskControlBlock, TaskStatus};
 use crate::task::pool::{remove_from_pid2process};
 pub use process::ProcessControlBlock;
+pub use signal::{handle_signals, pop_signal_frame, queue_signals_to_user_trap, signal_flag, take_fatal_signal};
 use crate::task::pid::TaskUserRes;
+use crate::trap::{push_trap_record, UserTrapRecord};
 
 lazy_static! {
     pub static ref WAIT_LOCK: Mutex<()> = Mutex::new(());
@@ -58,6 +61,21 @@ pub fn suspend_current_and_run_next() {
 }
 
 pub fn exit_current_and_run_next(exit_code: i32) {
+    exit_current(exit_code, false);
+}
+
+/// Terminate the whole process of the current thread, its other threads are stopped first.
+pub fn exit_process_and_run_next(exit_code: i32) {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    process.kill_other_threads(&task);
+    drop(process);
+    drop(task);
+    exit_current(exit_code, true);
+}
+
+/// The main thread takes the process with it, so does any thread if `whole_process`.
+fn exit_current(exit_code: i32, whole_process: bool) {
     debug!("exit start");
     // ++++++ hold initproc PCB lock here
     // let mut initproc_inner = INITPROC.acquire_inner_lock();
@@ -81,17 +99,22 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     // warn!("exit start: {} 2", tid);
     inner.res = None;
     // warn!("exit start: {} 3", tid);
+    let detached = inner.detached;
     drop(inner);
-    drop(wtl);
-    // do not move to its parent but under initproc
-    if tid == 0 {
-        let wl = WAIT_LOCK.lock();
-        let pid = process.getpid();
-        remove_from_pid2process(pid);
-        debug!("test2");
+    if tid != 0 {
+        // still holding WAITTID_LOCK, a waittid can't miss the wakeup
         let mut process_inner = process.acquire_inner_lock();
-        if let Some(trap_info) = &process_inner.user_trap_info {
-            trap_info.remove_user_ext_int_map();
+        for task in process_inner.thread_wait_queue.drain(..) {
+            add_task(task);
+        }
+        if detached {
+            process_inner.dealloc_tid(tid);
+            process_inner.tasks[tid] = None;
+        }
+    }
+    drop(wtl);
+    if tid == 0 || whole_process {
+        if process.acquire_inner_lock().user_trap_info.is_some() {
             use riscv::register::sie;
             unsafe {
                 sie::clear_uext();
@@ -99,40 +122,7 @@ pub fn exit_current_and_run_next(exit_code: i32) {
                 sie::clear_utimer();
             }
         }
-        process_inner.is_zombie = true;
-        process_inner.exit_code = exit_code;
-        {
-            let mut initproc_inner = INITPROC.acquire_inner_lock();
-            for child in process_inner.children.iter() {
-                child.acquire_inner_lock().parent = Some(Arc::downgrade(&INITPROC));
-                initproc_inner.children.push(child.clone());
-            }
-        }
-
-        if process_inner.user_trap_handler_task != None {
-            let task = process_inner.user_trap_handler_task.clone().unwrap();
-            let mut inner = task.acquire_inner_lock();
-            info!(
-                "pid: {} tid: {} exited with code {}, time intr: {}, cycle count: {}, interrupt time: {}, user_cycle: {} us",
-                1, 1, 2, inner.time_intr_count, inner.total_cpu_cycle_count, inner.interrupt_time, inner.user_time_us
-            );
-        }
-
-        let mut recycle_res = Vec::<TaskUserRes>::new();
-        for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
-            let task = task.as_ref().unwrap();
-            let mut task_inner = task.acquire_inner_lock();
-            if let Some(res) = task_inner.res.take() {
-                recycle_res.push(res);
-            }
-        }
-        process_inner.children.clear();
-        process_inner.memory_set.recycle_data_pages();
-        process_inner.fd_table.clear();
-        process_inner.user_trap_handler_task = None;
-        drop(process_inner);
-        recycle_res.clear();
-
+        exit_process(&process, exit_code);
     }
 
     // **** release current PCB lock
@@ -146,6 +136,101 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     schedule(&mut _unused as *mut _);
 }
 
+/// Terminate `process`, which is another process than the current one. Its threads
+/// are stopped wherever they are, the blocked ones are dropped instead of being woken up.
+pub fn kill_process(process: &Arc<ProcessControlBlock>, exit_code: i32) {
+    process.stop_threads(None);
+    exit_process(process, exit_code);
+}
+
+/// Turn `process` into a zombie, none of its threads may run any more.
+/// Nothing is done if it is a zombie already.
+fn exit_process(process: &Arc<ProcessControlBlock>, exit_code: i32) {
+    // do not move to its parent but under initproc
+    let wl = WAIT_LOCK.lock();
+    let pid = process.getpid();
+    let mut process_inner = process.acquire_inner_lock();
+    if process_inner.is_zombie {
+        return;
+    }
+    remove_from_pid2process(pid);
+    debug!("test2");
+    if let Some(trap_info) = &process_inner.user_trap_info {
+        trap_info.remove_user_ext_int_map();
+    }
+    process_inner.is_zombie = true;
+    process_inner.exit_code = exit_code;
+    let parent = process_inner.parent.as_ref().and_then(Weak::upgrade);
+    // the children that already exited wait to be reaped by initproc now
+    let mut zombie_children = Vec::new();
+    {
+        let mut initproc_inner = INITPROC.acquire_inner_lock();
+        for child in process_inner.children.iter() {
+            let mut child_inner = child.acquire_inner_lock();
+            child_inner.parent = Some(Arc::downgrade(&INITPROC));
+            if child_inner.is_zombie {
+                zombie_children.push(child.getpid());
+            }
+            initproc_inner.children.push(child.clone());
+        }
+    }
+
+    if process_inner.user_trap_handler_task != None {
+        let task = process_inner.user_trap_handler_task.clone().unwrap();
+        let inner = task.acquire_inner_lock();
+        info!(
+            "pid: {} tid: {} exited with code {}, time intr: {}, cycle count: {}, interrupt time: {}, user_cycle: {} us",
+            1, 1, 2, inner.time_intr_count, inner.total_cpu_cycle_count, inner.interrupt_time, inner.user_time_us
+        );
+    }
+
+    let mut recycle_res = Vec::<TaskUserRes>::new();
+    for task in process_inner.tasks.iter().filter(|t| t.is_some()) {
+        let task = task.as_ref().unwrap();
+        let mut task_inner = task.acquire_inner_lock();
+        if let Some(res) = task_inner.res.take() {
+            recycle_res.push(res);
+        }
+    }
+    process_inner.children.clear();
+    process_inner.memory_set.recycle_data_pages();
+    process_inner.fd_table.clear();
+    // writers blocked on the mailbox give up
+    process_inner.mail_box.close();
+    process_inner.user_trap_handler_task = None;
+    drop(process_inner);
+    recycle_res.clear();
+    // still holding WAIT_LOCK, a waitpid can't miss the wakeup
+    if let Some(parent) = parent {
+        wake_child_waiters(&parent, pid);
+    }
+    for child_pid in zombie_children {
+        wake_child_waiters(&INITPROC, child_pid);
+    }
+    drop(wl);
+}
+
+/// Wake the threads of `parent` blocked in waitpid and the coroutines waiting for
+/// child `pid` asynchronously.
+fn wake_child_waiters(parent: &Arc<ProcessControlBlock>, pid: usize) {
+    let mut parent_inner = parent.acquire_inner_lock();
+    for task in parent_inner.wait_queue.drain(..) {
+        add_task(task);
+    }
+    let mut cids = Vec::new();
+    parent_inner.async_waiters.retain(|&(wait_pid, cid)| {
+        let matched = wait_pid == -1 || wait_pid as usize == pid;
+        if matched {
+            cids.push(cid);
+        }
+        !matched
+    });
+    drop(parent_inner);
+    for cid in cids {
+        let _ = push_trap_record(parent.getpid(), UserTrapRecord { cause: 1, message: cid });
+    }
+}
+
 lazy_static! {
     pub static ref INITPROC: Arc<ProcessControlBlock> =
         ProcessControlBlock::new(get_app_data_by_name("initproc").unwrap());
@@ -211,6 +211,9 @@ impl TaskUserRes {
     pub fn ustack_base(&self) -> usize {
         self.ustack_base
     }
+    pub fn ustack_bottom(&self) -> usize {
+        ustack_bottom_from_tid(self.ustack_base, self.tid)
+    }
     pub fn ustack_top(&self) -> usize {
         ustack_bottom_from_tid(self.ustack_base, self.tid) + USER_STACK_SIZE
     }
@@ -1,18 +1,21 @@
 use lib_so::get_symbol_addr;
 use spin::{Mutex, MutexGuard};
-use crate::mm::{KERNEL_SPACE, MemorySet, PhysAddr, PhysPageNum, translate_writable_va, VirtAddr};
-use crate::task::{add_task, pid_alloc, PidHandle, TaskControlBlock};
+use crate::mm::{KERNEL_SPACE, MemorySet, PhysAddr, PhysPageNum, translate_writable_va, VirtAddr, write_user, write_user_bytes};
+use crate::task::{add_task, current_task, pid_alloc, suspend_current_and_run_next, PidHandle, TaskControlBlock, TaskStatus};
 use super::add_user_intr_task;
-use super::pid::RecycleAllocator;
+use super::pid::{RecycleAllocator, TaskUserRes};
+use super::signal::SignalState;
+use alloc::collections::VecDeque;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
+use alloc::string::String;
 use alloc::vec::Vec;
-use crate::config::{PAGE_SIZE, USER_TRAP_BUFFER};
-use crate::fs::{File, Stdin, Stdout};
-use crate::syscall::sys_gettid;
+use crate::config::{USER_STACK_SIZE, USER_TRAP_BUFFER};
+use crate::fs::{File, MailBox, Stdin, Stdout};
+use crate::syscall::{sys_gettid, Errno};
 use crate::task::pool::insert_into_pid2process;
 use crate::trap::{trap_handler, TrapContext, UserTrapInfo, UserTrapQueue, UserTrapRecord, UserTrapError};
-use crate::sync::{SimpleMutex, Condvar};
+use crate::sync::{SimpleMutex, Condvar, Semaphore};
 
 pub struct ProcessControlBlock {
     // immutable
@@ -37,6 +40,15 @@ pub struct ProcessControlBlockInner {
     pub user_trap_info_cache: Vec<UserTrapRecord>,
     pub mutex_list: Vec<Option<Arc<dyn SimpleMutex>>>,
     pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub mail_box: MailBox,
+    pub signals: SignalState,
+    // threads blocked in waitpid, woken whenever a child exits
+    pub wait_queue: VecDeque<Arc<TaskControlBlock>>,
+    // (pid or -1, cid) of the coroutines waiting for a child asynchronously
+    pub async_waiters: Vec<(isize, usize)>,
+    // threads blocked in waittid, woken whenever a sibling thread exits
+    pub thread_wait_queue: VecDeque<Arc<TaskControlBlock>>,
 }
 
 impl ProcessControlBlockInner {
@@ -58,6 +70,14 @@ impl ProcessControlBlockInner {
         self.is_zombie
     }
 
+    pub fn is_mailbox_full(&self) -> bool {
+        self.mail_box.is_full()
+    }
+
+    pub fn is_mailbox_empty(&self) -> bool {
+        self.mail_box.is_empty()
+    }
+
     pub fn alloc_tid(&mut self) -> usize {
         self.task_res_allocator.alloc()
     }
@@ -74,8 +94,8 @@ impl ProcessControlBlockInner {
         self.tasks[tid].as_ref().unwrap().clone()
     }
 
-    pub fn mmap(&mut self, start: usize, len: usize, port: usize) -> Result<isize, isize> {
-        self.memory_set.mmap(start, len, port)
+    pub fn mmap(&mut self, start: usize, len: usize, port: usize, flags: usize) -> Result<usize, isize> {
+        self.memory_set.mmap(start, len, port, flags)
     }
 
     pub fn munmap(&mut self, start: usize, len: usize) -> Result<isize, isize> {
@@ -89,8 +109,8 @@ impl ProcessControlBlockInner {
     pub fn init_user_trap(&mut self) -> Result<isize, isize> {
         use riscv::register::sstatus;
         if self.user_trap_info.is_none() {
-            // R | W
-            if self.mmap(USER_TRAP_BUFFER, PAGE_SIZE, 0b11).is_ok() {
+            // the user can not mmap it, it is above the mmap region
+            if self.memory_set.map_user_trap_buffer() {
                 let phys_addr =
                     translate_writable_va(self.get_user_token(), USER_TRAP_BUFFER).unwrap();
                 self.user_trap_info = Some(UserTrapInfo {
@@ -105,12 +125,13 @@ impl ProcessControlBlockInner {
                 self.is_sstatus_uie = true;
                 return Ok(USER_TRAP_BUFFER as isize);
             } else {
-                warn!("[init user trap] mmap failed!");
+                warn!("[init user trap] no frame left!");
+                return Err(Errno::ENOMEM.into());
             }
         } else {
             warn!("[init user trap] self user trap info is not None!");
         }
-        Err(-1)
+        Err(Errno::EEXIST.into())
     }
 
     pub fn restore_user_trap_info(&mut self) {
@@ -157,6 +178,41 @@ impl ProcessControlBlock {
     }
 
     pub fn new(elf_data: &[u8]) -> Arc<Self> {
+        let process = Self::from_elf(
+            elf_data,
+            None,
+            vec![
+                // 0 -> stdin
+                Some(Arc::new(Stdin)),
+                // 1 -> stdout
+                Some(Arc::new(Stdout)),
+                // 2 -> stderr
+                Some(Arc::new(Stdout)),
+            ],
+            &[String::from("initproc")],
+        );
+        // add main thread to scheduler
+        add_task(process.acquire_inner_lock().get_task(0));
+        process
+    }
+
+    /// Create a child process running `elf_data` without copying the memory of current process,
+    /// the child inherits the fd table and gets `path` as its only argument.
+    /// The main thread of the child is not added to the scheduler.
+    pub fn spawn(self: &Arc<Self>, elf_data: &[u8], path: String) -> Arc<Self> {
+        let mut parent = self.acquire_inner_lock();
+        let fd_table = parent.fd_table.clone();
+        let child = Self::from_elf(elf_data, Some(Arc::downgrade(self)), fd_table, &[path]);
+        parent.children.push(Arc::clone(&child));
+        child
+    }
+
+    fn from_elf(
+        elf_data: &[u8],
+        parent: Option<Weak<Self>>,
+        fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+        args: &[String],
+    ) -> Arc<Self> {
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (memory_set, ustack_base, _entry_point) = MemorySet::from_elf(elf_data);
         // allocate a pid
@@ -169,17 +225,10 @@ impl ProcessControlBlock {
                     is_sstatus_uie: false,
                     memory_set,
                     user_trap_info: None,
-                    parent: None,
+                    parent,
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
+                    fd_table,
                     tasks: Vec::new(),
                     task_res_allocator: RecycleAllocator::new(),
                     user_trap_handler_tid: 0,
@@ -187,6 +236,12 @@ impl ProcessControlBlock {
                     user_trap_info_cache: Vec::new(),
                     mutex_list: Vec::new(),
                     condvar_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    mail_box: MailBox::new(),
+                    signals: SignalState::new(),
+                    wait_queue: VecDeque::new(),
+                    async_waiters: Vec::new(),
+                    thread_wait_queue: VecDeque::new(),
                 }
             )
         });
@@ -202,64 +257,129 @@ impl ProcessControlBlock {
         let ustack_top = task_inner.res.as_ref().unwrap().ustack_top();
         let kstack_top = task.kstack.get_top();
         drop(task_inner);
-        *trap_cx = TrapContext::app_init_context(
-            // entry_point,
-            // lib_so::user_entry(),
-            get_symbol_addr(&crate::lkm::SHARED_ELF, "user_entry"),
-            ustack_top,
-            KERNEL_SPACE.lock().token(),
-            kstack_top,
-            trap_handler as usize,
-        );
+        let token = process.acquire_inner_lock().memory_set.token();
+        *trap_cx = main_init_context(token, ustack_top, kstack_top, args, &[]);
         // add main thread to the process
         let mut process_inner = process.acquire_inner_lock();
         process_inner.tasks.push(Some(Arc::clone(&task)));
         drop(process_inner);
         insert_into_pid2process(process.getpid(), Arc::clone(&process));
-        // add main thread to scheduler
-        add_task(task);
         process
     }
 
-    /// Only support processes with a single thread.
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8]) {
-        assert_eq!(self.acquire_inner_lock().thread_count(), 1);
+    /// The other threads, including the user trap handler thread, are torn down and the
+    /// calling thread becomes the main thread of the new program.
+    /// `args` and `envs` are copied to the top of the new user stack, the main thread
+    /// starts with argc in a0, argv in a1 and envp in a2.
+    /// Return -E2BIG before anything is torn down if they do not fit in the user stack.
+    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>, envs: Vec<String>) -> Result<(), isize> {
+        if str_array_size(&args) + str_array_size(&envs) > USER_STACK_SIZE {
+            return Err(Errno::E2BIG.into());
+        }
+        let task = current_task().unwrap();
+        self.kill_other_threads(&task);
         // memory_set with elf program headers/trampoline/trap context/user stack
         let (memory_set, ustack_base, entry_point) = MemorySet::from_elf(elf_data);
         debug!("entry_point: {}", entry_point);
+        // release the ustack and trap_cx of current thread while the old memory_set is in place
+        let old_res = task.acquire_inner_lock().res.take();
+        drop(old_res);
         // substitute memory_set
         let mut process_inner = self.acquire_inner_lock();
+        if let Some(trap_info) = &process_inner.user_trap_info {
+            trap_info.remove_user_ext_int_map();
+        }
         process_inner.memory_set = memory_set;
         process_inner.user_trap_info = None;
+        process_inner.is_sstatus_uie = false;
+        process_inner.user_trap_handler_tid = 0;
+        process_inner.user_trap_info_cache.clear();
+        process_inner.tasks = vec![Some(Arc::clone(&task))];
+        process_inner.task_res_allocator = RecycleAllocator::new();
+        process_inner.signals.exec();
+        process_inner.wait_queue.clear();
+        process_inner.async_waiters.clear();
+        process_inner.thread_wait_queue.clear();
+        // the ids of the old program mean nothing to the new one, nor do its mails
+        process_inner.mutex_list.clear();
+        process_inner.condvar_list.clear();
+        process_inner.semaphore_list.clear();
+        process_inner.mail_box.close();
+        process_inner.mail_box = MailBox::new();
         drop(process_inner);
         // then we alloc user resource for main thread again
         // since memory_set has been changed
-        let task = self.acquire_inner_lock().get_task(0);
+        let res = TaskUserRes::new(Arc::clone(self), ustack_base, true);
+        let trap_cx_ppn = res.trap_cx_ppn();
+        let ustack_top = res.ustack_top();
         let mut task_inner = task.acquire_inner_lock();
-        task_inner.res.as_mut().unwrap().ustack_base = ustack_base;
-        task_inner.res.as_mut().unwrap().alloc_user_res();
-        task_inner.trap_cx_ppn = task_inner.res.as_mut().unwrap().trap_cx_ppn();
-        let mut user_sp = task_inner.res.as_mut().unwrap().ustack_top();
+        task_inner.res = Some(res);
+        task_inner.trap_cx_ppn = trap_cx_ppn;
+        let token = self.acquire_inner_lock().memory_set.token();
         // initialize trap_cx
-        let mut trap_cx = TrapContext::app_init_context(
-            // lib_so::user_entry(),
-            get_symbol_addr(&crate::lkm::SHARED_ELF, "user_entry"),
-            user_sp,
-            KERNEL_SPACE.lock().token(),
-            task.kstack.get_top(),
-            trap_handler as usize,
-        );
-        // trap_cx.x[10] = args.len();
-        // trap_cx.x[11] = argv_base;
-        *task_inner.get_trap_cx() = trap_cx;
+        *task_inner.get_trap_cx() = main_init_context(token, ustack_top, task.kstack.get_top(), &args, &envs);
+        Ok(())
+    }
+
+    /// Stop every thread but `current` and release their ustacks and trap_cxs.
+    /// The stopped threads are dropped by the scheduler instead of running again.
+    pub fn kill_other_threads(self: &Arc<Self>, current: &Arc<TaskControlBlock>) {
+        self.stop_threads(Some(current));
+    }
+
+    /// Stop every thread but `except`, see `kill_other_threads`.
+    pub fn stop_threads(self: &Arc<Self>, except: Option<&Arc<TaskControlBlock>>) {
+        let others: Vec<Arc<TaskControlBlock>> = self
+            .acquire_inner_lock()
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|task| except.map_or(true, |current| !Arc::ptr_eq(task, current)))
+            .cloned()
+            .collect();
+        // a thread running on another hart still uses the memory_set, wait until it traps
+        loop {
+            let mut running = false;
+            for task in others.iter() {
+                let mut task_inner = task.acquire_inner_lock();
+                task_inner.killed = true;
+                running |= matches!(task_inner.task_status, TaskStatus::Running(_));
+            }
+            if !running {
+                break;
+            }
+            suspend_current_and_run_next();
+        }
+        self.acquire_inner_lock().user_trap_handler_task = None;
+        for task in others {
+            let res = task.acquire_inner_lock().res.take();
+            drop(res);
+        }
     }
 
-    /// Only support processes with a single thread.
+    /// Only the calling thread is duplicated, it becomes the main thread of the child
+    /// and keeps running on its own ustack with a copy of its trap_cx.
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        let current = current_task().unwrap();
         let mut parent = self.acquire_inner_lock();
-        assert_eq!(parent.thread_count(), 1);
-        // clone parent's memory_set completely including trampoline/ustacks/trap_cxs
-        let memory_set = MemorySet::from_existed_user(&parent.memory_set);
+        // share parent's user pages copy-on-write, the trap_cxs are copied
+        let mut memory_set = MemorySet::from_existed_user(&mut parent.memory_set);
+        let current_inner = current.acquire_inner_lock();
+        let current_res = current_inner.res.as_ref().unwrap();
+        let (current_tid, current_ustack_bottom) = (current_res.tid, current_res.ustack_bottom());
+        let current_trap_cx_ppn = current_inner.trap_cx_ppn;
+        drop(current_inner);
+        // the other threads are not duplicated, the trap_cx of tid 0 is kept for the child
+        for task in parent.tasks.iter().flatten() {
+            if let Some(res) = task.acquire_inner_lock().res.as_ref() {
+                if res.tid != current_tid {
+                    memory_set.remove_area_with_start_vpn(VirtAddr::from(res.ustack_bottom()).into());
+                }
+                if res.tid != 0 {
+                    memory_set.remove_area_with_start_vpn(VirtAddr::from(res.trap_cx_user_va()).into());
+                }
+            }
+        }
         // alloc a pid
         let pid = pid_alloc();
         // copy fd table
@@ -301,21 +421,22 @@ impl ProcessControlBlock {
                     user_trap_info_cache: Vec::new(),
                     mutex_list: Vec::new(),
                     condvar_list: Vec::new(),
+                    semaphore_list: Vec::new(),
+                    mail_box: MailBox::new(),
+                    signals: parent.signals.fork(),
+                    wait_queue: VecDeque::new(),
+                    async_waiters: Vec::new(),
+                    thread_wait_queue: VecDeque::new(),
                 }
             )
         });
         // add child
         parent.children.push(Arc::clone(&child));
-        // create main thread of child process
+        // create main thread of child process, tid 0 of the child has its ustack
+        // where the ustack of the calling thread is
         let task = Arc::new(TaskControlBlock::new(
             Arc::clone(&child),
-            parent
-                .get_task(0)
-                .acquire_inner_lock()
-                .res
-                .as_ref()
-                .unwrap()
-                .ustack_base(),
+            current_ustack_bottom,
             // here we do not allocate trap_cx or ustack again
             // but mention that we allocate a new kstack here
             false,
@@ -325,8 +446,12 @@ impl ProcessControlBlock {
         let mut child_inner = child.acquire_inner_lock();
         child_inner.tasks.push(Some(Arc::clone(&task)));
         drop(child_inner);
-        // modify kstack_top in trap_cx of this thread
+        // copy trap_cx of the calling thread and modify kstack_top in it
         let task_inner = task.acquire_inner_lock();
+        task_inner
+            .trap_cx_ppn
+            .get_bytes_array()
+            .copy_from_slice(current_trap_cx_ppn.get_bytes_array());
         let trap_cx = task_inner.get_trap_cx();
         trap_cx.kernel_sp = task.kstack.get_top();
         drop(task_inner);
@@ -337,4 +462,55 @@ impl ProcessControlBlock {
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
-}
\ No newline at end of file
+}
+/// The trap context of a main thread entering the user program with `args` and `envs`
+/// copied to the top of its user stack, argc in a0, argv in a1 and envp in a2.
+fn main_init_context(token: usize, ustack_top: usize, kstack_top: usize, args: &[String], envs: &[String]) -> TrapContext {
+    let mut user_sp = ustack_top;
+    let envp_base = push_str_array(token, &mut user_sp, envs);
+    let argv_base = push_str_array(token, &mut user_sp, args);
+    // make the user stack aligned to 16 bytes
+    user_sp -= user_sp % 16;
+    let mut trap_cx = TrapContext::app_init_context(
+        // lib_so::user_entry(),
+        get_symbol_addr(&crate::lkm::SHARED_ELF, "user_entry"),
+        user_sp,
+        KERNEL_SPACE.lock().token(),
+        kstack_top,
+        trap_handler as usize,
+    );
+    trap_cx.x[10] = args.len();
+    trap_cx.x[11] = argv_base;
+    trap_cx.x[12] = envp_base;
+    trap_cx
+}
+
+/// The most bytes `push_str_array` may take from the user stack for `strings`,
+/// including the padding of the array and of the final 16 bytes alignment.
+fn str_array_size(strings: &[String]) -> usize {
+    let bytes: usize = strings.iter().map(|string| string.len() + 1).sum();
+    bytes + (strings.len() + 1) * core::mem::size_of::<usize>() + core::mem::size_of::<usize>() + 16
+}
+
+/// Push a null-terminated array of C strings to the user stack at `user_sp`,
+/// return the user address of the array.
+fn push_str_array(token: usize, user_sp: &mut usize, strings: &[String]) -> usize {
+    // the array below the strings pushed before must be aligned
+    *user_sp -= *user_sp % core::mem::size_of::<usize>();
+    *user_sp -= (strings.len() + 1) * core::mem::size_of::<usize>();
+    let array_base = *user_sp;
+    // exec has checked that everything fits in the new user stack
+    let mut array = Vec::with_capacity(strings.len() + 1);
+    for string in strings {
+        *user_sp -= string.len() + 1;
+        write_user_bytes(token, *user_sp as *mut u8, string.as_bytes()).unwrap();
+        write_user(token, (*user_sp + string.len()) as *mut u8, &0u8).unwrap();
+        array.push(*user_sp);
+    }
+    array.push(0);
+    for (i, string_base) in array.iter().enumerate() {
+        let entry = array_base + i * core::mem::size_of::<usize>();
+        write_user(token, entry as *mut usize, string_base).unwrap();
+    }
+    array_base
+}
@@ -4,6 +4,7 @@ use super::__switch2;
 use super::add_task;
 use super::{fetch_task, TaskStatus};
 use crate::config::CPU_NUM;
+use crate::mm::{MapPermission, VirtAddr};
 use crate::trace::SCHEDULE;
 use crate::trace::{push_trace, RUN_NEXT, SUSPEND_CURRENT};
 use crate::trap::TrapContext;
@@ -75,6 +76,15 @@ impl Processor {
             unsafe { &*idle_task_cx_ptr }
         );
         // acquire
+        let mut task_inner = task.acquire_inner_lock();
+        // checked under the same lock that marks it running, see `ProcessControlBlock::exec`
+        if task_inner.killed {
+            return;
+        }
+        let next_task_cx_ptr = task_inner.get_task_cx_ptr();
+        task_inner.task_status = TaskStatus::Running(hart_id());
+        drop(task_inner);
+
         let process = task.process.upgrade().unwrap();
         let process_inner = process.acquire_inner_lock();
         if process_inner.is_user_trap_enabled() {
@@ -85,8 +95,6 @@ impl Processor {
         drop(process);
 
         let mut task_inner = task.acquire_inner_lock();
-        let next_task_cx_ptr = task_inner.get_task_cx_ptr();
-        task_inner.task_status = TaskStatus::Running(hart_id());
 
         let task_cx = unsafe { &*next_task_cx_ptr };
         trace!(
@@ -119,10 +127,13 @@ impl Processor {
             let mut task_inner = task.acquire_inner_lock();
             task_inner.task_status = TaskStatus::Ready;
             task_inner.total_cpu_cycle_count += cycle::read() - task_inner.last_cpu_cycle;
+            let killed = task_inner.killed;
             drop(task_inner);
             // ---- release current PCB lock
             // push back to ready queue.
-            add_task(task);
+            if !killed {
+                add_task(task);
+            }
         }
     }
 
@@ -265,15 +276,27 @@ pub fn set_current_priority(priority: isize) -> Result<isize, isize> {
     }
 }
 
-pub fn mmap(start: usize, len: usize, port: usize) -> Result<isize, isize> {
+pub fn mmap(start: usize, len: usize, port: usize, flags: usize) -> Result<usize, isize> {
     if let Some(current) = current_process() {
         let mut current = current.acquire_inner_lock();
-        current.mmap(start, len, port)
+        current.mmap(start, len, port, flags)
     } else {
         Err(-1)
     }
 }
 
+/// Resolve a page fault of the current process at `va`, see `MemorySet::handle_page_fault`.
+/// Return false if the access is not allowed or `token` is not the current address space.
+pub fn handle_page_fault(token: usize, va: usize, access: MapPermission) -> bool {
+    if let Some(current) = current_process() {
+        let mut current = current.acquire_inner_lock();
+        current.memory_set.token() == token
+            && current.memory_set.handle_page_fault(VirtAddr::from(va).floor(), access)
+    } else {
+        false
+    }
+}
+
 pub fn munmap(start: usize, len: usize) -> Result<isize, isize> {
     if let Some(current) = current_process() {
         let mut current = current.acquire_inner_lock();
@@ -282,3 +305,12 @@ pub fn munmap(start: usize, len: usize) -> Result<isize, isize> {
         Err(-1)
     }
 }
+
+pub fn mprotect(start: usize, len: usize, port: usize) -> Result<isize, isize> {
+    if let Some(current) = current_process() {
+        let mut current = current.acquire_inner_lock();
+        current.memory_set.mprotect(start, len, port)
+    } else {
+        Err(-1)
+    }
+}
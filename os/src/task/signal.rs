@@ -0,0 +1,220 @@
+use super::add_user_intr_task;
+use super::process::ProcessControlBlockInner;
+use super::processor::{current_task, current_trap_cx, current_user_token};
+use crate::mm::translated_byte_buffer;
+use crate::syscall::{SignalAction, SignalFlags, MAX_SIG, SIGKILL, SIGSEGV, SIG_DFL, SIG_IGN};
+use crate::trap::UserTrapRecord;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+/// The cause of the user trap records carrying a signal number.
+pub const SIGNAL_TRAP_CAUSE: usize = 2;
+
+/// Signals of a process, shared by all of its threads.
+#[derive(Clone)]
+pub struct SignalState {
+    pub pending: SignalFlags,
+    pub mask: SignalFlags,
+    pub actions: [SignalAction; MAX_SIG + 1],
+}
+
+impl SignalState {
+    pub fn new() -> Self {
+        Self {
+            pending: SignalFlags::empty(),
+            mask: SignalFlags::empty(),
+            actions: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+
+    /// The child inherits the actions and the mask but none of the pending signals.
+    pub fn fork(&self) -> Self {
+        Self {
+            pending: SignalFlags::empty(),
+            ..self.clone()
+        }
+    }
+
+    /// The handlers are gone with the old program, ignored signals stay ignored.
+    pub fn exec(&mut self) {
+        for action in self.actions.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+
+    /// SIGKILL can't be blocked.
+    pub fn set_mask(&mut self, mask: SignalFlags) -> SignalFlags {
+        core::mem::replace(&mut self.mask, mask - SignalFlags::SIGKILL)
+    }
+
+    fn deliverable(&self) -> impl Iterator<Item = usize> + '_ {
+        let deliverable = self.pending - self.mask;
+        (1..=MAX_SIG).filter(move |signum| deliverable.bits() & (1 << signum) != 0)
+    }
+
+    /// SIGKILL, or a signal whose default action terminates the process.
+    fn is_fatal(&self, signum: usize) -> bool {
+        signum == SIGKILL || (self.actions[signum].handler == SIG_DFL && default_terminates(signum))
+    }
+
+    /// Take the lowest fatal signal that is pending and not blocked.
+    fn take_fatal(&mut self) -> Option<usize> {
+        let signum = self.deliverable().find(|signum| self.is_fatal(*signum))?;
+        self.pending.remove(signal_flag(signum));
+        Some(signum)
+    }
+
+    /// Take the lowest pending signal that is not blocked.
+    fn take_deliverable(&mut self) -> Option<(usize, SignalAction)> {
+        let signum = self.deliverable().next()?;
+        self.pending.remove(signal_flag(signum));
+        Some((signum, self.actions[signum]))
+    }
+}
+
+/// Empty for the signal numbers without a name, which are not supported.
+pub fn signal_flag(signum: usize) -> SignalFlags {
+    if signum > MAX_SIG {
+        return SignalFlags::empty();
+    }
+    SignalFlags::from_bits_truncate(1 << signum)
+}
+
+/// SIGCHLD, SIGURG and SIGWINCH are ignored by default, the others terminate the process.
+fn default_terminates(signum: usize) -> bool {
+    !(SignalFlags::SIGCHLD | SignalFlags::SIGURG | SignalFlags::SIGWINCH).contains(signal_flag(signum))
+}
+
+/// The exit code of a process terminated by a signal, as reported by shells.
+fn signal_exit_code(signum: usize) -> i32 {
+    128 + signum as i32
+}
+
+/// Hand the deliverable signals with a handler to the user trap handler thread if the
+/// process has called init_user_trap, default actions are left to the main thread.
+pub fn queue_signals_to_user_trap(pid: usize, inner: &mut ProcessControlBlockInner) {
+    if inner.user_trap_info.is_none() {
+        return;
+    }
+    let signals = &inner.signals;
+    let queued: Vec<usize> = signals
+        .deliverable()
+        .filter(|signum| *signum != SIGKILL && !matches!(signals.actions[*signum].handler, SIG_DFL | SIG_IGN))
+        .collect();
+    for signum in queued {
+        inner.signals.pending.remove(signal_flag(signum));
+        add_user_intr_task(pid);
+        let _ = inner.push_user_trap_record(UserTrapRecord {
+            cause: SIGNAL_TRAP_CAUSE,
+            message: signum,
+        });
+    }
+}
+
+/// The exit code of the process if a fatal signal is deliverable, which is taken.
+/// The threads of another process may be blocked and never act on it themselves.
+pub fn take_fatal_signal(inner: &mut ProcessControlBlockInner) -> Option<i32> {
+    inner.signals.take_fatal().map(signal_exit_code)
+}
+
+/// What the kernel saves on the user stack before running a handler, restored by sigreturn.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    x: [usize; 32],
+    sepc: usize,
+    mask: usize,
+}
+
+fn frame_bytes(token: usize, frame_va: usize, write: bool) -> Option<Vec<&'static mut [u8]>> {
+    translated_byte_buffer(token, frame_va as *const u8, size_of::<SignalFrame>(), write).ok()
+}
+
+/// Act on the pending signals before the main thread returns to user space, the other
+/// threads only act on the fatal ones and leave the handlers to it.
+/// Return the exit code if one of them terminates the process.
+pub fn handle_signals() -> Option<i32> {
+    let task = current_task().unwrap();
+    let is_main = task.acquire_inner_lock().res.as_ref().unwrap().tid == 0;
+    let process = task.process.upgrade().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    queue_signals_to_user_trap(process.getpid(), &mut inner);
+    if !is_main {
+        return take_fatal_signal(&mut inner);
+    }
+    loop {
+        let (signum, action) = inner.signals.take_deliverable()?;
+        match action.handler {
+            _ if signum == SIGKILL => return Some(signal_exit_code(signum)),
+            SIG_DFL if default_terminates(signum) => return Some(signal_exit_code(signum)),
+            SIG_DFL | SIG_IGN => {}
+            handler => {
+                // the signal being handled is blocked until sigreturn
+                let old_mask = inner.signals.mask;
+                inner.signals.set_mask(old_mask | action.mask | signal_flag(signum));
+                // writing the frame may fault in the stack, which takes the process lock
+                drop(inner);
+                return push_signal_frame(signum, handler, action.restorer, old_mask)
+                    .err()
+                    .map(|_| signal_exit_code(SIGSEGV));
+            }
+        }
+    }
+}
+
+/// Save the context below the user sp and enter `handler` with signum in a0, it returns
+/// to `restorer`.
+fn push_signal_frame(signum: usize, handler: usize, restorer: usize, old_mask: SignalFlags) -> Result<(), ()> {
+    let trap_cx = current_trap_cx();
+    let frame = SignalFrame {
+        x: trap_cx.x,
+        sepc: trap_cx.sepc,
+        mask: old_mask.bits() as usize,
+    };
+    let frame_va = (trap_cx.x[2] - size_of::<SignalFrame>()) & !0xf;
+    let buffers = frame_bytes(current_user_token(), frame_va, true).ok_or(())?;
+    let src = unsafe {
+        core::slice::from_raw_parts(&frame as *const SignalFrame as *const u8, size_of::<SignalFrame>())
+    };
+    let mut copied = 0;
+    for buffer in buffers {
+        let len = buffer.len();
+        buffer.copy_from_slice(&src[copied..copied + len]);
+        copied += len;
+    }
+    trap_cx.x[1] = restorer;
+    trap_cx.x[2] = frame_va;
+    trap_cx.x[10] = signum;
+    trap_cx.sepc = handler;
+    Ok(())
+}
+
+/// Restore the context saved by `push_signal_frame`, the frame is at the user sp.
+/// Return a0 of the restored context, None if the frame is not readable.
+pub fn pop_signal_frame() -> Option<usize> {
+    let trap_cx = current_trap_cx();
+    let buffers = frame_bytes(current_user_token(), trap_cx.x[2], false)?;
+    let mut frame = SignalFrame {
+        x: [0; 32],
+        sepc: 0,
+        mask: 0,
+    };
+    let dst = unsafe {
+        core::slice::from_raw_parts_mut(&mut frame as *mut SignalFrame as *mut u8, size_of::<SignalFrame>())
+    };
+    let mut copied = 0;
+    for buffer in buffers {
+        let len = buffer.len();
+        dst[copied..copied + len].copy_from_slice(buffer);
+        copied += len;
+    }
+    trap_cx.x = frame.x;
+    trap_cx.sepc = frame.sepc;
+    let process = current_task().unwrap().process.upgrade().unwrap();
+    let mut inner = process.acquire_inner_lock();
+    inner.signals.set_mask(SignalFlags::from_bits_truncate(frame.mask as u32));
+    queue_signals_to_user_trap(process.getpid(), &mut inner);
+    Some(trap_cx.x[10])
+}
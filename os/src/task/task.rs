@@ -1,6 +1,6 @@
 use super::TaskContext;
 use super::{pid_alloc, KernelStack, PidHandle};
-use crate::fs::{File, MailBox, Serial, Socket, Stdin, Stdout};
+use crate::fs::{File, Serial, Stdin, Stdout};
 use crate::mm::{translate_writable_va, MemorySet, PhysAddr, PhysPageNum, VirtAddr, KERNEL_SPACE};
 use crate::task::pid::{kstack_alloc, RecycleAllocator, TaskUserRes};
 use crate::trap::{trap_handler, TrapContext, UserTrapInfo, UserTrapQueue};
@@ -31,9 +31,12 @@ pub struct TaskControlBlockInner {
     pub task_cx: TaskContext,
     pub task_cx_ptr: usize,
     pub task_status: TaskStatus,
+    // set when a sibling thread execs, the scheduler drops the task instead of running it
+    pub killed: bool,
+    // a detached thread is reaped as soon as it exits and can't be joined
+    pub detached: bool,
     pub priority: isize,
     pub exit_code: Option<i32>,
-    pub mail_box: Arc<MailBox>,
     pub time_intr_count: usize,
     pub total_cpu_cycle_count: usize,
     pub last_cpu_cycle: usize,
@@ -69,14 +72,6 @@ impl TaskControlBlockInner {
         self.priority = priority;
         Ok(priority)
     }
-
-    pub fn is_mailbox_full(&self) -> bool {
-        self.mail_box.is_full()
-    }
-
-    pub fn is_mailbox_empty(&self) -> bool {
-        self.mail_box.is_empty()
-    }
 }
 
 impl TaskControlBlock {
@@ -117,9 +112,10 @@ impl TaskControlBlock {
                     task_cx: TaskContext::goto_trap_return(kstack_top, tid),
                     task_cx_ptr: 0,
                     task_status: TaskStatus::Ready,
+                    killed: false,
+                    detached: false,
                     priority: 0,
                     exit_code: None,
-                    mail_box: Arc::new(MailBox::new()),
                     time_intr_count: 0,
                     total_cpu_cycle_count: 0,
                     last_cpu_cycle: 0,
@@ -131,11 +127,6 @@ impl TaskControlBlock {
         }
 
     }
-
-    #[allow(unused)]
-    pub fn create_socket(&self) -> Arc<Socket> {
-        self.inner.lock().mail_box.create_socket()
-    }
 }
 
 impl PartialEq for TaskControlBlock {
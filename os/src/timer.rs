@@ -95,3 +95,10 @@ pub fn set_virtual_timer(mut time: usize, pid: usize, cid: usize) {
         }
     }
 }
+
+/// Remove the timers set by coroutine `cid` of process `pid` on every hart.
+pub fn cancel_timers(pid: usize, cid: usize) {
+    for timer_map in TIMER_MAP.iter() {
+        timer_map.lock().retain(|_, task_id| task_id.pid != pid || task_id.coroutine_id != Some(cid));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::MEMORY_END;
+use crate::config::memory_end;
 // S trap
 pub const S_TRAP_VEC_ENTER: usize = 0x57ab_0000;
 pub const S_TRAP_VEC_RESTORE: usize = 0x57ab_1000;
@@ -36,17 +36,10 @@ pub const SEND_IPI_EXIT: usize = 0x5b1c_1000;
 // misc
 pub const TRACE_TEST: usize = 0x315c_0000;
 
-core::arch::global_asm!(include_str!("trace.asm"));
-
-extern "C" {
-    fn __push_trace(event_id: usize) -> usize;
-}
-
 pub fn push_trace(event_id: usize) -> usize {
     let mut cycle: usize = 0;
     #[cfg(feature = "board_lrv")]
     unsafe {
-        // __push_trace(event_id)
         core::arch::asm!(
             "
         amoadd.d {tail}, {step}, ({mem_end}) # t2 <- queue_tail, queue_tail <- queue_tail + 16
@@ -59,7 +52,7 @@ pub fn push_trace(event_id: usize) -> usize {
         sd {cy}, 1*8({tail})",
         eid = in(reg) event_id,
         step = in(reg) 16,
-        mem_end = in(reg) MEMORY_END,
+        mem_end = in(reg) memory_end(),
         cy = out(reg) cycle,
         tail = out(reg) _,
         eid_ext = out(reg) _,
@@ -69,10 +62,11 @@ pub fn push_trace(event_id: usize) -> usize {
 }
 
 pub fn init() {
+    let memory_end = memory_end();
     unsafe {
-        (MEMORY_END as *mut usize).write_volatile(MEMORY_END + 16);
-        ((MEMORY_END + 8) as *mut u32).write_volatile(0xbaad_f00d);
-        ((MEMORY_END + 12) as *mut u32).write_volatile(0xdead_beef);
+        (memory_end as *mut usize).write_volatile(memory_end + 16);
+        ((memory_end + 8) as *mut u32).write_volatile(0xbaad_f00d);
+        ((memory_end + 12) as *mut u32).write_volatile(0xdead_beef);
     }
 }
 
@@ -80,8 +74,8 @@ pub fn init() {
 pub fn trace_test() {
     let c1 = push_trace(TRACE_TEST);
     let c2 = push_trace(TRACE_TEST);
-    let trace_end = unsafe { (MEMORY_END as *mut usize).read_volatile() };
-    let trace_magic = unsafe { ((MEMORY_END + 8) as *mut usize).read_volatile() };
+    let trace_end = unsafe { (memory_end() as *mut usize).read_volatile() };
+    let trace_magic = unsafe { ((memory_end() + 8) as *mut usize).read_volatile() };
     info!("[trace] push_trace() takes {} cycles.", c2 - c1);
     info!(
         "[trace] trace tail addr: {:#x}, trace magic: {:#x}",
@@ -2,10 +2,11 @@ mod context;
 mod usertrap;
 
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
+use crate::mm::MapPermission;
 use crate::{plic, println};
 use crate::sbi::set_timer;
 use crate::syscall::{sys_gettid, syscall};
-use crate::task::{current_process, current_task, current_trap_cx, current_trap_cx_user_va, current_user_token, exit_current_and_run_next, hart_id, suspend_current_and_run_next};
+use crate::task::{current_process, current_task, current_trap_cx, current_trap_cx_user_va, current_user_token, exit_current_and_run_next, exit_process_and_run_next, handle_page_fault, handle_signals, hart_id, suspend_current_and_run_next};
 use crate::timer::{get_time_us, set_next_trigger, TIMER_MAP};
 use crate::trace::{push_trace, S_TRAP_HANDLER, S_TRAP_RETURN};
 use core::arch::{asm, global_asm};
@@ -73,6 +74,13 @@ pub fn trap_handler() -> ! {
                 cx.x[10] = result as usize;
             }
         }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault)
+            if handle_page_fault(current_user_token(), stval, page_fault_access(scause.cause())) =>
+        {
+            // the page is populated or copied now, retry the instruction
+        }
         Trap::Exception(Exception::StoreFault)
         | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::InstructionFault)
@@ -103,13 +111,21 @@ pub fn trap_handler() -> ! {
             // let current_time = time::read();
             let mut timer_map = TIMER_MAP[hart_id()].lock();
             // debug!("test");
-            while let Some((_, task_id)) = timer_map.pop_first() {
+            while let Some((deadline, task_id)) = timer_map.pop_first() {
+                if deadline > riscv::register::time::read() {
+                    // the timer was armed for an entry removed by `cancel_timers`
+                    timer_map.insert(deadline, task_id);
+                    set_timer(deadline);
+                    break;
+                }
                 if let Some((next_time, _)) = timer_map.first_key_value() {
                     set_timer(*next_time);
                 }
                 drop(timer_map);
                 if task_id.pid == 0 {
                     set_next_trigger();
+                    // drive the tcp timers, e.g. retransmission
+                    crate::net::net_poll();
                     suspend_current_and_run_next();
                 } else {
                     if task_id.coroutine_id.is_none() {
@@ -162,9 +178,22 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    // pending signals may terminate the process or redirect it to a handler
+    if let Some(exit_code) = handle_signals() {
+        exit_process_and_run_next(exit_code);
+    }
     trap_return();
 }
 
+/// The permission a page fault asks for.
+fn page_fault_access(cause: Trap) -> MapPermission {
+    match cause {
+        Trap::Exception(Exception::StorePageFault) => MapPermission::W,
+        Trap::Exception(Exception::InstructionPageFault) => MapPermission::X,
+        _ => MapPermission::R,
+    }
+}
+
 #[no_mangle]
 pub fn trap_return() -> ! {
     let task = current_task().unwrap();
@@ -228,5 +257,5 @@ pub extern "C" fn trap_from_kernel(cx: &mut TrapContext) {
 
 pub use context::TrapContext;
 pub use usertrap::{
-    push_trap_record, UserTrapError, UserTrapInfo, UserTrapQueue, UserTrapRecord, USER_EXT_INT_MAP,
+    push_trap_record, wake_user_coroutine, UserTrapError, UserTrapInfo, UserTrapQueue, UserTrapRecord, USER_EXT_INT_MAP,
 };
@@ -52,15 +52,19 @@ impl UserTrapInfo {
     pub fn enable_user_ext_int(&self) {
         push_trace(ENABLE_USER_EXT_INT_ENTER);
 
-        let u_context = get_context(hart_id(), 'U');
-        for (device_id, is_enabled) in &self.devices {
-            for hart_id in 0..CPU_NUM {
-                Plic::disable(get_context(hart_id, 'S'), *device_id);
-            }
-            if *is_enabled {
-                Plic::enable(u_context, *device_id);
-            } else {
-                Plic::disable(u_context, *device_id);
+        // the devices can only be claimed when the board has U contexts
+        if let Some(u_context) = get_context(hart_id(), 'U') {
+            for (device_id, is_enabled) in &self.devices {
+                for hart_id in 0..CPU_NUM {
+                    if let Some(s_context) = get_context(hart_id, 'S') {
+                        Plic::disable(s_context, *device_id);
+                    }
+                }
+                if *is_enabled {
+                    Plic::enable(u_context, *device_id);
+                } else {
+                    Plic::disable(u_context, *device_id);
+                }
             }
         }
         unsafe {
@@ -73,12 +77,14 @@ impl UserTrapInfo {
         push_trace(DISABLE_USER_EXT_INT_ENTER);
 
         let hart_id = hart_id();
-        for (device_id, is_enabled) in &self.devices {
-            Plic::disable(get_context(hart_id, 'U'), *device_id);
-            if *is_enabled {
-                Plic::enable(get_context(hart_id, 'S'), *device_id);
-            } else {
-                Plic::disable(get_context(hart_id, 'S'), *device_id);
+        if let (Some(u_context), Some(s_context)) = (get_context(hart_id, 'U'), get_context(hart_id, 'S')) {
+            for (device_id, is_enabled) in &self.devices {
+                Plic::disable(u_context, *device_id);
+                if *is_enabled {
+                    Plic::enable(s_context, *device_id);
+                } else {
+                    Plic::disable(s_context, *device_id);
+                }
             }
         }
         unsafe {
@@ -90,8 +96,10 @@ impl UserTrapInfo {
     pub fn remove_user_ext_int_map(&self) {
         let mut int_map = USER_EXT_INT_MAP.lock();
         for hart_id in 0..CPU_NUM {
-            let s_context = get_context(hart_id, 'S');
-            let u_context = get_context(hart_id, 'U');
+            let (s_context, u_context) = match (get_context(hart_id, 'S'), get_context(hart_id, 'U')) {
+                (Some(s_context), Some(u_context)) => (s_context, u_context),
+                _ => continue,
+            };
             for (device_id, _) in &self.devices {
                 // Plic::enable(u_context, *device_id);
                 // Plic::claim(u_context);
@@ -99,9 +107,11 @@ impl UserTrapInfo {
                 Plic::disable(u_context, *device_id);
                 Plic::enable(s_context, *device_id);
                 Plic::complete(s_context, *device_id);
-                int_map.remove(device_id);
             }
         }
+        for (device_id, _) in &self.devices {
+            int_map.remove(device_id);
+        }
     }
 
     pub fn get_trap_queue(&self) -> &UserTrapQueue {
@@ -161,3 +171,10 @@ pub fn push_trap_record(pid: usize, trap_record: UserTrapRecord) -> Result<(), U
         Err(UserTrapError::TaskNotFound)
     }
 }
+
+/// Installed as the `user_wake_hook` of the kernel executor. A waker of a user coroutine
+/// woken in kernel context can't reach the executor of that process, so the coroutine is
+/// handed to its `wake_handler`, `prio_idx` is the index in the priority array, i.e. pid + 1.
+pub fn wake_user_coroutine(cid: usize, prio_idx: usize) {
+    let _ = push_trap_record(prio_idx - 1, UserTrapRecord { cause: 1, message: cid });
+}
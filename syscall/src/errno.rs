@@ -0,0 +1,117 @@
+//! 内核与用户态共享的错误码，取值与 Linux 保持一致
+//!
+//! 系统调用失败时返回 `-(errno as isize)`，用户态可以通过 `Errno::from_ret` 还原出错误类型
+
+/// 同时生成错误码的枚举以及从返回值到枚举的转换，两者不会不一致
+macro_rules! errno {
+    ($($(#[$doc: meta])* $name: ident = $value: literal,)+) => {
+        /// 系统调用错误码
+        #[repr(isize)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum Errno {
+            $($(#[$doc])* $name = $value,)+
+        }
+
+        impl Errno {
+            /// 根据系统调用的返回值还原错误码，返回值非负或者不是已知错误码时返回 None
+            pub fn from_ret(ret: isize) -> Option<Self> {
+                match ret.checked_neg()? {
+                    $($value => Some(Errno::$name),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+errno! {
+    /// 操作不被允许
+    EPERM = 1,
+    /// 文件或目录不存在
+    ENOENT = 2,
+    /// 进程不存在
+    ESRCH = 3,
+    /// 被中断的系统调用
+    EINTR = 4,
+    /// I/O 错误
+    EIO = 5,
+    /// 参数列表过长
+    E2BIG = 7,
+    /// 可执行文件格式错误
+    ENOEXEC = 8,
+    /// 无效的文件描述符
+    EBADF = 9,
+    /// 没有子进程
+    ECHILD = 10,
+    /// 资源暂时不可用，需要重试
+    EAGAIN = 11,
+    /// 内存不足
+    ENOMEM = 12,
+    /// 没有访问权限
+    EACCES = 13,
+    /// 无效的地址
+    EFAULT = 14,
+    /// 设备或资源忙
+    EBUSY = 16,
+    /// 文件已存在
+    EEXIST = 17,
+    /// 设备不存在
+    ENODEV = 19,
+    /// 不是目录
+    ENOTDIR = 20,
+    /// 是目录
+    EISDIR = 21,
+    /// 无效的参数
+    EINVAL = 22,
+    /// 打开的文件过多
+    EMFILE = 24,
+    /// 文件过大
+    EFBIG = 27,
+    /// 设备上没有剩余空间
+    ENOSPC = 28,
+    /// 非法的 seek 操作
+    ESPIPE = 29,
+    /// 管道另一端已关闭
+    EPIPE = 32,
+    /// 会导致死锁
+    EDEADLK = 35,
+    /// 文件名过长
+    ENAMETOOLONG = 36,
+    /// 系统调用未实现
+    ENOSYS = 38,
+    /// 目录非空
+    ENOTEMPTY = 39,
+    /// 不是 socket
+    ENOTSOCK = 88,
+    /// 消息过长
+    EMSGSIZE = 90,
+    /// 地址已被占用
+    EADDRINUSE = 98,
+    /// 无法分配请求的地址
+    EADDRNOTAVAIL = 99,
+    /// 网络不可用
+    ENETDOWN = 100,
+    /// 网络不可达
+    ENETUNREACH = 101,
+    /// 连接被对端重置
+    ECONNRESET = 104,
+    /// socket 未连接
+    ENOTCONN = 107,
+    /// 连接超时
+    ETIMEDOUT = 110,
+    /// 连接被拒绝
+    ECONNREFUSED = 111,
+    /// 操作已经在进行中
+    EALREADY = 114,
+    /// 操作正在进行中
+    EINPROGRESS = 115,
+    /// 操作被取消
+    ECANCELED = 125,
+}
+
+impl From<Errno> for isize {
+    /// 转换为系统调用的返回值
+    fn from(errno: Errno) -> Self {
+        -(errno as isize)
+    }
+}
@@ -3,25 +3,33 @@
 #![no_std]
 
 mod async_help;
+mod errno;
 mod user_interface;
 
 extern crate syscall_macro;
 
 use syscall_macro::{GenSysMacro, GenSysTrait};
 pub use async_help::AsyncCall;
+pub use errno::Errno;
 pub use user_interface::*;
 
 #[repr(usize)]
 #[derive(Debug, GenSysMacro, GenSysTrait)]
 pub enum SyscallId{
+    #[arguments(args = "old_fd, new_fd")]
+    Dup2 = 23,
     #[arguments(args = "fd")]
     Dup = 24,
+    #[arguments(args = "path_ptr")]
+    Unlink = 35,
     #[arguments(args = "path_ptr, flag_bits")]
     Open = 56,
     #[arguments(args = "fd")]
     Close = 57,
     #[arguments(args = "pipe_ptr")]
     Pipe = 59,
+    #[arguments(args = "fd, offset, whence")]
+    Lseek = 62,
     #[arguments(args = "fd, buffer_ptr, buffer_len, key, cid")]
 	Read = 63,
     #[arguments(args = "fd, buffer_ptr, buffer_len, key, cid")]
@@ -29,17 +37,30 @@ pub enum SyscallId{
     #[arguments(args = "exit_code")]
     Exit = 93,
     Yield = 124,
+    #[arguments(args = "pid, signum")]
+    Kill = 129,
+    #[arguments(args = "signum, action_ptr, old_action_ptr")]
+    SigAction = 134,
+    #[arguments(args = "mask")]
+    SigProcMask = 135,
+    SigReturn = 139,
     #[arguments(args = "time_ptr, tz")]
     GetTime = 169,
     GetPid = 172,
     Fork = 220,
-    #[arguments(args = "path_ptr, args_ptr")]
+    #[arguments(args = "path_ptr, args_ptr, envs_ptr")]
     Exec = 221,
-    #[arguments(args = "pid, exit_code_ptr")]
+    #[arguments(args = "start, len")]
+    Munmap = 215,
+    #[arguments(args = "start, len, prot, flags")]
+    Mmap = 222,
+    #[arguments(args = "start, len, prot")]
+    Mprotect = 226,
+    #[arguments(args = "pid, exit_code_ptr, key, cid")]
     WaitPid = 260,
     #[arguments(args = "path_ptr")]
     Spawn = 400,
-    #[arguments(args = "buffer_ptr, buffer_len")]
+    #[arguments(args = "buffer_ptr, buffer_len, key, cid")]
     MailRead = 401,
     #[arguments(args = "pid, buffer_ptr, buffer_len")]
     MailWrite = 402,
@@ -54,18 +75,30 @@ pub enum SyscallId{
     ClaimExtInt = 603,
     #[arguments(args = "device_id, enable")]
     SetExtIntEnable = 604,
+    #[arguments(args = "cid")]
+    CancelAsync = 605,
+    #[arguments(args = "cid")]
+    CancelTimer = 606,
     #[arguments(args = "entry, arg")]
     ThreadCreate = 1000,
     GetTid = 1001,
-    #[arguments(args = "tid")]
+    #[arguments(args = "tid, exit_code_ptr")]
     WaitTid = 1002,
     Hang = 1003,
+    #[arguments(args = "tid")]
+    ThreadDetach = 1004,
     #[arguments(args = "blocking")]
     MutexCreate = 1010,
     #[arguments(args = "id")]
     MutexLock = 1011,
     #[arguments(args = "id")]
     MutexUnlock = 1012,
+    #[arguments(args = "res_count")]
+    SemaphoreCreate = 1020,
+    #[arguments(args = "sem_id")]
+    SemaphoreUp = 1021,
+    #[arguments(args = "sem_id")]
+    SemaphoreDown = 1022,
     #[arguments(args = "arg")]
     CondvarCreate = 1030,
     #[arguments(args = "condvar_id")]
@@ -78,4 +111,18 @@ pub enum SyscallId{
     Listen = 1200,
     #[arguments(args = "fd")]
     Accept = 1201,
+    #[arguments(args = "sock_type")]
+    Socket = 1202,
+    #[arguments(args = "fd, port")]
+    Bind = 1203,
+    #[arguments(args = "fd, buffer_ptr, buffer_len, addr_ptr")]
+    SendTo = 1204,
+    #[arguments(args = "fd, buffer_ptr, buffer_len, addr_ptr, key, cid")]
+    RecvFrom = 1205,
+    #[arguments(args = "ip, port, key, cid")]
+    Connect = 1206,
+    #[arguments(args = "config_ptr")]
+    GetIfConfig = 1207,
+    #[arguments(args = "config_ptr")]
+    SetIfConfig = 1208,
 }
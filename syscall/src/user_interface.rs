@@ -2,10 +2,16 @@ use syscall_macro::async_fn;
 use bitflags::bitflags;
 use crate::*;
 
+/// 复制 fd，返回最小的空闲 fd
 pub fn dup(fd: usize) -> isize {
     sys_dup(fd)
 }
 
+/// 让 new_fd 指向 old_fd 打开的文件，new_fd 原来打开的文件被关闭，返回 new_fd
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    sys_dup2(old_fd, new_fd)
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
@@ -16,10 +22,29 @@ bitflags! {
     }
 }
 
+/// 打开文件，path 需要以 `\0` 结尾。`/dev/` 下是设备文件：stdin、stdout、stderr、null
+/// 以及串口 ttyS0 ~ ttyS3，其余路径为根目录下的普通文件
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path.as_ptr() as usize, flags.bits as usize)
 }
 
+/// 删除文件，path 需要以 `\0` 结尾
+pub fn unlink(path: &str) -> isize {
+    sys_unlink(path.as_ptr() as usize)
+}
+
+/// `lseek` 的 whence：从文件开头计算偏移
+pub const SEEK_SET: usize = 0;
+/// `lseek` 的 whence：从当前位置计算偏移
+pub const SEEK_CUR: usize = 1;
+/// `lseek` 的 whence：从文件末尾计算偏移
+pub const SEEK_END: usize = 2;
+
+/// 移动文件的读写位置，返回新的位置
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset as usize, whence)
+}
+
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
@@ -38,6 +63,12 @@ pub fn write(fd: usize, buffer: &[u8], key: usize, cid: usize) -> isize {
     sys_write(fd, buffer.as_ptr() as usize, buffer.len(), key, cid)
 }
 
+/// TCP 发送，异步版本在数据被对端确认之后唤醒协程 cid，pid 为当前进程的 pid
+#[async_fn(true)]
+pub fn send(fd: usize, buffer: &[u8], pid: usize, cid: usize) -> isize {
+    sys_write(fd, buffer.as_ptr() as usize, buffer.len(), cid, pid)
+}
+
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code as usize);
     panic!("sys_exit never returns!");
@@ -85,36 +116,50 @@ pub fn fork() -> isize {
     sys_fork()
 }
 
+/// args 为以空指针结尾的参数数组，新程序的环境变量为空
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path.as_ptr() as usize, args.as_ptr() as usize)
+    sys_exec(path.as_ptr() as usize, args.as_ptr() as usize, 0)
+}
+
+/// args、envs 均为以空指针结尾的数组，环境变量的格式为 `KEY=VALUE`
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_exec(path.as_ptr() as usize, args.as_ptr() as usize, envs.as_ptr() as usize)
 }
 
 pub fn spawn(path: &str) -> isize {
     sys_spawn(path.as_ptr() as usize)
 }
 
+/// 等待任意一个子进程退出，返回子进程的 pid，子进程退出之前当前线程被阻塞
 pub fn wait(exit_code: *mut i32) -> isize {
-    loop {
-        match sys_wait_pid(usize::MAX, exit_code as usize) {
-            -2 => {
-                sys_yield();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
-        }
-    }
+    sys_wait_pid(usize::MAX, exit_code as usize, usize::MAX, usize::MAX)
 }
 
+/// 等待子进程 pid 退出，子进程退出之前当前线程被阻塞
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_wait_pid(pid, exit_code as *mut _ as usize) {
-            -2 => {
-                sys_yield();
+    sys_wait_pid(pid, exit_code as *mut _ as usize, usize::MAX, usize::MAX)
+}
+
+/// 等待子进程 pid（usize::MAX 表示任意子进程）退出。异步版本只挂起协程 cid，子进程
+/// 退出时内核唤醒协程，协程被唤醒后再回收子进程，返回子进程的 pid；没有子进程时返回 ECHILD
+#[macro_export]
+macro_rules! waitpid {
+    ($pid: expr, $exit_code: expr) => {
+        syscall::waitpid($pid, $exit_code)
+    };
+    ($pid: expr, $exit_code: expr, $key: expr, $cid: expr) => {
+        {
+            let exit_code: &mut i32 = $exit_code;
+            loop {
+                // 子进程还在运行时内核返回 0，并在子进程退出时唤醒协程
+                let ret = $crate::sys_wait_pid($pid, &mut *exit_code as *mut i32 as usize, $key, $cid);
+                if ret != 0 {
+                    break ret;
+                }
+                $crate::AsyncCall::new().await;
             }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
         }
-    }
+    };
 }
 
 pub fn sleep(period_ms: usize) {
@@ -124,8 +169,134 @@ pub fn sleep(period_ms: usize) {
     }
 }
 
-pub fn mailread(buf: &mut [u8]) -> isize {
-    sys_mail_read(buf.as_mut_ptr() as usize, buf.len())
+/// `mmap`、`mprotect` 的 prot：不可访问
+pub const PROT_NONE: usize = 0;
+/// `mmap`、`mprotect` 的 prot：可读
+pub const PROT_READ: usize = 1 << 0;
+/// `mmap`、`mprotect` 的 prot：可写
+pub const PROT_WRITE: usize = 1 << 1;
+/// `mmap`、`mprotect` 的 prot：可执行
+pub const PROT_EXEC: usize = 1 << 2;
+/// `mmap` 的 flags：必须映射在 start 处，替换该范围内原有的映射
+pub const MAP_FIXED: usize = 0x10;
+/// `mmap` 的 flags：立即分配物理页，而不是在第一次访问时才分配
+pub const MAP_POPULATE: usize = 0x8000;
+
+/// 映射 len 字节的匿名内存，物理页在第一次访问时才分配，返回映射的起始地址。
+/// 没有 MAP_FIXED 时 start 只是提示，为 0 或与已有映射重叠时由内核选择地址
+pub fn mmap(start: usize, len: usize, prot: usize, flags: usize) -> isize {
+    sys_mmap(start, len, prot, flags)
+}
+
+/// 解除 `[start, start + len)` 内的映射，可以只解除一个映射的一部分
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+
+/// 修改 `[start, start + len)` 的访问权限，该范围必须已经全部映射
+pub fn mprotect(start: usize, len: usize, prot: usize) -> isize {
+    sys_mprotect(start, len, prot)
+}
+
+bitflags! {
+    /// 信号集合，第 n 位表示第 n 号信号
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGCHLD = 1 << 17;
+        const SIGURG = 1 << 23;
+        const SIGWINCH = 1 << 28;
+    }
+}
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGURG: usize = 23;
+pub const SIGWINCH: usize = 28;
+/// 最大的信号编号
+pub const MAX_SIG: usize = 31;
+
+/// 信号的默认处理方式：SIGCHLD、SIGURG、SIGWINCH 被忽略，其余信号终止进程
+pub const SIG_DFL: usize = 0;
+/// 忽略信号
+pub const SIG_IGN: usize = 1;
+
+/// 信号的处理方式
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    /// SIG_DFL、SIG_IGN 或者 `extern "C" fn(signum: usize)` 的地址
+    pub handler: usize,
+    /// 处理函数运行期间额外屏蔽的信号，正在处理的信号总是被屏蔽
+    pub mask: SignalFlags,
+    /// 处理函数返回到这个地址，它需要在不改变 sp 的情况下调用 sigreturn
+    pub restorer: usize,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            restorer: 0,
+        }
+    }
+}
+
+/// 向进程 pid 发送信号，signum 为 0 时只检查进程是否存在
+pub fn kill(pid: usize, signum: usize) -> isize {
+    sys_kill(pid, signum)
+}
+
+/// 设置信号的处理方式，原来的处理方式写入 old_action，SIGKILL 的处理方式不能修改
+pub fn sigaction(signum: usize, action: Option<&SignalAction>, old_action: Option<&mut SignalAction>) -> isize {
+    sys_sig_action(
+        signum,
+        action.map_or(0, |action| action as *const SignalAction as usize),
+        old_action.map_or(0, |old_action| old_action as *mut SignalAction as usize),
+    )
+}
+
+/// 设置屏蔽的信号集合，返回原来的集合，SIGKILL 不能被屏蔽
+pub fn sigprocmask(mask: SignalFlags) -> isize {
+    sys_sig_proc_mask(mask.bits() as usize)
+}
+
+/// 从信号处理函数返回，恢复内核构造的信号帧中保存的上下文，只能由 restorer 调用
+pub fn sigreturn() -> isize {
+    sys_sig_return()
+}
+
+#[async_fn(true)]
+pub fn mailread(buffer: &mut [u8], key: usize, cid: usize) -> isize {
+    sys_mail_read(buffer.as_mut_ptr() as usize, buffer.len(), key, cid)
 }
 
 pub fn mailwrite(pid: usize, buf: &[u8]) -> isize {
@@ -148,6 +319,16 @@ pub fn set_timer(time_us: isize, cid: usize) -> isize {
     sys_set_timer(time_us as usize, cid)
 }
 
+/// 取消内核中为协程 cid 注册的异步操作、定时器以及异步等待子进程，之后内核不会再唤醒这个协程
+pub fn cancel_async(cid: usize) -> isize {
+    sys_cancel_async(cid)
+}
+
+/// 只撤销协程 cid 设置的定时器，协程的其他异步操作不受影响
+pub fn cancel_timer(cid: usize) -> isize {
+    sys_cancel_timer(cid)
+}
+
 #[macro_export]
 macro_rules! set_timer {
     ($a: expr) => {
@@ -174,41 +355,28 @@ pub fn gettid() -> isize {
     sys_get_tid()
 }
 
+/// 阻塞等待线程退出并回收，返回线程的退出码，出错时返回负的错误码
 pub fn waittid(tid: usize) -> isize {
-    loop {
-        match sys_wait_tid(tid) {
-            -2 => {
-                sys_yield();
-            }
-            exit_code => return exit_code,
-        }
+    let mut exit_code: i32 = 0;
+    match thread_join(tid, &mut exit_code) {
+        0 => exit_code as isize,
+        err => err,
     }
-    
 }
 
-pub fn hang() {
-    sys_hang();
+/// 阻塞等待线程退出并回收，退出码写入 exit_code，成功返回 0
+pub fn thread_join(tid: usize, exit_code: &mut i32) -> isize {
+    sys_wait_tid(tid, exit_code as *mut _ as usize)
 }
 
+/// 分离线程，线程退出后立即被回收，不能再被等待
+pub fn thread_detach(tid: usize) -> isize {
+    sys_thread_detach(tid)
+}
 
-// pub fn sys_semaphore_create(res_count: usize) -> isize {
-//     unsafe {
-//         syscall1(SYSCALL_SEMAPHORE_CREATE, res_count)
-//     }
-    
-// }
-
-// pub fn sys_semaphore_up(sem_id: usize) -> isize {
-//     unsafe {
-//         syscall1(SYSCALL_SEMAPHORE_UP, sem_id)
-//     }
-// }
-
-// pub fn sys_semaphore_down(sem_id: usize) -> isize {
-//     unsafe {
-//         syscall1(SYSCALL_SEMAPHORE_DOWN, sem_id)
-//     }
-// }
+pub fn hang() {
+    sys_hang();
+}
 
 
 pub fn mutex_create() -> isize {
@@ -227,6 +395,21 @@ pub fn mutex_unlock(mutex_id: usize) {
     sys_mutex_unlock(mutex_id);
 }
 
+/// 创建初始值为 res_count 的信号量，返回信号量 id
+pub fn semaphore_create(res_count: usize) -> isize {
+    sys_semaphore_create(res_count)
+}
+
+/// V 操作，唤醒一个等待的线程
+pub fn semaphore_up(sem_id: usize) {
+    sys_semaphore_up(sem_id);
+}
+
+/// P 操作，资源不足时阻塞当前线程
+pub fn semaphore_down(sem_id: usize) {
+    sys_semaphore_down(sem_id);
+}
+
 pub fn condvar_create() -> isize {
     sys_condvar_create(0)
 }
@@ -249,4 +432,87 @@ pub fn listen(port: usize) -> isize {
 
 pub fn accept(fd: usize) -> isize {
     sys_accept(fd)
+}
+
+/// 面向连接的字节流 socket
+pub const SOCK_STREAM: usize = 1;
+/// 数据报 socket
+pub const SOCK_DGRAM: usize = 2;
+
+/// IPv4 socket 地址
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SockAddr {
+    pub ip: [u8; 4],
+    pub port: u16,
+}
+
+impl SockAddr {
+    pub fn new(ip: [u8; 4], port: u16) -> Self {
+        Self { ip, port }
+    }
+}
+
+/// 网卡配置，地址均为网络字节序
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IfConfig {
+    pub mac: [u8; 6],
+    pub ip: [u8; 4],
+    pub netmask: [u8; 4],
+    /// 全 0 表示没有默认网关
+    pub gateway: [u8; 4],
+}
+
+/// 读取当前的网卡配置，没有网卡时返回 ENETDOWN
+pub fn get_ifconfig(config: &mut IfConfig) -> isize {
+    sys_get_if_config(config as *mut IfConfig as usize)
+}
+
+/// 修改网卡配置，已有的连接不会迁移到新地址
+pub fn set_ifconfig(config: &IfConfig) -> isize {
+    sys_set_if_config(config as *const IfConfig as usize)
+}
+
+/// 建立 TCP 连接，成功时返回 fd；异步版本立即返回 fd，握手结束之后唤醒协程 cid，
+/// 握手失败时之后对这个 fd 的 read/write 返回 ECONNREFUSED
+pub fn connect(addr: &SockAddr, key: usize, cid: usize) -> isize {
+    sys_connect(u32::from_be_bytes(addr.ip) as usize, addr.port as usize, key, cid)
+}
+
+/// 与 async_fn 生成的宏不同，异步版本返回 fd，调用出错时内核不会唤醒协程，所以不等待
+#[macro_export]
+macro_rules! connect {
+    ($addr: expr) => {
+        syscall::connect($addr, usize::MAX, usize::MAX)
+    };
+    ($addr: expr, $key: expr, $cid: expr) => {
+        {
+            let ret = syscall::connect($addr, $key, $cid);
+            if ret >= 0 {
+                $crate::AsyncCall::new().await;
+            }
+            ret
+        }
+    };
+}
+
+/// 创建 socket，目前只支持 SOCK_DGRAM。没有网卡时 socket、listen、connect 都返回 ENETDOWN
+pub fn socket(sock_type: usize) -> isize {
+    sys_socket(sock_type)
+}
+
+/// 绑定本地端口，port 为 0 时由内核分配
+pub fn bind(fd: usize, port: u16) -> isize {
+    sys_bind(fd, port as usize)
+}
+
+pub fn sendto(fd: usize, buffer: &[u8], addr: &SockAddr) -> isize {
+    sys_send_to(fd, buffer.as_ptr() as usize, buffer.len(), addr as *const SockAddr as usize)
+}
+
+/// 接收一个数据报，发送方的地址写入 addr
+#[async_fn(true)]
+pub fn recvfrom(fd: usize, buffer: &mut [u8], addr: &mut SockAddr, key: usize, cid: usize) -> isize {
+    sys_recv_from(fd, buffer.as_mut_ptr() as usize, buffer.len(), addr as *mut SockAddr as usize, key, cid)
 }
\ No newline at end of file
@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+use user_lib::*;
+
+static READ_END: AtomicUsize = AtomicUsize::new(0);
+static STUCK: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+// 管道中没有数据，读操作不会完成
+async fn stuck_read(key: usize) {
+    let mut buffer = [0u8; 16];
+    read!(READ_END.load(Ordering::SeqCst), &mut buffer, key, current_cid());
+    unreachable!();
+}
+
+async fn quick() -> usize {
+    42
+}
+
+async fn cancel_stuck() {
+    let handle = STUCK.lock().take().unwrap();
+    // 此时 stuck_read 已经阻塞，取消之后内核中的读操作也被撤销
+    assert!(cancel(handle.cid()));
+    assert_eq!(handle.await, Err(JoinError::Cancelled));
+    assert!(!cancel(usize::MAX / 4));
+    println!("cancel passed!");
+}
+
+async fn timeouts() {
+    assert_eq!(timeout(1000, quick()).await, Ok(42));
+    let start = get_time();
+    assert_eq!(timeout(50, stuck_read(2)).await, Err(Elapsed));
+    assert!(get_time() - start >= 50);
+    println!("timeout passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    init_user_trap();
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    READ_END.store(fds[0], Ordering::SeqCst);
+    // 优先级高的先执行，stuck_read 阻塞之后才会执行 cancel_stuck
+    *STUCK.lock() = Some(spawn(move || stuck_read(1), 0));
+    spawn(move || cancel_stuck(), 1);
+    spawn(move || timeouts(), 2);
+    0
+}
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+const FILE_NAME: &str = "async_file_data\0";
+pub const TASK_NUM: usize = 16;             // 并发读文件的线程/协程数
+pub const CHUNK_SIZE: usize = 4096;         // 每个线程/协程读取的字节数
+
+static START: AtomicUsize = AtomicUsize::new(0);
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let init_res = init_user_trap();
+    println!("[async file] trap init result: {:#x}, pid: {}", init_res, getpid());
+
+    // 准备测试文件，每个块填充不同的字节
+    let fd = open(FILE_NAME, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd >= 0, "open failed: {}", fd);
+    for i in 0..TASK_NUM {
+        let chunk = [i as u8; CHUNK_SIZE];
+        write(fd as usize, &chunk, usize::MAX, usize::MAX);
+    }
+    close(fd as usize);
+
+    // 线程版本：每个线程同步读取自己的块
+    let start = get_time_us();
+    let tids: Vec<isize> = (0..TASK_NUM)
+        .map(|i| thread_create(thread_read as usize, i))
+        .collect();
+    for tid in tids {
+        assert_eq!(waittid(tid as usize), 0);
+    }
+    println!("[async file] {} threads read {} bytes in {} us", TASK_NUM, TASK_NUM * CHUNK_SIZE, get_time_us() - start);
+
+    // 协程版本：每个协程发起异步读，磁盘中断到来后被唤醒
+    START.store(get_time_us() as usize, Ordering::SeqCst);
+    for i in 0..TASK_NUM {
+        spawn(move || coroutine_read(i), 0);
+    }
+    0
+}
+
+fn open_chunk(i: usize) -> usize {
+    let fd = open(FILE_NAME, OpenFlags::RDONLY);
+    assert!(fd >= 0, "open failed: {}", fd);
+    assert_eq!(lseek(fd as usize, (i * CHUNK_SIZE) as isize, SEEK_SET), (i * CHUNK_SIZE) as isize);
+    fd as usize
+}
+
+fn check_chunk(i: usize, buffer: &[u8]) {
+    assert!(buffer.iter().all(|byte| *byte == i as u8), "chunk {} corrupted", i);
+}
+
+fn thread_read(i: usize) -> ! {
+    let fd = open_chunk(i);
+    let mut buffer = [0u8; CHUNK_SIZE];
+    assert_eq!(read(fd, &mut buffer, usize::MAX, usize::MAX), CHUNK_SIZE as isize);
+    check_chunk(i, &buffer);
+    close(fd);
+    exit(0)
+}
+
+async fn coroutine_read(i: usize) {
+    let fd = open_chunk(i);
+    let mut buffer = [0u8; CHUNK_SIZE];
+    read!(fd, &mut buffer, 0, current_cid());
+    check_chunk(i, &buffer);
+    close(fd);
+    if FINISHED.fetch_add(1, Ordering::SeqCst) + 1 == TASK_NUM {
+        let start = START.load(Ordering::SeqCst);
+        println!("[async file] {} coroutines read {} bytes in {} us", TASK_NUM, TASK_NUM * CHUNK_SIZE, get_time_us() as usize - start);
+        unlink(FILE_NAME);
+    }
+}
+
+#[no_mangle]
+pub fn wake_handler(cid: usize) {
+    re_back(cid);
+}
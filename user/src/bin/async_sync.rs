@@ -0,0 +1,61 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use lib_so::sync::{mpsc, oneshot, Mutex, Notify, RwLock};
+use user_lib::*;
+
+const PRODUCERS: usize = 3;
+const ITEMS: usize = 5;
+
+static SENT: Mutex<usize> = Mutex::new(0);
+static TOTAL: RwLock<usize> = RwLock::new(0);
+static DONE: Notify = Notify::new();
+
+async fn produce(id: usize, tx: mpsc::Sender<usize>) {
+    for i in 0..ITEMS {
+        // 持有锁时因为通道已满而阻塞，其他生产者阻塞在锁上
+        let mut sent = SENT.lock().await;
+        tx.send(id * ITEMS + i).await.unwrap();
+        *sent += 1;
+    }
+}
+
+async fn consume(mut rx: mpsc::Receiver<usize>, result: oneshot::Sender<usize>) {
+    let mut sum = 0;
+    // 所有生产者结束之后返回 None
+    while let Some(value) = rx.recv().await {
+        sum += value;
+    }
+    result.send(sum).unwrap();
+}
+
+async fn report(result: oneshot::Receiver<usize>) {
+    let sum = result.await.unwrap();
+    let n = PRODUCERS * ITEMS;
+    assert_eq!(sum, n * (n - 1) / 2);
+    assert_eq!(*SENT.lock().await, n);
+    *TOTAL.write().await = sum;
+    DONE.notify_one();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let (tx, rx) = mpsc::channel(2);
+    let (result_tx, result_rx) = oneshot::channel();
+    spawn(move || async move {
+        DONE.notified().await;
+        println!("sum received: {}", *TOTAL.read().await);
+        println!("async_sync passed!");
+    }, 1);
+    spawn(move || report(result_rx), 1);
+    for id in 0..PRODUCERS {
+        let tx = tx.clone();
+        spawn(move || produce(id, tx), 2);
+    }
+    drop(tx);
+    spawn(move || consume(rx, result_tx), 3);
+    0
+}
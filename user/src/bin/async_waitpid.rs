@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+const CHILD_NUM: usize = 8;
+static REAPED: AtomicUsize = AtomicUsize::new(0);
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let init_res = init_user_trap();
+    println!("[async waitpid] trap init result: {:#x}, pid: {}", init_res, getpid());
+
+    // 阻塞版本：父线程在子进程退出之前不会被调度
+    let pid = fork();
+    if pid == 0 {
+        sleep(10);
+        exit(3);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 3);
+
+    // 协程版本：每个协程等待一个子进程，子进程退出时被唤醒
+    for i in 0..CHILD_NUM {
+        let pid = fork();
+        if pid == 0 {
+            sleep(10 * (CHILD_NUM - i));
+            exit(i as i32 + 1);
+        }
+        spawn(move || supervise(pid as usize, i as i32 + 1), 0);
+    }
+    0
+}
+
+async fn supervise(pid: usize, expected: i32) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid!(pid, &mut exit_code, 0, current_cid()), pid as isize);
+    assert_eq!(exit_code, expected);
+    if REAPED.fetch_add(1, Ordering::SeqCst) + 1 == CHILD_NUM {
+        println!("async_waitpid passed!");
+    }
+}
+
+#[no_mangle]
+pub fn wake_handler(cid: usize) {
+    re_back(cid);
+}
@@ -0,0 +1,91 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+use user_lib::*;
+
+/// 只能等待一次的事件，poll 时保存 waker，由 set 调用 wake 唤醒
+struct Event {
+    ready: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Event {
+    const fn new() -> Self {
+        Self { ready: AtomicBool::new(false), waker: Mutex::new(None) }
+    }
+
+    fn has_waiter(&self) -> bool {
+        self.waker.lock().is_some()
+    }
+
+    fn set(&self) {
+        self.ready.store(true, Ordering::SeqCst);
+        if let Some(waker) = self.waker.lock().take() {
+            // 重复唤醒不会让协程被执行两次
+            waker.wake_by_ref();
+            waker.wake();
+        }
+    }
+
+    fn wait(&'static self) -> EventFuture {
+        EventFuture(self)
+    }
+}
+
+struct EventFuture(&'static Event);
+
+impl Future for EventFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        *self.0.waker.lock() = Some(cx.waker().clone());
+        // 保存 waker 之后再检查，避免错过唤醒
+        if self.0.ready.load(Ordering::SeqCst) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+static SAME_THREAD: Event = Event::new();
+static OTHER_THREAD: Event = Event::new();
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+async fn waiter(event: &'static Event) {
+    event.wait().await;
+    if FINISHED.fetch_add(1, Ordering::SeqCst) + 1 == 2 {
+        println!("async_waker passed!");
+    }
+}
+
+async fn notifier() {
+    SAME_THREAD.set();
+}
+
+pub fn notify_thread() -> ! {
+    while !OTHER_THREAD.has_waiter() {
+        yield_();
+    }
+    OTHER_THREAD.set();
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 被同一个线程上的另一个协程唤醒
+    spawn(move || waiter(&SAME_THREAD), 1);
+    spawn(move || notifier(), 2);
+    // 被同一进程的另一个线程唤醒
+    spawn(move || waiter(&OTHER_THREAD), 1);
+    thread_create(notify_thread as usize, 0);
+    0
+}
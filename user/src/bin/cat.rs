@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::*;
+
+const BUFFER_SIZE: usize = 512;
+
+/// 把 fd 的内容全部写到标准输出
+fn copy_to_stdout(fd: usize) -> Result<(), isize> {
+    let mut buffer = [0u8; BUFFER_SIZE];
+    loop {
+        match read(fd, &mut buffer, usize::MAX, usize::MAX) {
+            0 => return Ok(()),
+            // 串口暂时没有数据
+            len if Errno::from_ret(len) == Some(Errno::EIO) => {
+                yield_();
+            }
+            len if len < 0 => return Err(len),
+            len => {
+                write(1, &buffer[..len as usize], usize::MAX, usize::MAX);
+            }
+        }
+    }
+}
+
+/// 依次输出参数中的文件，没有参数时输出标准输入
+#[no_mangle]
+pub fn main() -> i32 {
+    if args().len() <= 1 {
+        return match copy_to_stdout(0) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        };
+    }
+    for name in &args()[1..] {
+        let fd = open(format!("{}\0", name).as_str(), OpenFlags::RDONLY);
+        if fd < 0 {
+            println!("cat: can't open {}", name);
+            return -1;
+        }
+        let result = copy_to_stdout(fd as usize);
+        close(fd as usize);
+        if result.is_err() {
+            println!("cat: can't read {}", name);
+            return -1;
+        }
+    }
+    0
+}
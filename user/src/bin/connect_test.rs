@@ -127,8 +127,8 @@ pub fn main() -> i32 {
             for i in 0..MAX_CONNECTION {
                 close(CONNECTIONS[i][1]);
                 close(CONNECTIONS[i][2]);
-                let send_cid = spawn(|| msg_sender(CONNECTIONS[i][3], i + MAX_CONNECTION, pid as usize), 2);
-                let server_cid = spawn(|| msg_server(i, send_cid), 2);
+                let send_cid = spawn(|| msg_sender(CONNECTIONS[i][3], i + MAX_CONNECTION, pid as usize), 2).cid();
+                let server_cid = spawn(|| msg_server(i, send_cid), 2).cid();
 
                 spawn(|| msg_receiver(CONNECTIONS[i][0], i, server_cid), 2);
             }
@@ -136,9 +136,9 @@ pub fn main() -> i32 {
                 close(CONNECTIONS[i][1]);
                 close(CONNECTIONS[i][2]);
                 let send_cid = lib_so::spawn(|| msg_sender(CONNECTIONS[i][3], i + MAX_CONNECTION, pid as usize), 
-                                (i % SERVER_USE_PRIO) + 1, cur_pid + 1, lib_so::CoroutineKind::UserNorm);
+                                (i % SERVER_USE_PRIO) + 1, cur_pid + 1, lib_so::CoroutineKind::UserNorm).cid();
                 let server_cid = lib_so::spawn(|| msg_server(i, send_cid),
-                                (i % SERVER_USE_PRIO) + 1, cur_pid + 1, lib_so::CoroutineKind::UserNorm);
+                                (i % SERVER_USE_PRIO) + 1, cur_pid + 1, lib_so::CoroutineKind::UserNorm).cid();
 
                 lib_so::spawn(|| msg_receiver(CONNECTIONS[i][0], i, server_cid),
                                 (i % SERVER_USE_PRIO) + 1, cur_pid + 1, lib_so::CoroutineKind::UserNorm);
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+const PAGE_SIZE: usize = 0x1000;
+
+fn page(base: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(base as *mut u8, PAGE_SIZE) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let base = mmap(0, 2 * PAGE_SIZE, PROT_READ | PROT_WRITE, 0);
+    assert!(base > 0, "mmap failed: {}", base);
+    let base = base as usize;
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    page(base).fill(b'a');
+
+    // 内核替子进程写入写时复制的页时先复制，父进程看到的内容不变
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(syscall::write!(fds[1], &page(base)[..4]), 4);
+        assert_eq!(syscall::write!(fds[1], b"bbbb"), 4);
+        assert_eq!(read!(fds[0], &mut page(base)[..8]), 8);
+        assert_eq!(&page(base)[..8], b"aaaabbbb");
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert!(page(base).iter().all(|&b| b == b'a'));
+
+    // 内核不会写只读的页
+    let read_only = base + PAGE_SIZE;
+    assert_eq!(mprotect(read_only, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(syscall::write!(fds[1], b"cccc"), 4);
+    assert_eq!(Errno::from_ret(read!(fds[0], &mut page(read_only)[..4])), Some(Errno::EFAULT));
+    assert!(page(read_only).iter().all(|&b| b == 0));
+
+    // 与父进程共享的代码页也不会被内核写入
+    assert_eq!(Errno::from_ret(sys_pipe(main as usize)), Some(Errno::EFAULT));
+
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(munmap(base, 2 * PAGE_SIZE), 0);
+    println!("cow_test passed!");
+    0
+}
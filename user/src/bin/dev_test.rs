@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // /dev/null 读不到数据，写入的数据全部丢弃
+    let fd = open("/dev/null\0", OpenFlags::RDWR);
+    assert!(fd >= 0, "open failed: {}", fd);
+    let fd = fd as usize;
+    let mut buf = [0u8; 8];
+    assert_eq!(syscall::write!(fd, b"discard"), 7);
+    assert_eq!(read!(fd, &mut buf), 0);
+    close(fd);
+
+    // 只读打开的设备不能写，只写打开的设备不能读
+    let fd = open("/dev/null\0", OpenFlags::RDONLY) as usize;
+    assert_eq!(Errno::from_ret(syscall::write!(fd, b"discard")), Some(Errno::EBADF));
+    close(fd);
+    let fd = open("/dev/null\0", OpenFlags::WRONLY) as usize;
+    assert_eq!(Errno::from_ret(read!(fd, &mut buf)), Some(Errno::EBADF));
+    close(fd);
+
+    // 设备不支持的访问方式返回 EACCES
+    assert_eq!(Errno::from_ret(open("/dev/stdin\0", OpenFlags::WRONLY)), Some(Errno::EACCES));
+    assert_eq!(Errno::from_ret(open("/dev/stdout\0", OpenFlags::RDONLY)), Some(Errno::EACCES));
+    assert_eq!(Errno::from_ret(open("/dev/missing\0", OpenFlags::RDONLY)), Some(Errno::ENOENT));
+    println!("dev_test passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use core::ptr::null;
+use user_lib::*;
+
+const PATH: &str = "exec_args_test\0";
+/// 没有映射的用户地址
+const BAD_PTR: usize = 0x10;
+/// 比用户栈还大的参数
+const HUGE_ARG_LEN: usize = 0x4000;
+
+// exec 之后的新程序收到全部参数和环境变量
+fn child() -> i32 {
+    assert_eq!(args(), ["exec_args_test", "child", "arg"]);
+    assert_eq!(env("KEY"), Some("value"));
+    println!("exec_args_test passed!");
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if args().get(1) == Some(&"child") {
+        return child();
+    }
+    // spawn 和 exec 启动的程序都以程序名作为第一个参数
+    assert_eq!(args(), ["exec_args_test"]);
+
+    // 参数指针无效时返回 EFAULT，当前程序继续运行
+    let argv = [BAD_PTR as *const u8, null()];
+    assert_eq!(Errno::from_ret(exec(PATH, &argv)), Some(Errno::EFAULT));
+    assert_eq!(Errno::from_ret(sys_exec(BAD_PTR, 0, 0)), Some(Errno::EFAULT));
+
+    // 放不进新的用户栈的参数返回 E2BIG
+    let mut huge = String::new();
+    (0..HUGE_ARG_LEN).for_each(|_| huge.push('a'));
+    huge.push('\0');
+    let argv = [PATH.as_ptr(), huge.as_ptr(), null()];
+    assert_eq!(Errno::from_ret(exec(PATH, &argv)), Some(Errno::E2BIG));
+
+    let argv = [PATH.as_ptr(), "child\0".as_ptr(), "arg\0".as_ptr(), null()];
+    let envs = ["KEY=value\0".as_ptr(), null()];
+    execve(PATH, &argv, &envs);
+    panic!("exec failed!");
+}
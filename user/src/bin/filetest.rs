@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+const FILE_NAME: &str = "filetest_tmp\0";
+const DATA: &str = "Hello, easy-fs!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open(FILE_NAME, OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY);
+    assert!(fd >= 0, "open for write failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(write(fd, DATA.as_bytes(), usize::MAX, usize::MAX), DATA.len() as isize);
+    close(fd);
+
+    let fd = open(FILE_NAME, OpenFlags::RDONLY);
+    assert!(fd >= 0, "open for read failed: {}", fd);
+    let fd = fd as usize;
+    let mut buffer = [0u8; 32];
+    let len = read(fd, &mut buffer, usize::MAX, usize::MAX);
+    assert_eq!(&buffer[..len as usize], DATA.as_bytes());
+    // 跳过 "Hello, " 再读一次
+    assert_eq!(lseek(fd, 7, SEEK_SET), 7);
+    let len = read(fd, &mut buffer, usize::MAX, usize::MAX);
+    assert_eq!(&buffer[..len as usize], &DATA.as_bytes()[7..]);
+    assert_eq!(lseek(fd, 0, SEEK_END), DATA.len() as isize);
+    close(fd);
+
+    assert_eq!(unlink(FILE_NAME), 0);
+    assert!(open(FILE_NAME, OpenFlags::RDONLY) < 0);
+    println!("filetest passed!");
+    0
+}
@@ -0,0 +1,85 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{args, get_ifconfig, set_ifconfig, IfConfig};
+
+fn print_config(config: &IfConfig) {
+    let [a, b, c, d, e, f] = config.mac;
+    println!("ether {:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", a, b, c, d, e, f);
+    let [a, b, c, d] = config.ip;
+    let [m0, m1, m2, m3] = config.netmask;
+    println!("inet {}.{}.{}.{} netmask {}.{}.{}.{}", a, b, c, d, m0, m1, m2, m3);
+    let [a, b, c, d] = config.gateway;
+    println!("gateway {}.{}.{}.{}", a, b, c, d);
+}
+
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut addr = [0u8; 4];
+    let mut parts = s.split('.');
+    for byte in addr.iter_mut() {
+        *byte = parts.next()?.parse().ok()?;
+    }
+    parts.next().is_none().then_some(addr)
+}
+
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(':');
+    for byte in mac.iter_mut() {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
+
+/// `<ip>[/<prefix>] [<gateway>] [<mac>]`, the fields not given are left unchanged
+fn parse_config(line: &str, config: &mut IfConfig) -> Option<()> {
+    let mut fields = line.split_whitespace();
+    let addr = fields.next()?;
+    let (ip, prefix) = match addr.split_once('/') {
+        Some((ip, prefix)) => (ip, Some(prefix.parse::<u32>().ok().filter(|&len| len <= 32)?)),
+        None => (addr, None),
+    };
+    config.ip = parse_ipv4(ip)?;
+    if let Some(prefix) = prefix {
+        config.netmask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0).to_be_bytes();
+    }
+    if let Some(gateway) = fields.next() {
+        config.gateway = parse_ipv4(gateway)?;
+    }
+    if let Some(mac) = fields.next() {
+        config.mac = parse_mac(mac)?;
+    }
+    Some(())
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut config = IfConfig::default();
+    if get_ifconfig(&mut config) < 0 {
+        println!("ifconfig: failed to get the config");
+        return -1;
+    }
+    // usage: ifconfig [<ip>[/<prefix>] [<gateway>] [<mac>]]
+    if args().len() <= 1 {
+        print_config(&config);
+        return 0;
+    }
+    let line = args()[1..].iter().copied().collect::<Vec<_>>().join(" ");
+    if parse_config(&line, &mut config).is_none() {
+        println!("ifconfig: invalid address {}", line);
+        return -1;
+    }
+    let ret = set_ifconfig(&config);
+    if ret < 0 {
+        println!("ifconfig: failed to set the config: {}", ret);
+        return -1;
+    }
+    print_config(&config);
+    0
+}
@@ -14,7 +14,7 @@ const BS: u8 = 0x08u8;
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, waitpid, OpenFlags};
+use user_lib::{close, dup2, exec, exit, fork, open, pipe, waitpid, OpenFlags};
 
 // #[no_mangle]
 // fn main() -> i32 {
@@ -52,6 +52,142 @@ use user_lib::{close, dup, exec, fork, open, waitpid, OpenFlags};
 //     0
 // }
 
+/// 管道中的一条命令，参数都以 `\0` 结尾
+struct Command {
+    args: Vec<String>,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+impl Command {
+    /// 解析 `cmd arg... [< file] [> file]`，重定向缺少文件名或者没有程序名时返回 None
+    fn parse(segment: &str) -> Option<Self> {
+        let mut command = Command { args: Vec::new(), input: None, output: None };
+        let mut words = segment.split(' ').filter(|word| !word.is_empty());
+        while let Some(word) = words.next() {
+            match word {
+                "<" => command.input = Some(c_string(words.next()?)),
+                ">" => command.output = Some(c_string(words.next()?)),
+                _ => command.args.push(c_string(word)),
+            }
+        }
+        if command.args.is_empty() {
+            None
+        } else {
+            Some(command)
+        }
+    }
+
+    /// 在子进程中完成重定向，然后 exec
+    fn exec(&self) -> ! {
+        if let Some(input) = &self.input {
+            redirect(open(input.as_str(), OpenFlags::RDONLY), 0, input);
+        }
+        if let Some(output) = &self.output {
+            let flags = OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC;
+            redirect(open(output.as_str(), flags), 1, output);
+        }
+        let mut args_addr: Vec<*const u8> = self.args.iter().map(|arg| arg.as_ptr()).collect();
+        args_addr.push(0 as *const u8);
+        exec(self.args[0].as_str(), args_addr.as_slice());
+        println!("Error when executing!");
+        exit(-4);
+    }
+}
+
+fn c_string(word: &str) -> String {
+    let mut string = String::from(word);
+    string.push('\0');
+    string
+}
+
+fn redirect(fd: isize, target: usize, path: &str) {
+    if fd < 0 {
+        println!("Error when opening file {}", path.trim_end_matches('\0'));
+        exit(-4);
+    }
+    dup2(fd as usize, target);
+    close(fd as usize);
+}
+
+/// 执行 `a | b | ...`，只有第一条命令可以重定向输入，只有最后一条命令可以重定向输出
+fn run_pipeline(line: &str) {
+    let commands: Option<Vec<Command>> = line.split('|').map(Command::parse).collect();
+    let commands = match commands {
+        Some(commands) => commands,
+        None => {
+            println!("Syntax error!");
+            return;
+        }
+    };
+    let last = commands.len() - 1;
+    if commands.iter().enumerate().any(|(i, command)| {
+        (i != 0 && command.input.is_some()) || (i != last && command.output.is_some())
+    }) {
+        println!("Only the first command can redirect input and the last one output!");
+        return;
+    }
+    if last == 0 && commands[0].input.is_none() && commands[0].output.is_none() && commands[0].args.len() == 1 {
+        // nothing to set up in the child, no need to copy the shell
+        let pid = syscall::spawn(commands[0].args[0].as_str());
+        if pid < 0 {
+            println!("Error when executing!");
+        } else {
+            wait_child(pid);
+        }
+        return;
+    }
+    // pipes[i] connects commands[i] to commands[i + 1]
+    let mut pipes: Vec<[usize; 2]> = Vec::new();
+    for _ in 0..last {
+        let mut fds = [0usize; 2];
+        if pipe(&mut fds) < 0 {
+            println!("Error when creating pipe!");
+            close_pipes(&pipes);
+            return;
+        }
+        pipes.push(fds);
+    }
+    let mut pids = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            // dup2 before closing, the pipe ends to keep may be any of them
+            if i > 0 {
+                dup2(pipes[i - 1][0], 0);
+            }
+            if i < last {
+                dup2(pipes[i][1], 1);
+            }
+            close_pipes(&pipes);
+            command.exec();
+        } else if pid < 0 {
+            println!("Error when executing!");
+            break;
+        }
+        pids.push(pid);
+    }
+    // the readers see the end of file only when every write end is closed
+    close_pipes(&pipes);
+    for pid in pids {
+        wait_child(pid);
+    }
+}
+
+fn close_pipes(pipes: &[[usize; 2]]) {
+    for fds in pipes {
+        close(fds[0]);
+        close(fds[1]);
+    }
+}
+
+fn wait_child(pid: isize) {
+    let mut exit_code: i32 = 0;
+    let exit_pid = waitpid(pid as usize, &mut exit_code);
+    assert_eq!(pid, exit_pid);
+    println!("Shell: Process {} exited with code {}", pid, exit_code);
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("=============================Rust user shell");
@@ -63,84 +199,7 @@ pub fn main() -> i32 {
             LF | CR => {
                 println!("");
                 if !line.is_empty() {
-                    let args: Vec<_> = line.as_str().split(' ').collect();
-                    let mut args_copy: Vec<String> = args
-                        .iter()
-                        .map(|&arg| {
-                            let mut string = String::new();
-                            string.push_str(arg);
-                            string
-                        })
-                        .collect();
-
-                    args_copy.iter_mut().for_each(|string| {
-                        string.push('\0');
-                    });
-
-                    // redirect input
-                    let mut input = String::new();
-                    if let Some((idx, _)) = args_copy
-                        .iter()
-                        .enumerate()
-                        .find(|(_, arg)| arg.as_str() == "<\0")
-                    {
-                        input = args_copy[idx + 1].clone();
-                        args_copy.drain(idx..=idx + 1);
-                    }
-
-                    // redirect output
-                    let mut output = String::new();
-                    if let Some((idx, _)) = args_copy
-                        .iter()
-                        .enumerate()
-                        .find(|(_, arg)| arg.as_str() == ">\0")
-                    {
-                        output = args_copy[idx + 1].clone();
-                        args_copy.drain(idx..=idx + 1);
-                    }
-
-                    let mut args_addr: Vec<*const u8> =
-                        args_copy.iter().map(|arg| arg.as_ptr()).collect();
-                    args_addr.push(0 as *const u8);
-                    let pid = fork();
-                    if pid == 0 {
-                        // input redirection
-                        if !input.is_empty() {
-                            let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                            if input_fd == -1 {
-                                println!("Error when opening file {}", input);
-                                return -4;
-                            }
-                            let input_fd = input_fd as usize;
-                            close(0);
-                            assert_eq!(dup(input_fd), 0);
-                            close(input_fd);
-                        }
-                        // output redirection
-                        if !output.is_empty() {
-                            let output_fd =
-                                open(output.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY);
-                            if output_fd == -1 {
-                                println!("Error when opening file {}", output);
-                                return -4;
-                            }
-                            let output_fd = output_fd as usize;
-                            close(1);
-                            assert_eq!(dup(output_fd), 1);
-                            close(output_fd);
-                        }
-                        // child process
-                        if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                            println!("Error when executing!");
-                            return -4;
-                        }
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as usize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
-                    }
+                    run_pipeline(line.as_str());
                     line.clear();
                 }
                 print!(">> ");
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use spin::Mutex;
+use lib_so::PRIO_NUM;
+use user_lib::*;
+
+static JOINED: Mutex<Option<JoinHandle<usize>>> = Mutex::new(None);
+
+async fn square(x: usize) -> usize {
+    x * x
+}
+
+async fn sum_of_squares(n: usize) {
+    // 在协程中 await 其他协程的结果
+    let handles: Vec<JoinHandle<usize>> = (1..=n).map(|i| spawn(move || square(i), 1)).collect();
+    let mut sum = 0;
+    for handle in handles {
+        sum += handle.await.unwrap();
+    }
+    assert_eq!(sum, n * (n + 1) * (2 * n + 1) / 6);
+    println!("sum of squares: {}", sum);
+}
+
+pub fn join_thread() -> ! {
+    // 在线程中阻塞等待协程的结果
+    let handle = JOINED.lock().take().unwrap();
+    let cid = handle.cid();
+    assert_eq!(handle.join(), Ok(144));
+    println!("coroutine {} joined from thread", cid);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    spawn(move || sum_of_squares(10), 2);
+    *JOINED.lock() = Some(spawn(move || square(12), 1));
+    let tid = thread_create(join_thread as usize, 0) as usize;
+    // 优先级最低，其他协程都已经结束，阻塞当前线程不影响它们执行
+    spawn(move || async move { assert_eq!(waittid(tid), 0) }, PRIO_NUM - 1);
+    0
+}
@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+const PAGE_SIZE: usize = 0x1000;
+// 预留 512 MiB，只访问其中的少数几页
+const REGION_SIZE: usize = 512 << 20;
+const TOUCHED_PAGES: usize = 16;
+
+fn page_ptr(base: usize, page: usize) -> *mut usize {
+    (base + page * PAGE_SIZE) as *mut usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let base = mmap(0, REGION_SIZE, PROT_READ | PROT_WRITE, 0);
+    assert!(base > 0, "mmap failed: {}", base);
+    let base = base as usize;
+    println!("[lazy mmap] reserved {:#x} bytes at {:#x}", REGION_SIZE, base);
+
+    // 物理页在第一次访问时才分配
+    let stride = REGION_SIZE / PAGE_SIZE / TOUCHED_PAGES;
+    for i in 0..TOUCHED_PAGES {
+        unsafe { *page_ptr(base, i * stride) = i; }
+    }
+    for i in 0..TOUCHED_PAGES {
+        assert_eq!(unsafe { *page_ptr(base, i * stride) }, i);
+    }
+
+    // 范围被占用时 start 只是提示
+    let other = mmap(base, PAGE_SIZE, PROT_READ | PROT_WRITE, 0);
+    assert!(other > 0 && other as usize != base);
+    assert_eq!(munmap(other as usize, PAGE_SIZE), 0);
+
+    // MAP_FIXED 替换原有的映射，新的页是全零的
+    assert_eq!(mmap(base, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_FIXED), base as isize);
+    assert_eq!(unsafe { *page_ptr(base, 0) }, 0);
+
+    // 子进程写写时复制的页，父进程看到的内容不变
+    unsafe { *page_ptr(base, stride) = 42; }
+    let pid = fork();
+    if pid == 0 {
+        unsafe { *page_ptr(base, stride) = 0; }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(unsafe { *page_ptr(base, stride) }, 42);
+
+    // 只读之后再写会被内核杀死
+    assert_eq!(mprotect(base, PAGE_SIZE, PROT_READ), 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { *page_ptr(base, 0) = 1; }
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -2);
+
+    // PROT_NONE 的页不可访问，改回可读写之后内容不变
+    assert_eq!(mprotect(base, PAGE_SIZE, PROT_READ | PROT_WRITE), 0);
+    unsafe { *page_ptr(base, 0) = 7; }
+    assert_eq!(mprotect(base, PAGE_SIZE, PROT_NONE), 0);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { core::ptr::read_volatile(page_ptr(base, 0)); }
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -2);
+    assert_eq!(mprotect(base, PAGE_SIZE, PROT_READ), 0);
+    assert_eq!(unsafe { *page_ptr(base, 0) }, 7);
+    let none = mmap(0, PAGE_SIZE, PROT_NONE, MAP_POPULATE);
+    assert!(none > 0, "mmap failed: {}", none);
+    assert_eq!(munmap(none as usize, PAGE_SIZE), 0);
+
+    // 用户栈和内核共享的页不能被用户改动
+    let stack_page = &exit_code as *const i32 as usize & !(PAGE_SIZE - 1);
+    assert_eq!(Errno::from_ret(munmap(stack_page, PAGE_SIZE)), Some(Errno::EINVAL));
+    assert_eq!(Errno::from_ret(mprotect(stack_page, PAGE_SIZE, PROT_READ)), Some(Errno::EACCES));
+    let ret = mmap(stack_page, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_FIXED);
+    assert_eq!(Errno::from_ret(ret), Some(Errno::EINVAL));
+    let ret = mmap(trap::USER_TRAP_BUFFER, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_FIXED);
+    assert_eq!(Errno::from_ret(ret), Some(Errno::EINVAL));
+
+    assert_eq!(munmap(base, REGION_SIZE), 0);
+    println!("lazy_mmap passed!");
+    0
+}
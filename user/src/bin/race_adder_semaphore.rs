@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{exit, get_time, thread_create, waittid};
+use user_lib::{semaphore_create, semaphore_down, semaphore_up};
+
+const PRODUCER_COUNT: usize = 4;
+const PER_PRODUCER: usize = 1000;
+const BUFFER_SIZE: usize = 8;
+
+const SEM_MUTEX: usize = 0;
+const SEM_EMPTY: usize = 1;
+const SEM_FULL: usize = 2;
+
+static mut BUFFER: [usize; BUFFER_SIZE] = [0; BUFFER_SIZE];
+static mut FRONT: usize = 0;
+static mut TAIL: usize = 0;
+
+unsafe fn producer(id: usize) -> ! {
+    for i in 0..PER_PRODUCER {
+        semaphore_down(SEM_EMPTY);
+        semaphore_down(SEM_MUTEX);
+        BUFFER[TAIL] = id * PER_PRODUCER + i;
+        TAIL = (TAIL + 1) % BUFFER_SIZE;
+        semaphore_up(SEM_MUTEX);
+        semaphore_up(SEM_FULL);
+    }
+    exit(0)
+}
+
+unsafe fn consumer() -> ! {
+    let mut sum = 0usize;
+    for _ in 0..PRODUCER_COUNT * PER_PRODUCER {
+        semaphore_down(SEM_FULL);
+        semaphore_down(SEM_MUTEX);
+        sum += BUFFER[FRONT];
+        FRONT = (FRONT + 1) % BUFFER_SIZE;
+        semaphore_up(SEM_MUTEX);
+        semaphore_up(SEM_EMPTY);
+    }
+    let n = PRODUCER_COUNT * PER_PRODUCER;
+    assert_eq!(sum, n * (n - 1) / 2);
+    exit(0)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = get_time();
+    assert_eq!(semaphore_create(1) as usize, SEM_MUTEX);
+    assert_eq!(semaphore_create(BUFFER_SIZE) as usize, SEM_EMPTY);
+    assert_eq!(semaphore_create(0) as usize, SEM_FULL);
+    let mut v = Vec::new();
+    for id in 0..PRODUCER_COUNT {
+        v.push(thread_create(producer as usize, id) as usize);
+    }
+    v.push(thread_create(consumer as usize, 0) as usize);
+    for tid in v.iter() {
+        assert_eq!(waittid(*tid), 0);
+    }
+    println!("time cost is {}ms", get_time() - start);
+    0
+}
@@ -0,0 +1,77 @@
+#![no_std]
+#![no_main]
+#![feature(inline_const)]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lib_so::config::MAX_PROC_NUM;
+use user_lib::*;
+
+const ROUNDS: usize = 10000;
+/// 测试内核调度时同时就绪的其他进程数
+const READY_PROC_NUM: usize = 32;
+/// 有很多就绪进程时每次让权都要轮转所有进程，减少次数
+const YIELD_ROUNDS: usize = 100;
+
+/// 按照进程编号逐个比较优先级的选择方式，作为对照
+static LINEAR: [AtomicUsize; MAX_PROC_NUM + 1] = [const { AtomicUsize::new(usize::MAX) }; MAX_PROC_NUM + 1];
+
+fn linear_max_prio_pid() -> usize {
+    let mut ret = LINEAR[1].load(Ordering::Relaxed);
+    let mut pid = 1;
+    for (i, prio) in LINEAR.iter().enumerate().take(MAX_PROC_NUM).skip(1) {
+        let prio = prio.load(Ordering::Relaxed);
+        if prio < ret {
+            ret = prio;
+            pid = i;
+        }
+    }
+    pid
+}
+
+/// 执行 rounds 次 f，返回平均每次的时间，单位为 ns
+fn bench(rounds: usize, f: impl Fn() -> usize) -> usize {
+    let start = get_time_us();
+    for _ in 0..rounds {
+        // 避免编译器把没有使用的结果连同计算一起优化掉
+        core::hint::black_box(f());
+    }
+    (get_time_us() - start) as usize * 1000 / rounds
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let idx = getpid() as usize + 1;
+    LINEAR[idx].store(lib_so::PRIO_NUM - 1, Ordering::Relaxed);
+    println!("linear scan:  {} ns per selection", bench(ROUNDS, linear_max_prio_pid));
+    println!("max_prio_pid: {} ns per selection", bench(ROUNDS, lib_so::max_prio_pid));
+    // 每次让权都要经过内核 TaskManager::fetch 选出优先级最高的进程
+    println!("yield:        {} ns per schedule", bench(ROUNDS, || yield_() as usize));
+
+    // 就绪队列中有很多进程时，fetch 需要在队列中查找优先级最高的进程，
+    // 每次让权之后所有就绪的进程各被调度一次
+    let mut children = [0usize; READY_PROC_NUM];
+    for child in children.iter_mut() {
+        let pid = fork();
+        if pid == 0 {
+            loop {
+                yield_();
+            }
+        }
+        *child = pid as usize;
+    }
+    let per_yield = bench(YIELD_ROUNDS, || yield_() as usize);
+    println!(
+        "yield with {} ready processes: {} ns per schedule",
+        READY_PROC_NUM,
+        per_yield / (READY_PROC_NUM + 1)
+    );
+    for &child in children.iter() {
+        kill(child, SIGKILL);
+        let mut exit_code = 0;
+        waitpid(child, &mut exit_code);
+    }
+    0
+}
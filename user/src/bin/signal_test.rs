@@ -0,0 +1,107 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn count_handler(signum: usize) {
+    assert_eq!(signum, SIGUSR1);
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+extern "C" fn queue_handler(signum: usize) {
+    assert_eq!(signum, SIGUSR2);
+    HANDLED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn blocked_reader(fd: usize) -> ! {
+    let mut buf = [0u8; 1];
+    read(fd, &mut buf, usize::MAX, usize::MAX);
+    exit(0)
+}
+
+fn wait_exit_code(pid: isize) -> i32 {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+
+    // 内核构造信号帧，处理函数返回之后 kill 的返回值不变
+    assert_eq!(signal(SIGUSR1, count_handler, SignalFlags::empty()), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+
+    // 被屏蔽的信号在解除屏蔽时才处理
+    sigprocmask(SignalFlags::SIGUSR1);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 1);
+    assert_eq!(sigprocmask(SignalFlags::empty()), SignalFlags::SIGUSR1.bits() as isize);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 2);
+
+    // 忽略的信号，SIGKILL 不能被捕获
+    assert_eq!(signal_ignore(SIGTERM), 0);
+    assert_eq!(kill(pid, SIGTERM), 0);
+    assert!(signal(SIGKILL, count_handler, SignalFlags::empty()) < 0);
+    assert!(kill(pid, 64) < 0);
+
+    // 默认处理方式终止进程，退出码为 128 + signum
+    assert_eq!(signal_default(SIGTERM), 0);
+    let child = fork();
+    if child == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(child as usize, SIGTERM), 0);
+    assert_eq!(wait_exit_code(child), 128 + SIGTERM as i32);
+
+    // 阻塞在系统调用中的进程同样立即终止，包括其他线程
+    let mut idle = [0usize; 2];
+    assert_eq!(pipe(&mut idle), 0);
+    let child = fork();
+    if child == 0 {
+        blocked_reader(idle[0]);
+    }
+    assert_eq!(kill(child as usize, SIGTERM), 0);
+    assert_eq!(wait_exit_code(child), 128 + SIGTERM as i32);
+    let child = fork();
+    if child == 0 {
+        let tid = thread_create(blocked_reader as usize, idle[0]);
+        waittid(tid as usize);
+        exit(0);
+    }
+    assert_eq!(kill(child as usize, SIGKILL), 0);
+    assert_eq!(wait_exit_code(child), 128 + SIGKILL as i32);
+    close(idle[0]);
+    close(idle[1]);
+
+    // 调用过 init_user_trap 的进程在用户态中断处理线程中处理信号
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let child = fork();
+    if child == 0 {
+        HANDLED.store(0, Ordering::SeqCst);
+        init_user_trap();
+        assert_eq!(signal(SIGUSR2, queue_handler, SignalFlags::empty()), 0);
+        write(fds[1], &[1u8], usize::MAX, usize::MAX);
+        while HANDLED.load(Ordering::SeqCst) == 0 {
+            yield_();
+        }
+        exit(7);
+    }
+    let mut ready = [0u8; 1];
+    assert_eq!(read(fds[0], &mut ready, usize::MAX, usize::MAX), 1);
+    assert_eq!(kill(child as usize, SIGUSR2), 0);
+    assert_eq!(wait_exit_code(child), 7);
+
+    println!("signal_test passed!");
+    0
+}
@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+/// 宿主机上没有程序监听的端口，qemu 会用 RST 拒绝连接
+const CLOSED_PORT: u16 = 1;
+
+fn closed_addr() -> SockAddr {
+    let mut config = IfConfig::default();
+    assert_eq!(get_ifconfig(&mut config), 0);
+    SockAddr::new(config.gateway, CLOSED_PORT)
+}
+
+// 异步连接立即返回 fd，握手失败由之后的 read/write 报告
+async fn async_refused() {
+    let fd = connect!(&closed_addr(), 0, current_cid());
+    assert!(fd >= 0, "async connect failed: {}", fd);
+    let ret = syscall::write!(fd as usize, b"ping");
+    assert_eq!(Errno::from_ret(ret), Some(Errno::ECONNREFUSED));
+    close(fd as usize);
+    // 参数错误时不会等待唤醒
+    let ret = connect!(&SockAddr::new([10, 0, 2, 2], 0), 0, current_cid());
+    assert_eq!(Errno::from_ret(ret), Some(Errno::EINVAL));
+    println!("async connect passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    init_user_trap();
+    let ret = connect!(&closed_addr());
+    assert_eq!(Errno::from_ret(ret), Some(Errno::ECONNREFUSED));
+    println!("sync connect passed!");
+    spawn(move || async_refused(), 0);
+    0
+}
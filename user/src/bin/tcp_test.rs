@@ -161,9 +161,10 @@ async fn handle_tcp_client_async(client_fd: usize, matrix_calc_cid: usize) {
     // println!("start tcp_client");
     let str: &str = "connect ok";
     let current_cid = current_cid();
+    let pid = getpid() as usize;
     let mut begin_buf = vec![0u8; BUF_LEN];
     read!(client_fd as usize, &mut begin_buf, 0, current_cid);
-    syscall::write!(client_fd, str.as_bytes());
+    send!(client_fd, str.as_bytes(), pid, current_cid);
     loop {
         let mut buf = vec![0u8; BUF_LEN];
         read!(client_fd as usize, &mut buf, 0, current_cid);
@@ -226,6 +227,8 @@ async fn matrix_calc_async(client_fd: usize, send_rsp_cid: usize) {
 }
 
 async fn send_rsp_async(client_fd: usize) {
+    let current_cid = current_cid();
+    let pid = getpid() as usize;
     loop {
         unsafe {
             mutex_lock(RSP_MAP_MUTEX[client_fd]);
@@ -239,7 +242,7 @@ async fn send_rsp_async(client_fd: usize) {
                     break;
                 }
                 
-                syscall::write!(client_fd, rsp.as_bytes());
+                send!(client_fd, rsp.as_bytes(), pid, current_cid);
             } else {
                 mutex_unlock(RSP_MAP_MUTEX[client_fd]);
                 let mut helper = Box::new(AwaitHelper::new());
@@ -270,9 +273,11 @@ pub fn main() -> i32 {
 
     }
     
-    let tcp_fd = listen(80);
+    // usage: tcp_test [port]
+    let port: usize = arg_or(1, 80);
+    let tcp_fd = listen(port);
     if tcp_fd < 0 {
-        println!("Failed to listen on port 80");
+        println!("Failed to listen on port {}", port);
         return -1;
     }
     init_connection();
@@ -288,8 +293,8 @@ pub fn main() -> i32 {
             wait_tid.push(tid2);
             wait_tid.push(tid3);
         } else {
-            let send_rsp_cid = spawn(move || send_rsp_async(client_fd as usize), 0);
-            let matrix_calc_cid = spawn(move || matrix_calc_async(client_fd as usize, send_rsp_cid), 0);
+            let send_rsp_cid = spawn(move || send_rsp_async(client_fd as usize), 0).cid();
+            let matrix_calc_cid = spawn(move || matrix_calc_async(client_fd as usize, send_rsp_cid), 0).cid();
             spawn(move || handle_tcp_client_async(client_fd as usize, matrix_calc_cid), 0);
         }
     }
@@ -145,16 +145,18 @@ pub fn main() -> i32 {
         init_res, pid
     );
     
-    let tcp_fd = listen(80);
+    // usage: tcp_test_with_prio [port]
+    let port: usize = arg_or(1, 80);
+    let tcp_fd = listen(port);
     if tcp_fd < 0 {
-        println!("Failed to listen on port 80");
+        println!("Failed to listen on port {}", port);
         return -1;
     }
     init_connection();
     for i in 0..CONNECTION_NUM {
         let client_fd = accept(tcp_fd as usize);
-        let send_rsp_cid = spawn(move || send_rsp_async(client_fd as usize), i % SERVER_USE_PRIO);
-        let matrix_calc_cid = spawn(move || matrix_calc_async(client_fd as usize, send_rsp_cid), i % SERVER_USE_PRIO);
+        let send_rsp_cid = spawn(move || send_rsp_async(client_fd as usize), i % SERVER_USE_PRIO).cid();
+        let matrix_calc_cid = spawn(move || matrix_calc_async(client_fd as usize, send_rsp_cid), i % SERVER_USE_PRIO).cid();
         spawn(move || handle_tcp_client_async(client_fd as usize, matrix_calc_cid), i % SERVER_USE_PRIO);
     }
 
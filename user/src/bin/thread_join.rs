@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+fn spin(rounds: usize) {
+    for _ in 0..rounds {
+        yield_();
+    }
+}
+
+pub fn worker(arg: usize) -> ! {
+    // 让各个线程在不同时刻退出
+    spin(arg * 10);
+    FINISHED.fetch_add(1, Ordering::SeqCst);
+    exit(arg as i32)
+}
+
+pub fn negative() -> ! {
+    exit(-3)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // 线程还在运行时 waittid 阻塞，返回时线程已经退出
+    let mut tids = [0usize; 4];
+    for (i, tid) in tids.iter_mut().enumerate() {
+        *tid = thread_create(worker as usize, 4 - i) as usize;
+    }
+    for (i, tid) in tids.iter().enumerate() {
+        let mut exit_code = 0;
+        assert_eq!(thread_join(*tid, &mut exit_code), 0);
+        assert_eq!(exit_code, 4 - i as i32);
+    }
+    assert_eq!(FINISHED.load(Ordering::SeqCst), 4);
+    // 已经回收的线程不能再次等待
+    assert_eq!(Errno::from_ret(thread_join(tids[0], &mut 0)), Some(Errno::ESRCH));
+
+    // 负的退出码也能正确传给等待者
+    let tid = thread_create(negative as usize, 0) as usize;
+    let mut exit_code = 0;
+    assert_eq!(thread_join(tid, &mut exit_code), 0);
+    assert_eq!(exit_code, -3);
+
+    // 分离的线程退出后立即被回收
+    let tid = thread_create(worker as usize, 1) as usize;
+    assert_eq!(thread_detach(tid), 0);
+    assert_eq!(Errno::from_ret(thread_detach(tid)), Some(Errno::EINVAL));
+    assert_eq!(Errno::from_ret(thread_join(tid, &mut 0)), Some(Errno::EINVAL));
+    while Errno::from_ret(thread_join(tid, &mut 0)) == Some(Errno::EINVAL) {
+        yield_();
+    }
+    assert_eq!(FINISHED.load(Ordering::SeqCst), 5);
+
+    assert_eq!(Errno::from_ret(thread_join(gettid() as usize, &mut 0)), Some(Errno::EDEADLK));
+    assert_eq!(Errno::from_ret(thread_detach(0)), Some(Errno::EINVAL));
+    println!("thread_join passed!");
+    0
+}
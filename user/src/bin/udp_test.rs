@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+use user_lib::*;
+
+const PORT: u16 = 6200;
+/// 没有映射的用户地址
+const BAD_PTR: usize = 0x10;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = socket(SOCK_DGRAM);
+    assert!(fd >= 0, "socket failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(bind(fd, PORT), 0);
+    // 已经绑定过的 socket 不能再次绑定
+    assert_eq!(Errno::from_ret(bind(fd, PORT + 1)), Some(Errno::EINVAL));
+
+    // 端口已经被占用
+    let other = socket(SOCK_DGRAM) as usize;
+    assert_eq!(Errno::from_ret(bind(other, PORT)), Some(Errno::EADDRINUSE));
+    // port 为 0 时由内核分配一个空闲端口
+    assert_eq!(bind(other, 0), 0);
+
+    // 地址指针无效时返回 EFAULT 而不是让内核崩溃
+    let data = b"hello udp";
+    let ret = sys_send_to(fd, data.as_ptr() as usize, data.len(), BAD_PTR);
+    assert_eq!(Errno::from_ret(ret), Some(Errno::EFAULT));
+    let mut buf = [0u8; 32];
+    let ret = sys_recv_from(fd, buf.as_mut_ptr() as usize, buf.len(), BAD_PTR, 0, 0);
+    assert_eq!(Errno::from_ret(ret), Some(Errno::EFAULT));
+
+    // 发往网关的数据报放进发送队列之后立即返回
+    let mut config = IfConfig::default();
+    assert_eq!(get_ifconfig(&mut config), 0);
+    let gateway = SockAddr::new(config.gateway, PORT);
+    assert_eq!(sendto(other, data, &gateway), data.len() as isize);
+
+    close(other);
+    close(fd);
+    println!("udp_test passed!");
+    0
+}
@@ -0,0 +1,65 @@
+//! 程序参数与环境变量
+//!
+//! 内核在 exec 和 spawn 时把 argv、envp 两个以空指针结尾的字符串数组放在新的用户栈上，
+//! `_start` 收到 argc、argv、envp 之后调用 `init` 解析一次，之后可以在任何地方读取。
+
+use alloc::vec::Vec;
+use core::str::FromStr;
+use spin::Once;
+
+static ARGS: Once<Vec<&'static str>> = Once::new();
+static ENVS: Once<Vec<&'static str>> = Once::new();
+
+/// 解析以空指针结尾的 C 字符串数组，`ptr` 为 0 时表示空数组
+unsafe fn parse_str_array(ptr: usize) -> Vec<&'static str> {
+    let mut v = Vec::new();
+    if ptr == 0 {
+        return v;
+    }
+    let mut array = ptr as *const usize;
+    while *array != 0 {
+        let start = *array as *const u8;
+        let mut len = 0;
+        while *start.add(len) != 0 {
+            len += 1;
+        }
+        v.push(core::str::from_utf8(core::slice::from_raw_parts(start, len)).unwrap());
+        array = array.add(1);
+    }
+    v
+}
+
+pub(crate) fn init(argc: usize, argv: usize, envp: usize) {
+    ARGS.call_once(|| {
+        let args = unsafe { parse_str_array(argv) };
+        assert_eq!(args.len(), argc);
+        args
+    });
+    ENVS.call_once(|| unsafe { parse_str_array(envp) });
+}
+
+/// 全部参数，第一个参数为程序名
+pub fn args() -> &'static [&'static str] {
+    ARGS.get().map(|args| args.as_slice()).unwrap_or(&[])
+}
+
+/// 第 index 个参数解析为 T，参数不存在时返回 default，格式错误时 panic
+pub fn arg_or<T: FromStr>(index: usize, default: T) -> T {
+    match args().get(index) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| panic!("invalid argument {}: {}", index, arg)),
+        None => default,
+    }
+}
+
+/// 全部环境变量，格式为 `KEY=VALUE`
+pub fn envs() -> &'static [&'static str] {
+    ENVS.get().map(|envs| envs.as_slice()).unwrap_or(&[])
+}
+
+/// 读取环境变量 key 的值
+pub fn env(key: &str) -> Option<&'static str> {
+    envs().iter().find_map(|env| {
+        let (k, v) = env.split_once('=')?;
+        if k == key { Some(v) } else { None }
+    })
+}
@@ -8,12 +8,15 @@ pub mod console;
 #[macro_use]
 extern crate syscall;
 mod lang_items;
+pub mod env;
 pub mod trace;
 pub mod trap;
+pub mod signal;
 pub mod user_uart;
 pub mod matrix;
 
 extern crate alloc;
+use alloc::boxed::Box;
 use core::future::Future;
 use core::pin::Pin;
 use core::task::{Context, Poll};
@@ -24,7 +27,10 @@ use riscv::register::mtvec::TrapMode;
 use riscv::register::{uie, utvec};
 
 
+pub use env::{arg_or, args, env, envs};
 pub use trap::{UserTrapContext, UserTrapQueue, UserTrapRecord};
+pub use signal::{signal, signal_default, signal_ignore, SignalHandler};
+pub use lib_so::{JoinError, JoinHandle};
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
@@ -33,8 +39,7 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() {
-// pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) {
     extern "C" {
         fn __alltraps_u();
     }
@@ -42,6 +47,7 @@ pub extern "C" fn _start() {
         utvec::write(__alltraps_u as usize, TrapMode::Direct);
     }
     heap::init();
+    env::init(argc, argv, envp);
     lib_so::spawn(move || async{ main(); }, lib_so::PRIO_NUM - 1, getpid() as usize + 1, lib_so::CoroutineKind::UserNorm);
 }
 
@@ -60,9 +66,11 @@ pub fn add_virtual_core() {
     lib_so::add_virtual_core();
 }
 
-pub fn spawn<F, T>(f: F, prio: usize) -> usize 
+/// 添加协程，返回的 JoinHandle 可以在另一个协程中 await，也可以在线程中 join
+pub fn spawn<F, T>(f: F, prio: usize) -> JoinHandle<T::Output>
     where F: FnOnce() -> T,
-    T: Future<Output = ()> + 'static + Send + Sync {
+    T: Future + 'static + Send + Sync,
+    T::Output: Send + 'static {
     lib_so::spawn(f, prio, sys_get_pid() as usize + 1, lib_so::CoroutineKind::UserNorm)
 }
 
@@ -70,6 +78,16 @@ pub fn get_pending_status(cid: usize) -> bool {
     lib_so::get_pending_status(cid)
 }
 
+/// 取消协程，协程的 future 被丢弃，内核中为它注册的异步操作和定时器也一并取消；
+/// 正在执行的协程在这次执行返回之后才被丢弃，这次执行中注册的异步操作届时再取消。协程不存在时返回 false
+pub fn cancel(cid: usize) -> bool {
+    let found = lib_so::cancel(cid, getpid() as usize + 1);
+    if found {
+        cancel_async(cid);
+    }
+    found
+}
+
 pub struct AwaitHelper {
     flag: bool,
 }
@@ -124,6 +142,54 @@ impl Future for TimerHelper {
 }
 
 
+/// `timeout` 等待超时
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Elapsed;
+
+/// 带超时的 future，由 `timeout` 创建
+pub struct Timeout<F> {
+    future: Pin<Box<F>>,
+    deadline: usize,
+    timer_set: bool,
+}
+
+/// 等待 future 至多 duration 毫秒，超时之后 future 被丢弃，返回 Err(Elapsed)。
+/// 超时通过 set_timer 唤醒当前协程，需要先调用 init_user_trap；只能在协程内部使用
+pub fn timeout<F: Future>(duration: usize, future: F) -> Timeout<F> {
+    Timeout {
+        future: Box::pin(future),
+        deadline: get_time() as usize + duration,
+        timer_set: false,
+    }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // 被定时器唤醒时不能再 poll future，AsyncCall 会把任何一次唤醒当成系统调用完成
+        let result = if (get_time() as usize) >= self.deadline {
+            Err(Elapsed)
+        } else if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            Ok(output)
+        } else {
+            if !self.timer_set {
+                set_timer!((self.deadline * 1000) as isize, current_cid());
+                self.timer_set = true;
+            }
+            return Poll::Pending;
+        };
+        // 超时的时候撤销 future 在内核中未完成的异步操作以及定时器，
+        // 完成的时候只撤销定时器，协程的其他异步操作不受影响
+        if result.is_err() {
+            cancel_async(current_cid());
+        } else if self.timer_set {
+            cancel_timer(current_cid());
+        }
+        Poll::Ready(result)
+    }
+}
+
 #[linkage = "weak"]
 #[no_mangle]
 fn main() -> i32 {
@@ -0,0 +1,69 @@
+//! 信号处理函数的注册与分发
+//!
+//! 没有调用 `init_user_trap` 的进程，由内核在主线程返回用户态时在用户栈上构造信号帧，
+//! 处理函数返回到 `__sigreturn_trampoline`，由它调用 sigreturn 恢复被打断的上下文；
+//! 调用过 `init_user_trap` 的进程，信号作为 cause 为 2 的记录放入用户态中断队列，
+//! 在用户态中断处理线程中调用处理函数。
+
+use core::arch::global_asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use syscall::{sigaction, SignalAction, SignalFlags, MAX_SIG, SIG_DFL, SIG_IGN};
+
+// 不能改变 sp，内核在 sp 处读取信号帧
+global_asm!(
+    "    .globl __sigreturn_trampoline",
+    "__sigreturn_trampoline:",
+    "    li a7, 139",
+    "    ecall",
+);
+
+extern "C" {
+    fn __sigreturn_trampoline();
+}
+
+/// 信号处理函数，参数为信号编号
+pub type SignalHandler = extern "C" fn(signum: usize);
+
+#[allow(clippy::declare_interior_mutable_const)]
+const DEFAULT_HANDLER: AtomicUsize = AtomicUsize::new(SIG_DFL);
+static HANDLERS: [AtomicUsize; MAX_SIG + 1] = [DEFAULT_HANDLER; MAX_SIG + 1];
+
+fn set_action(signum: usize, handler: usize, mask: SignalFlags) -> isize {
+    let action = SignalAction {
+        handler,
+        mask,
+        restorer: __sigreturn_trampoline as usize,
+    };
+    let ret = sigaction(signum, Some(&action), None);
+    if ret == 0 {
+        HANDLERS[signum].store(handler, Ordering::SeqCst);
+    }
+    ret
+}
+
+/// 设置信号的处理函数，处理函数运行期间该信号与 mask 中的信号被屏蔽
+pub fn signal(signum: usize, handler: SignalHandler, mask: SignalFlags) -> isize {
+    set_action(signum, handler as usize, mask)
+}
+
+/// 忽略信号
+pub fn signal_ignore(signum: usize) -> isize {
+    set_action(signum, SIG_IGN, SignalFlags::empty())
+}
+
+/// 恢复信号的默认处理方式
+pub fn signal_default(signum: usize) -> isize {
+    set_action(signum, SIG_DFL, SignalFlags::empty())
+}
+
+/// 调用 `signal` 注册的处理函数，由用户态中断处理线程使用
+pub fn dispatch(signum: usize) {
+    let handler = match HANDLERS.get(signum) {
+        Some(handler) => handler.load(Ordering::SeqCst),
+        None => return,
+    };
+    if handler != SIG_DFL && handler != SIG_IGN {
+        let handler: SignalHandler = unsafe { core::mem::transmute(handler) };
+        handler(signum);
+    }
+}
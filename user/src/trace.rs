@@ -37,7 +37,14 @@ pub const SEND_IPI_EXIT: usize = 0x5b1c_1000;
 // misc
 pub const TRACE_TEST: usize = 0x315c_0000;
 
-pub const MEMORY_END: usize = 0x101000000;
+/// 内核把 trace 缓冲区的地址写在 heap 地址之后，缓冲区的位置取决于内存大小
+#[cfg(feature = "board_lrv")]
+const TRACE_BUFFER_PTR: usize = crate::trap::UNFI_SCHE_BUFFER + core::mem::size_of::<usize>();
+
+#[cfg(feature = "board_lrv")]
+fn trace_buffer() -> usize {
+    unsafe { (TRACE_BUFFER_PTR as *const usize).read_volatile() }
+}
 
 core::arch::global_asm!(include_str!("trace.asm"));
 
@@ -63,7 +70,7 @@ pub fn push_trace(event_id: usize) -> usize {
         sd {cy}, 1*8({tail})",
         eid = in(reg) event_id,
         step = in(reg) 16,
-        mem_end = in(reg) MEMORY_END,
+        mem_end = in(reg) trace_buffer(),
         cy = out(reg) cycle,
         tail = out(reg) _,
         eid_ext = out(reg) _,
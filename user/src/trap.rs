@@ -82,6 +82,8 @@ pub fn user_trap_handler(cx: &mut UserTrapContext) -> &mut UserTrapContext {
                     timer_intr_handler(msg);
                 } else if cause == 1 {
                     wake_handler(msg);
+                } else if cause == 2 {
+                    signal_handler(msg);
                 }
             }
             // push_trace(TRAP_QUEUE_EXIT);
@@ -139,9 +141,13 @@ pub fn timer_intr_handler(time_us: usize) {
 
 #[linkage = "weak"]
 #[no_mangle]
-pub fn wake_handler(tid: usize) {
-    println!(
-        "[user trap default] need to handle wake event: {}",
-        tid
-    );
+pub fn wake_handler(cid: usize) {
+    // 内核唤醒的协程，重复唤醒或者协程已经结束时 re_back 不做处理
+    crate::re_back(cid);
+}
+
+#[linkage = "weak"]
+#[no_mangle]
+pub fn signal_handler(signum: usize) {
+    crate::signal::dispatch(signum);
 }